@@ -1,15 +1,36 @@
-mod bluetooth;
+pub mod bluetooth;
+pub mod capture;
+mod classifier;
+pub mod database;
+pub mod diagnostics;
+mod discovery;
+mod driver;
+mod eightbitdo;
+pub mod gamepad;
 mod generic;
-mod nintendo;
+pub mod input;
+pub mod nintendo;
 mod playstation;
+mod power_supply;
+mod report;
+pub mod report_descriptor;
+#[cfg(test)]
+mod test_bus;
+pub mod transport;
+#[cfg(test)]
+mod uhid;
+pub mod upower;
+mod valve;
+mod wiimote;
 mod xbox;
+use std::{path::PathBuf, time::Duration};
+
 use anyhow::Result;
-use hidapi::HidApi;
 use log::debug;
-use udev::Enumerator;
-use std::collections::HashSet;
 
-use crate::controller::{Controller, Status};
+use crate::controller::Controller;
+
+use self::transport::{HidApiTransport, HidTransport};
 
 pub async fn controllers_async() -> Result<Vec<Controller>> {
     // Spawn a tokio blocking task because `get_controllers()` is a blocking API
@@ -18,7 +39,10 @@ pub async fn controllers_async() -> Result<Vec<Controller>> {
 }
 
 pub fn controllers() -> Result<Vec<Controller>> {
-    let hidapi = HidApi::new()?;
+    controllers_from(&HidApiTransport::new()?)
+}
+
+fn controllers_from(transport: &dyn HidTransport) -> Result<Vec<Controller>> {
     let mut controllers: Vec<Controller> = Vec::new();
 
     // If in debug mode, check if there is a fake controller in /tmp/fake_controller.json
@@ -26,170 +50,51 @@ pub fn controllers() -> Result<Vec<Controller>> {
         parse_fake_controller(&mut controllers);
     }
 
-    // HidApi will return 2 copies of the device when the Nintendo Pro Controller is connected via USB.
-    // It will additionally return a 3rd device when the controller is connected via Bluetooth + USB.
-    let nintendo_pro_controllers: Vec<_> = hidapi
-        .device_list()
-        .filter(|device_info| {
-            device_info.vendor_id() == nintendo::VENDOR_ID_NINTENDO
-                && device_info.product_id() == nintendo::PRODUCT_ID_NINTENDO_PROCON
-        })
-        .collect();
-
-    if nintendo_pro_controllers.len() == 1 || nintendo_pro_controllers.len() == 2 {
-        // When we only get one device, we know it's connected via Bluetooth.
-        // When we get two devices, we know it's connected only via USB. Both will report the same data, so we'll just return the first one.
-        let controller = nintendo::parse_controller_data(nintendo_pro_controllers[0], &hidapi)?;
-        controllers.push(controller);
-    } else if nintendo_pro_controllers.len() == 3 {
-        // When we get three devices, we know it's connected via USB + Bluetooth.
-        // We'll only return the Bluetooth device because the USB devices will not report any data.
-        let bt_controller = nintendo_pro_controllers
-            .iter()
-            .find(|device_info| device_info.interface_number() == -1);
-
-        if let Some(bt_controller) = bt_controller {
-            let controller = nintendo::parse_controller_data(bt_controller, &hidapi)?;
-            controllers.push(controller);
-        }
-    }
+    controllers.extend(discovery::discover(transport)?);
 
-    let nintendo_non_pro_controllers: Vec<_> = hidapi
-        .device_list()
-        .filter(|device_info| {
-            device_info.vendor_id() == nintendo::VENDOR_ID_NINTENDO
-                && device_info.product_id() != nintendo::PRODUCT_ID_NINTENDO_PROCON
-        })
-        .collect();
-    for device_info in nintendo_non_pro_controllers {
-        let controller = nintendo::parse_controller_data(device_info, &hidapi)?;
-        controllers.push(controller);
-    }
-
-    // for some reason HidApi's list_devices() is returning multiple instances of the same controller
-    // so dedupe by serial number
-    let mut xbox_controllers: Vec<_> = hidapi
-        .device_list()
-        .filter(|device_info| {
-            device_info.vendor_id() == xbox::MS_VENDOR_ID
-                && (device_info.product_id() == xbox::XBOX_ONE_S_CONTROLLER_BT_PRODUCT_ID
-                    || device_info.product_id() == xbox::XBOX_ONE_S_LATEST_FW_PRODUCT_ID
-                    || device_info.product_id() == xbox::XBOX_WIRELESS_CONTROLLER_USB_PRODUCT_ID
-                    || device_info.product_id() == xbox::XBOX_WIRELESS_CONTROLLER_BT_PRODUCT_ID
-                    || device_info.product_id() == xbox::XBOX_WIRELESS_ELITE_CONTROLLER_USB_PRODUCT_ID
-                    || device_info.product_id() == xbox::XBOX_WIRELESS_ELITE_CONTROLLER_BT_PRODUCT_ID
-                    || device_info.product_id() == xbox::XBOX_WIRELESS_ELITE_CONTROLLER_BTLE_PRODUCT_ID)
-        })
-        .collect();
-    xbox_controllers.dedup_by(|a, b| a.serial_number() == b.serial_number());
-    for device_info in xbox_controllers {
-        match (device_info.vendor_id(), device_info.product_id()) {
-            (xbox::MS_VENDOR_ID, xbox::XBOX_ONE_S_CONTROLLER_BT_PRODUCT_ID) => {
-                debug!("!Found Xbox One S controller: {:?}", device_info);
-                let controller = xbox::parse_xbox_controller_data(device_info, &hidapi)?;
-                controllers.push(controller);
-            }
-            (xbox::MS_VENDOR_ID, xbox::XBOX_ONE_S_LATEST_FW_PRODUCT_ID) => {
-                debug!("Found Xbox One S controller: {:?}", device_info);
-                let controller = xbox::parse_xbox_controller_data(device_info, &hidapi)?;
-
-                controllers.push(controller);
-            }
-            (xbox::MS_VENDOR_ID, xbox::XBOX_WIRELESS_CONTROLLER_BT_PRODUCT_ID) => {
-                debug!("Found Xbox Series X/S controller: {:?}", device_info);
-                let controller = xbox::parse_xbox_controller_data(device_info, &hidapi)?;
-                controllers.push(controller);
-            }
-            (xbox::MS_VENDOR_ID, xbox::XBOX_WIRELESS_ELITE_CONTROLLER_BT_PRODUCT_ID) => {
-                debug!("Found Xbox Elite 2 controller: {:?}", device_info);
-                let controller = xbox::parse_xbox_controller_data(device_info, &hidapi)?;
-                controllers.push(controller);
-            }
-            (xbox::MS_VENDOR_ID, xbox::XBOX_WIRELESS_ELITE_CONTROLLER_BTLE_PRODUCT_ID) => {
-                debug!("Found Xbox Elite 2 controller: {:?}", device_info);
-                let controller = xbox::parse_xbox_controller_data(device_info, &hidapi)?;
-                controllers.push(controller);
-            }
-            _ => {}
-        }
-    }
-
-    let mut unique_devices: Vec<_> = hidapi.device_list().collect();
-    unique_devices.dedup_by(|a, b| a.serial_number() == b.serial_number());
-    for device_info in unique_devices {
-        match (device_info.vendor_id(), device_info.product_id()) {
-            (playstation::DS_VENDOR_ID, playstation::DS3_PRODUCT_ID) => {
-                debug!("Found DualShock3 controller: {:?}", device_info);
-                let controller = playstation::parse_dualshock3_controller_data(
-                    device_info,
-                    &hidapi,
-                    "DualShock3",
-                )?;
-
-                controllers.push(controller);
-            }
-            (playstation::DS_VENDOR_ID, playstation::DS_PRODUCT_ID) => {
-                debug!("Found DualSense controller: {:?}", device_info);
-                let controller = playstation::parse_dualsense_controller_data(
-                    device_info,
-                    &hidapi,
-                    "DualSense",
-                )?;
-
-                controllers.push(controller);
-            }
-            (playstation::DS_VENDOR_ID, playstation::DS_EDGE_PRODUCT_ID) => {
-                debug!("Found DualSense Edge controller: {:?}", device_info);
-                let controller = playstation::parse_dualsense_controller_data(
-                    device_info,
-                    &hidapi,
-                    "DualSense Edge",
-                )?;
-
-                controllers.push(controller);
-            }
-            (playstation::DS_VENDOR_ID, playstation::DS4_NEW_PRODUCT_ID) => {
-                debug!("Found new DualShock 4 controller: {:?}", device_info);
-                let controller =
-                    playstation::parse_dualshock_controller_data(device_info, &hidapi)?;
-
-                controllers.push(controller);
-            }
-            (playstation::DS_VENDOR_ID, playstation::DS4_OLD_PRODUCT_ID) => {
-                debug!("Found old DualShock 4 controller: {:?}", device_info);
-                let controller =
-                    playstation::parse_dualshock_controller_data(device_info, &hidapi)?;
+    Ok(controllers)
+}
 
-                controllers.push(controller);
-            }
-            _ => {}
+/// Forget the devices of a controller that left the inventory, and of each of its parts
+pub fn forget(controller: &Controller) {
+    for controller in std::iter::once(controller).chain(&controller.parts) {
+        if let Some(device_path) = controller.device_path.as_deref() {
+            forget_device(device_path);
         }
     }
+}
 
-    let mut enumerator = Enumerator::new()?;
-    enumerator.match_subsystem("input")?;
-
-    let mut controllers = Vec::new();
-    let mut seen_gips = HashSet::new();
-
-    for device in enumerator.scan_devices()? {
-        let mut controller = Controller::from_udev(&device, "Unknown Controller", 0, Status::Unknown, false);
+/// Forget what the drivers cached about a device that was removed, e.g. "/dev/hidraw5"
+pub fn forget_device(device_path: &str) {
+    driver::forget(device_path);
+}
 
-        // Only include records where gip starts with "gip" or "input" and exclude "gip0.1"
-        if !(controller.gip.starts_with("gip") || controller.gip.starts_with("input")) || controller.gip == "gip0.1" {
-            continue;
-        }
+pub async fn refresh_async(controllers: Vec<Controller>) -> Result<Vec<Controller>> {
+    let controllers =
+        tokio::task::spawn_blocking(move || discovery::refresh(&controllers)).await??;
+    Ok(controllers)
+}
 
-        // Deduplicate based on 'gip'
-        if seen_gips.insert(controller.gip.clone()) {
-            if xbox::is_xbox_controller(controller.vendor_id) {
-                xbox::update_xbox_controller(&mut controller, false);
-                controllers.push(controller);
-            }
-        }
-    }
+pub async fn capture_async(
+    selector: capture::DeviceSelector,
+    duration: Duration,
+    directory: PathBuf,
+) -> Result<PathBuf> {
+    let path = tokio::task::spawn_blocking(move || {
+        capture::capture(&HidApiTransport::new()?, &selector, duration, &directory)
+    })
+    .await??;
+    Ok(path)
+}
 
-    Ok(controllers)
+pub async fn nintendo_info_async(
+    selector: capture::DeviceSelector,
+) -> Result<nintendo::protocol::DeviceInfo> {
+    let info = tokio::task::spawn_blocking(move || {
+        nintendo::device_info(&HidApiTransport::new()?, &selector)
+    })
+    .await??;
+    Ok(info)
 }
 
 fn parse_fake_controller(controllers: &mut Vec<Controller>) {
@@ -209,3 +114,251 @@ fn parse_fake_controller(controllers: &mut Vec<Controller>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::controllers_from;
+    use super::discovery::refresh_with;
+    use super::transport::fixture::Fixture;
+    use super::transport::replay::ReplayTransport;
+    use crate::controller::{Controller, Status};
+
+    const FIXTURES: [&str; 12] = [
+        "dualsense_usb",
+        "dualsense_bt",
+        "ds4_usb",
+        "ds4_bt",
+        "ds3_usb",
+        "pro_controller_bt",
+        "xbox_series_bt",
+        "generic_bt",
+        "8bitdo_pro2_bt",
+        "steam_controller_dongle",
+        "steam_controller_dongle_empty",
+        "wii_remote_bt",
+    ];
+
+    fn summary(controllers: &[Controller]) -> Vec<(String, &str, bool, u8, Status)> {
+        let mut summary: Vec<_> = controllers
+            .iter()
+            // Leave out whatever real controllers the machine running the tests has
+            .filter(|controller| controller.id().starts_with("/dev/replay/"))
+            .map(|controller| {
+                (
+                    controller.id(),
+                    controller.name.as_str(),
+                    controller.bluetooth,
+                    controller.capacity,
+                    controller.status.clone(),
+                )
+            })
+            .collect();
+        summary.sort_by(|a, b| a.0.cmp(&b.0));
+        summary
+    }
+
+    fn entry(
+        id: &str,
+        name: &'static str,
+        bluetooth: bool,
+        capacity: u8,
+        status: Status,
+    ) -> (String, &'static str, bool, u8, Status) {
+        (id.to_string(), name, bluetooth, capacity, status)
+    }
+
+    #[test]
+    fn test_controllers_from_fixtures() {
+        let transport =
+            ReplayTransport::new(FIXTURES.iter().map(|name| Fixture::named(name)).collect());
+
+        let controllers = controllers_from(&transport).unwrap();
+        assert_eq!(
+            summary(&controllers),
+            vec![
+                entry(
+                    "/dev/replay/8bitdo_pro2_bt",
+                    "8BitDo Pro 2 (D-input)",
+                    true,
+                    90,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/ds3_usb",
+                    "DualShock3",
+                    false,
+                    50,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/ds4_bt",
+                    "DualShock 4",
+                    true,
+                    85,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/ds4_usb",
+                    "DualShock 4",
+                    false,
+                    55,
+                    Status::Charging
+                ),
+                entry(
+                    "/dev/replay/dualsense_bt",
+                    "DualSense",
+                    true,
+                    35,
+                    Status::Charging
+                ),
+                entry(
+                    "/dev/replay/dualsense_usb",
+                    "DualSense",
+                    false,
+                    65,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/generic_bt",
+                    "Bluetooth Gamepad",
+                    true,
+                    75,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/pro_controller_bt",
+                    "Pro Controller",
+                    true,
+                    75,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/steam_controller_dongle",
+                    "Steam Controller #1",
+                    true,
+                    75,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/wii_remote_bt",
+                    "Wii Remote",
+                    true,
+                    75,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/xbox_series_bt",
+                    "Xbox Series X/S",
+                    true,
+                    70,
+                    Status::Discharging
+                ),
+            ]
+        );
+
+        // The next poll reads the next recorded report of every controller
+        let controllers = refresh_with(&transport, &controllers).unwrap();
+        assert_eq!(
+            summary(&controllers),
+            vec![
+                entry(
+                    "/dev/replay/8bitdo_pro2_bt",
+                    "8BitDo Pro 2 (D-input)",
+                    true,
+                    80,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/ds3_usb",
+                    "DualShock3",
+                    false,
+                    25,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/ds4_bt",
+                    "DualShock 4",
+                    true,
+                    75,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/ds4_usb",
+                    "DualShock 4",
+                    false,
+                    65,
+                    Status::Charging
+                ),
+                entry(
+                    "/dev/replay/dualsense_bt",
+                    "DualSense",
+                    true,
+                    45,
+                    Status::Charging
+                ),
+                entry(
+                    "/dev/replay/dualsense_usb",
+                    "DualSense",
+                    false,
+                    55,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/generic_bt",
+                    "Bluetooth Gamepad",
+                    true,
+                    25,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/pro_controller_bt",
+                    "Pro Controller",
+                    true,
+                    50,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/steam_controller_dongle",
+                    "Steam Controller #1",
+                    true,
+                    25,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/wii_remote_bt",
+                    "Wii Remote",
+                    true,
+                    25,
+                    Status::Discharging
+                ),
+                entry(
+                    "/dev/replay/xbox_series_bt",
+                    "Xbox Series X/S",
+                    true,
+                    40,
+                    Status::Discharging
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_controllers_without_reports() {
+        // A controller that stops sending reports is still listed, with an unknown battery
+        let mut fixture = Fixture::named("dualsense_usb");
+        fixture.reports.clear();
+        let transport = ReplayTransport::new(vec![fixture]);
+
+        let controllers = controllers_from(&transport).unwrap();
+        assert_eq!(
+            summary(&controllers),
+            vec![entry(
+                "/dev/replay/dualsense_usb",
+                "DualSense",
+                false,
+                0,
+                Status::Unknown
+            )]
+        );
+    }
+}
@@ -1,20 +1,43 @@
-use anyhow::Result;
-use hidapi::DeviceInfo;
+use anyhow::{anyhow, Result};
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{
+    ObjectManagerInterfacesAdded, ObjectManagerInterfacesRemoved, Properties,
+    PropertiesPropertiesChanged,
+};
+use dbus::blocking::Connection;
+use dbus::message::SignalArgs;
+use dbus::Path as DBusPath;
+use log::{debug, error, info};
+use std::collections::HashMap;
 use std::io::BufRead;
-use std::{fs::File, io, path::Path, process::Command};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use std::{fs::File, io, path::Path};
 
-/// Get the bluetooth address from the DeviceInfo's hidraw,
+use super::transport::HidDevice;
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const BLUEZ_ROOT: &str = "/org/bluez";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+const BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+const DBUS_TIMEOUT: Duration = Duration::from_millis(5000);
+
+// Battery levels pushed by BlueZ, keyed by lowercase address. Only populated while
+// `watch_batteries` is running, so it is never stale.
+static BATTERY_CACHE: OnceLock<Mutex<HashMap<String, u8>>> = OnceLock::new();
+
+/// Get the bluetooth address from the device's hidraw,
 /// e.g. "/sys/class/hidraw/hidraw5/device/uevent".
 /// This file contains the BT address as value of HID_UNIQ
-pub fn get_bluetooth_address(device_info: &DeviceInfo) -> Result<String> {
+pub fn get_bluetooth_address(device: &HidDevice) -> Result<String> {
     let mut bt_address = "".to_string();
-    let hidraw_path = device_info.path().to_str()?;
+    let hidraw_path = &device.path;
     let prefix = hidraw_path.replace("/dev", "/sys/class/hidraw");
     let path = [prefix, "device/uevent".to_string()].join("/");
     let lines = read_lines(path)?;
     for line in lines {
         let val = line?;
-        // HID_UNIQ points to the BT address we want to use to grab data from bluetoothctl
+        // HID_UNIQ points to the BT address we want to use to grab data from BlueZ
         if val.starts_with("HID_UNIQ") {
             if let Some(address) = val.split('=').nth(1) {
                 bt_address = address.to_string();
@@ -24,26 +47,208 @@ pub fn get_bluetooth_address(device_info: &DeviceInfo) -> Result<String> {
     Ok(bt_address)
 }
 
-/// For Xbox controllers, "bluetoothctl info <address>" will return info about the controller
-/// including its battery percentage. This important output is:
-/// "Battery Percentage: 0x42 (66)"
+/// For Xbox and generic controllers, BlueZ exposes the battery level reported over the
+/// Bluetooth Battery Service as `org.bluez.Battery1.Percentage`.
 pub fn get_battery_percentage(address: String) -> Result<u8> {
-    let mut percentage = 0;
-    let output = Command::new("bluetoothctl")
-        .args(["info", address.as_str()])
-        .output()?;
-    let content = String::from_utf8_lossy(&output.stdout).to_string();
-    for bt_line in content.lines() {
-        if bt_line.contains("Battery Percentage") {
-            // format is: "Battery Percentage: 0x42 (66)"
-            if let Some(percentage_hex) = bt_line.split(' ').nth(2) {
-                if let Ok(pct) = i64::from_str_radix(&percentage_hex[2..], 16) {
-                    percentage = pct as u8;
+    if let Some(percentage) = cached_battery_percentage(&address) {
+        return Ok(percentage);
+    }
+
+    let bluez = Bluez::new()?;
+    let device = bluez
+        .find_device(&address)?
+        .ok_or_else(|| anyhow!("{} is not known to BlueZ", address))?;
+    bluez.battery_percentage(&device.path)
+}
+
+fn cached_battery_percentage(address: &str) -> Option<u8> {
+    let cache = BATTERY_CACHE.get()?.lock().ok()?;
+    cache.get(&address.to_lowercase()).copied()
+}
+
+/// A Bluetooth device as exposed by BlueZ's `org.bluez.Device1` and `org.bluez.Battery1`
+#[derive(Debug, PartialEq)]
+pub struct BluezDevice {
+    pub path: String,
+    pub address: String,
+    pub name: Option<String>,
+    pub connected: bool,
+    pub battery_percentage: Option<u8>,
+}
+
+impl BluezDevice {
+    fn from_interfaces(path: &DBusPath, interfaces: &HashMap<String, PropMap>) -> Option<Self> {
+        let device = interfaces.get(DEVICE_INTERFACE)?;
+        Some(Self {
+            path: path.to_string(),
+            address: prop_cast::<String>(device, "Address")?.clone(),
+            name: prop_cast::<String>(device, "Name").cloned(),
+            connected: prop_cast::<bool>(device, "Connected")
+                .copied()
+                .unwrap_or(false),
+            battery_percentage: interfaces
+                .get(BATTERY_INTERFACE)
+                .and_then(|battery| prop_cast::<u8>(battery, "Percentage"))
+                .copied(),
+        })
+    }
+}
+
+/// Minimal BlueZ D-Bus client
+pub struct Bluez {
+    connection: Connection,
+}
+
+impl Bluez {
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_connection(Connection::new_system()?))
+    }
+
+    pub fn from_connection(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    pub fn devices(&self) -> Result<Vec<BluezDevice>> {
+        let proxy = self.connection.with_proxy(BLUEZ_SERVICE, "/", DBUS_TIMEOUT);
+        let (objects,): (HashMap<DBusPath<'static>, HashMap<String, PropMap>>,) = proxy
+            .method_call(
+                "org.freedesktop.DBus.ObjectManager",
+                "GetManagedObjects",
+                (),
+            )?;
+
+        Ok(objects
+            .iter()
+            .filter_map(|(path, interfaces)| BluezDevice::from_interfaces(path, interfaces))
+            .collect())
+    }
+
+    pub fn find_device(&self, address: &str) -> Result<Option<BluezDevice>> {
+        Ok(self
+            .devices()?
+            .into_iter()
+            .find(|device| device.address.eq_ignore_ascii_case(address)))
+    }
+
+    pub fn battery_percentage(&self, device_path: &str) -> Result<u8> {
+        let proxy = self
+            .connection
+            .with_proxy(BLUEZ_SERVICE, device_path, DBUS_TIMEOUT);
+        Ok(proxy.get::<u8>(BATTERY_INTERFACE, "Percentage")?)
+    }
+
+    /// Follow battery level changes pushed by BlueZ, calling `on_change` with the device
+    /// address and its new percentage. Never returns unless the connection fails.
+    pub fn watch_batteries<F>(&self, on_change: F) -> Result<()>
+    where
+        F: Fn(&str, Option<u8>) + Send + Sync + 'static,
+    {
+        let on_change = std::sync::Arc::new(on_change);
+
+        let rule =
+            PropertiesPropertiesChanged::match_rule(None, None).with_namespaced_path(BLUEZ_ROOT);
+        let callback = on_change.clone();
+        self.connection.add_match(
+            rule,
+            move |signal: PropertiesPropertiesChanged, _: &Connection, message| {
+                if signal.interface_name != BATTERY_INTERFACE {
+                    return true;
                 }
-            }
+                let percentage = prop_cast::<u8>(&signal.changed_properties, "Percentage");
+                if let (Some(path), Some(percentage)) = (message.path(), percentage) {
+                    if let Some(address) = address_from_path(&path) {
+                        callback(&address, Some(*percentage));
+                    }
+                }
+                true
+            },
+        )?;
+
+        let rule = ObjectManagerInterfacesAdded::match_rule(None, None);
+        let callback = on_change.clone();
+        self.connection.add_match(
+            rule,
+            move |signal: ObjectManagerInterfacesAdded, _: &Connection, _: &_| {
+                if let Some(device) =
+                    BluezDevice::from_interfaces(&signal.object, &signal.interfaces)
+                {
+                    if let Some(percentage) = device.battery_percentage {
+                        callback(&device.address, Some(percentage));
+                    }
+                }
+                true
+            },
+        )?;
+
+        let rule = ObjectManagerInterfacesRemoved::match_rule(None, None);
+        let callback = on_change;
+        self.connection.add_match(
+            rule,
+            move |signal: ObjectManagerInterfacesRemoved, _: &Connection, _: &_| {
+                if signal.interfaces.iter().any(|i| i == BATTERY_INTERFACE) {
+                    if let Some(address) = address_from_path(&signal.object) {
+                        callback(&address, None);
+                    }
+                }
+                true
+            },
+        )?;
+
+        loop {
+            self.connection.process(Duration::from_secs(60))?;
         }
     }
-    Ok(percentage)
+}
+
+/// BlueZ device paths look like /org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF
+fn address_from_path(path: &str) -> Option<String> {
+    let device = path.rsplit('/').next()?.strip_prefix("dev_")?;
+    Some(device.replace('_', ":"))
+}
+
+/// Keep a cache of the battery levels BlueZ pushes, calling `on_change` after every update.
+/// Runs on its own thread since the blocking D-Bus connection needs to be polled.
+pub fn spawn_battery_watcher<F>(on_change: F)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let result = std::thread::Builder::new()
+        .name("bluez".to_string())
+        .spawn(move || {
+            let cache = BATTERY_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+            let result = Bluez::new().and_then(|bluez| {
+                // Seed the cache with what BlueZ already knows
+                if let Ok(mut cache) = cache.lock() {
+                    for device in bluez.devices()? {
+                        if let Some(percentage) = device.battery_percentage {
+                            cache.insert(device.address.to_lowercase(), percentage);
+                        }
+                    }
+                }
+                info!("Watching BlueZ battery levels");
+
+                bluez.watch_batteries(move |address, percentage| {
+                    debug!("BlueZ battery of {} is now {:?}", address, percentage);
+                    if let Ok(mut cache) = cache.lock() {
+                        match percentage {
+                            Some(percentage) => cache.insert(address.to_lowercase(), percentage),
+                            None => cache.remove(&address.to_lowercase()),
+                        };
+                    }
+                    on_change();
+                })
+            });
+            // The cache would go stale without the watcher
+            if let Ok(mut cache) = cache.lock() {
+                cache.clear();
+            }
+            if let Err(err) = result {
+                error!("BlueZ battery watcher stopped: {}", err);
+            }
+        });
+    if let Err(err) = result {
+        error!("Failed to spawn BlueZ thread: {}", err);
+    }
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -53,3 +258,136 @@ where
     let file = File::open(filename)?;
     Ok(io::BufReader::new(file).lines())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{address_from_path, Bluez, BluezDevice, BATTERY_INTERFACE, DEVICE_INTERFACE};
+    use crate::api::test_bus::{prop, TestBus};
+    use dbus::arg::{PropMap, Variant};
+    use dbus::blocking::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+    use dbus::blocking::Connection;
+    use dbus::channel::{MatchingReceiver, Sender};
+    use dbus::message::{MatchRule, SignalArgs};
+    use dbus::Path;
+    use std::collections::HashMap;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    const DEVICE_PATH: &str = "/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF";
+
+    /// Serve a single Xbox controller the way BlueZ would
+    fn spawn_mock_bluez(address: String) -> mpsc::Sender<u8> {
+        let (battery_tx, battery_rx) = mpsc::channel::<u8>();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let connection = Connection::new_address(&address).unwrap();
+            connection
+                .request_name("org.bluez", false, true, false)
+                .unwrap();
+            connection.start_receive(
+                MatchRule::new_method_call(),
+                Box::new(|message, connection| {
+                    let reply = match message.member().as_deref() {
+                        Some("GetManagedObjects") => {
+                            let mut device = PropMap::new();
+                            device.insert("Address".into(), prop("AA:BB:CC:DD:EE:FF".to_string()));
+                            device.insert(
+                                "Name".into(),
+                                prop("Xbox Wireless Controller".to_string()),
+                            );
+                            device.insert("Connected".into(), prop(true));
+                            let mut battery = PropMap::new();
+                            battery.insert("Percentage".into(), prop(66u8));
+                            let interfaces = HashMap::from([
+                                (DEVICE_INTERFACE.to_string(), device),
+                                (BATTERY_INTERFACE.to_string(), battery),
+                            ]);
+                            let objects = HashMap::from([(Path::from(DEVICE_PATH), interfaces)]);
+                            message.method_return().append1(objects)
+                        }
+                        Some("Get") => message.method_return().append1(Variant(55u8)),
+                        _ => message.error(
+                            &"org.freedesktop.DBus.Error.UnknownMethod".into(),
+                            &std::ffi::CString::new("unknown method").unwrap(),
+                        ),
+                    };
+                    let _ = connection.send(reply);
+                    true
+                }),
+            );
+            ready_tx.send(()).unwrap();
+
+            loop {
+                connection.process(Duration::from_millis(50)).unwrap();
+                if let Ok(percentage) = battery_rx.try_recv() {
+                    let mut changed = PropMap::new();
+                    changed.insert("Percentage".into(), prop(percentage));
+                    let signal = PropertiesPropertiesChanged {
+                        interface_name: BATTERY_INTERFACE.to_string(),
+                        changed_properties: changed,
+                        invalidated_properties: Vec::new(),
+                    };
+                    let _ = connection.send(signal.to_emit_message(&Path::from(DEVICE_PATH)));
+                }
+            }
+        });
+
+        ready_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        battery_tx
+    }
+
+    #[test]
+    fn test_bluez_client() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let battery_tx = spawn_mock_bluez(bus.address.clone());
+
+        let bluez = Bluez::from_connection(Connection::new_address(&bus.address).unwrap());
+        let device = bluez.find_device("aa:bb:cc:dd:ee:ff").unwrap().unwrap();
+        assert_eq!(
+            device,
+            BluezDevice {
+                path: DEVICE_PATH.to_string(),
+                address: "AA:BB:CC:DD:EE:FF".to_string(),
+                name: Some("Xbox Wireless Controller".to_string()),
+                connected: true,
+                battery_percentage: Some(66),
+            }
+        );
+        assert_eq!(bluez.find_device("11:22:33:44:55:66").unwrap(), None);
+        assert_eq!(bluez.battery_percentage(DEVICE_PATH).unwrap(), 55);
+
+        // Push updates
+        let (changes_tx, changes_rx) = mpsc::channel();
+        let address = bus.address.clone();
+        std::thread::spawn(move || {
+            let bluez = Bluez::from_connection(Connection::new_address(&address).unwrap());
+            let changes_tx = std::sync::Mutex::new(changes_tx);
+            let _ = bluez.watch_batteries(move |address, percentage| {
+                let _ = changes_tx
+                    .lock()
+                    .unwrap()
+                    .send((address.to_string(), percentage));
+            });
+        });
+        // Give the watcher time to register its match rules
+        std::thread::sleep(Duration::from_millis(200));
+        battery_tx.send(42).unwrap();
+        assert_eq!(
+            changes_rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            ("AA:BB:CC:DD:EE:FF".to_string(), Some(42))
+        );
+    }
+
+    #[test]
+    fn test_address_from_path() {
+        assert_eq!(
+            address_from_path(DEVICE_PATH),
+            Some("AA:BB:CC:DD:EE:FF".to_string())
+        );
+        assert_eq!(address_from_path("/org/bluez/hci0"), None);
+    }
+}
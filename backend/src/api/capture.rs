@@ -0,0 +1,219 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+
+use super::report_descriptor::{ReportDescriptor, ReportType};
+use super::transport::fixture::{Fixture, Report, ReportKind};
+use super::transport::{HidDevice, HidTransport};
+
+// Large enough for any input or feature report a controller sends
+const MAX_REPORT_SIZE: usize = 1024;
+const READ_TIMEOUT_MS: u64 = 100;
+pub const MAX_CAPTURE_DURATION: Duration = Duration::from_secs(60);
+
+/// Which HID device to capture, by hidraw path or by vendor/product ID
+#[derive(Debug, PartialEq)]
+pub enum DeviceSelector {
+    Path(String),
+    Ids(u16, u16),
+}
+
+impl DeviceSelector {
+    /// Parse "/dev/hidraw5" or "054c:0ce6"
+    pub fn parse(value: &str) -> Result<Self> {
+        if value.starts_with('/') {
+            return Ok(Self::Path(value.to_string()));
+        }
+        let (vendor_id, product_id) = value
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected a hidraw path or vendor:product, got {}", value))?;
+        Ok(Self::Ids(
+            u16::from_str_radix(vendor_id, 16)?,
+            u16::from_str_radix(product_id, 16)?,
+        ))
+    }
+
+    pub fn matches(&self, device: &HidDevice) -> bool {
+        match self {
+            Self::Path(path) => device.path == *path,
+            Self::Ids(vendor_id, product_id) => {
+                device.vendor_id == *vendor_id && device.product_id == *product_id
+            }
+        }
+    }
+
+    /// The first device of `transport` matching the selector
+    pub fn find(&self, transport: &dyn HidTransport) -> Result<HidDevice> {
+        transport
+            .devices()
+            .into_iter()
+            .find(|device| self.matches(device))
+            .ok_or_else(|| anyhow!("no HID device matches {:?}", self))
+    }
+}
+
+/// Record the raw reports of a device for `duration` into a timestamped fixture file in
+/// `directory`, for bug reports and to be replayed in tests. Returns the path of the file.
+pub fn capture(
+    transport: &dyn HidTransport,
+    selector: &DeviceSelector,
+    duration: Duration,
+    directory: &Path,
+) -> Result<PathBuf> {
+    let fixture = record(transport, selector, duration.min(MAX_CAPTURE_DURATION))?;
+
+    std::fs::create_dir_all(directory)?;
+    let path = directory.join(format!(
+        "capture-{:04x}-{:04x}-{}.json",
+        fixture.device.vendor_id,
+        fixture.device.product_id,
+        fixture.captured_at.unwrap_or_default()
+    ));
+    fixture.save(&path)?;
+    info!(
+        "Captured {} reports from {} to {}",
+        fixture.reports.len(),
+        fixture.device.path,
+        path.display()
+    );
+    Ok(path)
+}
+
+pub fn record(
+    transport: &dyn HidTransport,
+    selector: &DeviceSelector,
+    duration: Duration,
+) -> Result<Fixture> {
+    let device = selector.find(transport)?;
+    let mut connection = transport.open(&device)?;
+    let captured_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let started = Instant::now();
+    let mut reports = Vec::new();
+
+    let report_descriptor = match connection.report_descriptor() {
+        Ok(report_descriptor) => report_descriptor,
+        Err(err) => {
+            debug!(
+                "Failed to get report descriptor of {}: {}",
+                device.path, err
+            );
+            Vec::new()
+        }
+    };
+
+    let feature_report_ids = match ReportDescriptor::parse(&report_descriptor) {
+        Ok(descriptor) => descriptor.report_ids(ReportType::Feature),
+        Err(err) => {
+            debug!("Invalid report descriptor of {}: {}", device.path, err);
+            Vec::new()
+        }
+    };
+    for report_id in feature_report_ids {
+        let mut buf = [0u8; MAX_REPORT_SIZE];
+        buf[0] = report_id;
+        match connection.get_feature_report(&mut buf) {
+            Ok(len) => reports.push(Report {
+                kind: ReportKind::Feature,
+                time_ms: Some(started.elapsed().as_millis() as u64),
+                data: buf[..len].to_vec(),
+            }),
+            // Some report IDs are write-only, or stall until the device is activated
+            Err(err) => debug!("Failed to get feature report {:#04x}: {}", report_id, err),
+        }
+    }
+
+    while started.elapsed() < duration {
+        let remaining = duration.saturating_sub(started.elapsed()).as_millis() as u64;
+        let mut buf = [0u8; MAX_REPORT_SIZE];
+        let len = connection.read_timeout(&mut buf, remaining.min(READ_TIMEOUT_MS) as i32)?;
+        if len > 0 {
+            reports.push(Report {
+                kind: ReportKind::Input,
+                time_ms: Some(started.elapsed().as_millis() as u64),
+                data: buf[..len].to_vec(),
+            });
+        }
+    }
+
+    Ok(Fixture {
+        device,
+        captured_at: Some(captured_at),
+        report_descriptor,
+        reports,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{record, DeviceSelector};
+    use crate::api::transport::fixture::{Fixture, Report, ReportKind};
+    use crate::api::transport::replay::ReplayTransport;
+    use std::time::Duration;
+
+    #[test]
+    fn test_device_selector() {
+        assert_eq!(
+            DeviceSelector::parse("/dev/hidraw5").unwrap(),
+            DeviceSelector::Path("/dev/hidraw5".to_string())
+        );
+        assert_eq!(
+            DeviceSelector::parse("054c:0CE6").unwrap(),
+            DeviceSelector::Ids(0x054c, 0x0ce6)
+        );
+        assert!(DeviceSelector::parse("dualsense").is_err());
+    }
+
+    #[test]
+    fn test_record_replays() {
+        let mut fixture = Fixture::named("dualsense_usb");
+        // Report ID 5 is a feature report
+        fixture.report_descriptor = vec![0x85, 0x05, 0xb1, 0x02];
+        fixture.reports.push(Report {
+            kind: ReportKind::Feature,
+            time_ms: None,
+            data: vec![0x05, 0x01, 0x02],
+        });
+        let transport = ReplayTransport::new(vec![fixture.clone()]);
+
+        let captured = record(
+            &transport,
+            &DeviceSelector::Ids(0x054c, 0x0ce6),
+            Duration::from_millis(50),
+        )
+        .unwrap();
+        assert_eq!(captured.device, fixture.device);
+        assert_eq!(captured.report_descriptor, fixture.report_descriptor);
+        assert!(captured.captured_at.is_some());
+
+        // The capture holds the same reports and can be replayed in turn
+        let data = |fixture: &Fixture, kind: ReportKind| -> Vec<Vec<u8>> {
+            fixture
+                .reports
+                .iter()
+                .filter(|report| report.kind == kind)
+                .map(|report| report.data.clone())
+                .collect()
+        };
+        assert_eq!(
+            data(&captured, ReportKind::Input),
+            data(&fixture, ReportKind::Input)
+        );
+        assert_eq!(
+            data(&captured, ReportKind::Feature),
+            vec![vec![0x05, 0x01, 0x02]]
+        );
+        let json = serde_json::to_string(&captured).unwrap();
+        assert_eq!(serde_json::from_str::<Fixture>(&json).unwrap(), captured);
+
+        assert!(record(
+            &transport,
+            &DeviceSelector::Path("/dev/hidraw99".to_string()),
+            Duration::ZERO
+        )
+        .is_err());
+    }
+}
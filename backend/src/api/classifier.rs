@@ -0,0 +1,190 @@
+//! Decides which devices are game controllers before any driver looks at them, so the
+//! keyboards, mice, touchpads and headsets of controller vendors are left out and gamepads of
+//! unknown brands are let in.
+//!
+//! HID devices are judged by the usage of their top-level collection. Input devices, and HID
+//! devices with a vendor defined collection, by the keys and axes the kernel gave their evdev
+//! nodes.
+
+use std::{fs, path::Path};
+
+use udev::Device;
+
+use super::database;
+use super::transport::HidDevice;
+
+const USAGE_PAGE_GENERIC_DESKTOP: u16 = 0x01;
+const USAGE_JOYSTICK: u16 = 0x04;
+const USAGE_GAMEPAD: u16 = 0x05;
+const USAGE_PAGE_VENDOR_DEFINED: u16 = 0xff00;
+
+// Event codes from linux/input-event-codes.h
+const BTN_JOYSTICK: usize = 0x120;
+// BTN_GAMEPAD is 0x130, the range ends before BTN_DIGI
+const BTN_GAMEPAD_LAST: usize = 0x13f;
+const BTN_TRIGGER_HAPPY: usize = 0x2c0;
+const BTN_TRIGGER_HAPPY_LAST: usize = 0x2e7;
+const ABS_X: usize = 0x00;
+const ABS_Y: usize = 0x01;
+const ABS_HAT0X: usize = 0x10;
+const ABS_HAT0Y: usize = 0x11;
+
+/// The `capabilities/key` and `capabilities/abs` bitmaps of an input device
+#[derive(Debug, Default, PartialEq)]
+pub struct Capabilities {
+    keys: Vec<u64>,
+    axes: Vec<u64>,
+}
+
+impl Capabilities {
+    /// sysfs prints the bitmaps as hex words, the most significant first, e.g.
+    /// "7fff000000000000 0 0 0 0"
+    pub fn parse(keys: &str, axes: &str) -> Self {
+        Self {
+            keys: parse_bitmap(keys),
+            axes: parse_bitmap(axes),
+        }
+    }
+
+    /// The capabilities of an `input` device, or of the one an event or joystick node
+    /// belongs to
+    pub fn from_udev(device: &Device) -> Option<Self> {
+        let read = |device: &Device| {
+            let keys = device.attribute_value("capabilities/key")?;
+            let axes = device.attribute_value("capabilities/abs")?;
+            Some(Self::parse(
+                &keys.to_string_lossy(),
+                &axes.to_string_lossy(),
+            ))
+        };
+        read(device).or_else(|| read(&device.parent_with_subsystem("input").ok()??))
+    }
+
+    /// Read from the sysfs directory of an input device
+    fn from_sysfs(path: &Path) -> Option<Self> {
+        let keys = fs::read_to_string(path.join("capabilities/key")).ok()?;
+        let axes = fs::read_to_string(path.join("capabilities/abs")).ok()?;
+        Some(Self::parse(&keys, &axes))
+    }
+
+    /// Joystick or gamepad buttons along with sticks or a d-pad, the same test udev's
+    /// input_id uses to tag joysticks. Touchpads, keyboards and motion sensors have one or
+    /// the other but not both.
+    pub fn is_game_controller(&self) -> bool {
+        let buttons = (BTN_JOYSTICK..=BTN_GAMEPAD_LAST)
+            .chain(BTN_TRIGGER_HAPPY..=BTN_TRIGGER_HAPPY_LAST)
+            .any(|code| has_bit(&self.keys, code));
+        let axes = (has_bit(&self.axes, ABS_X) && has_bit(&self.axes, ABS_Y))
+            || (has_bit(&self.axes, ABS_HAT0X) && has_bit(&self.axes, ABS_HAT0Y));
+        buttons && axes
+    }
+}
+
+fn parse_bitmap(bitmap: &str) -> Vec<u64> {
+    bitmap
+        .split_whitespace()
+        .rev()
+        .map(|word| u64::from_str_radix(word, 16).unwrap_or(0))
+        .collect()
+}
+
+fn has_bit(bitmap: &[u64], bit: usize) -> bool {
+    bitmap
+        .get(bit / 64)
+        .is_some_and(|word| word & (1 << (bit % 64)) != 0)
+}
+
+pub fn is_game_controller_hid(device: &HidDevice) -> bool {
+    if device.usage_page == USAGE_PAGE_GENERIC_DESKTOP
+        && matches!(device.usage, USAGE_JOYSTICK | USAGE_GAMEPAD)
+    {
+        return true;
+    }
+    // Some controllers only declare a vendor collection, and some backends don't report the
+    // usage at all. The input devices the kernel created for them tell what they are.
+    if device.usage_page == 0 || device.usage_page >= USAGE_PAGE_VENDOR_DEFINED {
+        // Unless the device table knows the product, e.g. hid-steam removes the gamepad input
+        // device of a Steam Controller while Steam is running
+        let known = database::get()
+            .lookup(device.vendor_id, device.product_id)
+            .is_some_and(|entry| entry.product_id.is_some() && entry.driver.is_some());
+        return known
+            || hidraw_capabilities(&device.path)
+                .iter()
+                .any(Capabilities::is_game_controller);
+    }
+    false
+}
+
+pub fn is_game_controller_udev(device: &Device) -> bool {
+    Capabilities::from_udev(device).is_some_and(|capabilities| capabilities.is_game_controller())
+}
+
+/// The capabilities of every input device of the HID device behind "/dev/hidraw5"
+fn hidraw_capabilities(hidraw_path: &str) -> Vec<Capabilities> {
+    let Some(name) = Path::new(hidraw_path).file_name() else {
+        return Vec::new();
+    };
+    let input = Path::new("/sys/class/hidraw")
+        .join(name)
+        .join("device/input");
+    let Ok(entries) = fs::read_dir(input) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| Capabilities::from_sysfs(&entry.path()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_game_controller_hid, Capabilities};
+    use crate::api::transport::fixture::Fixture;
+
+    #[test]
+    fn test_capabilities() {
+        // Captured from a DualSense: gamepad, touchpad and motion sensors
+        let gamepad = Capabilities::parse("7fdb000000000000 0 0 0 0", "3003f");
+        let touchpad = Capabilities::parse("2420 0 0 0 0 0", "260800000000003");
+        let motion = Capabilities::parse("0", "3f");
+        assert!(gamepad.is_game_controller());
+        assert!(!touchpad.is_game_controller());
+        assert!(!motion.is_game_controller());
+
+        // A keyboard with media keys and a mouse
+        let keyboard = Capabilities::parse(
+            "1000000000007 ff800000000007ff febeffdfffefffff fffffffffffffffe",
+            "0",
+        );
+        let mouse = Capabilities::parse("1f0000 0 0 0 0", "0");
+        assert!(!keyboard.is_game_controller());
+        assert!(!mouse.is_game_controller());
+
+        // An arcade stick with only a d-pad and the extra buttons of some adapters
+        let arcade_stick = Capabilities::parse("ff 0 0 0 0 0 0 0 0 0 0 0", "30000");
+        assert!(arcade_stick.is_game_controller());
+        assert_eq!(Capabilities::parse("", ""), Capabilities::default());
+    }
+
+    #[test]
+    fn test_hid_usage() {
+        let mut device = Fixture::named("dualsense_usb").device;
+        assert!(is_game_controller_hid(&device));
+
+        // Keyboard and Consumer Control of a headset
+        for (usage_page, usage) in [(0x01, 0x06), (0x0c, 0x01)] {
+            device.usage_page = usage_page;
+            device.usage = usage;
+            assert!(!is_game_controller_hid(&device));
+        }
+
+        // A vendor collection counts for products in the device table, but not for unknown
+        // devices without input devices behind them
+        device.usage_page = 0xff00;
+        device.usage = 0x01;
+        assert!(is_game_controller_hid(&device));
+        device.vendor_id = 0x1234;
+        assert!(!is_game_controller_hid(&device));
+    }
+}
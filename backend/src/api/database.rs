@@ -0,0 +1,205 @@
+//! Table of known controllers by vendor/product ID: display name, the driver that handles
+//! them, quirks and whether they have a battery. The table bundled with the binary can be
+//! extended or overridden by a `devices.json` in the settings directory, so new controllers
+//! and renamed models don't need a new build.
+
+use std::{fs::File, path::Path, sync::OnceLock};
+
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use serde::Deserialize;
+
+/// Name of the user's file in the settings directory
+pub const OVERRIDE_FILE: &str = "devices.json";
+
+const BUNDLED: &str = include_str!("../../devices.json");
+
+static DATABASE: OnceLock<Database> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Quirk {
+    /// Never list the device, e.g. the Steam Deck's own controller
+    Ignore,
+    /// hidapi lists a USB connection twice, and a third time when Bluetooth is connected too
+    UsbDuplicates,
+    /// A wireless receiver, the pad behind it shows up as a USB device
+    Dongle,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceEntry {
+    #[serde(with = "hex_id")]
+    pub vendor_id: u16,
+    // Entries without a product ID apply to every product of the vendor not listed on its own
+    #[serde(default, with = "hex_id::option")]
+    pub product_id: Option<u16>,
+    pub name: Option<String>,
+    // Name of the driver that handles the device, see `ControllerDriver::name`
+    pub driver: Option<String>,
+    #[serde(default)]
+    pub quirks: Vec<Quirk>,
+    #[serde(default = "has_battery")]
+    pub battery: bool,
+}
+
+fn has_battery() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Database {
+    devices: Vec<DeviceEntry>,
+}
+
+impl Database {
+    pub fn parse(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// The entries of `other` win over the ones of this database
+    pub fn merge(mut self, other: Database) -> Self {
+        let mut devices = other.devices;
+        devices.append(&mut self.devices);
+        Self { devices }
+    }
+
+    /// The entry of the exact product, or else the one of its vendor
+    pub fn lookup(&self, vendor_id: u16, product_id: u16) -> Option<&DeviceEntry> {
+        let product = self
+            .devices
+            .iter()
+            .find(|entry| entry.vendor_id == vendor_id && entry.product_id == Some(product_id));
+        product.or_else(|| {
+            self.devices
+                .iter()
+                .find(|entry| entry.vendor_id == vendor_id && entry.product_id.is_none())
+        })
+    }
+
+    pub fn name(&self, vendor_id: u16, product_id: u16) -> Option<&str> {
+        self.lookup(vendor_id, product_id)?.name.as_deref()
+    }
+
+    pub fn driver(&self, vendor_id: u16, product_id: u16) -> Option<&str> {
+        self.lookup(vendor_id, product_id)?.driver.as_deref()
+    }
+
+    pub fn has_quirk(&self, vendor_id: u16, product_id: u16, quirk: Quirk) -> bool {
+        self.lookup(vendor_id, product_id)
+            .is_some_and(|entry| entry.quirks.contains(&quirk))
+    }
+
+    /// Unknown devices are assumed to have one
+    pub fn has_battery(&self, vendor_id: u16, product_id: u16) -> bool {
+        self.lookup(vendor_id, product_id)
+            .is_none_or(|entry| entry.battery)
+    }
+}
+
+/// Load the bundled table, with the entries of the user's file on top when there is one. A
+/// broken user file is logged and skipped. Only the first call has an effect.
+pub fn init(override_path: Option<&Path>) {
+    DATABASE.get_or_init(|| {
+        let bundled = bundled();
+        match override_path {
+            Some(path) if path.exists() => match Database::load(path) {
+                Ok(user) => {
+                    info!("Loaded device overrides from {}", path.display());
+                    bundled.merge(user)
+                }
+                Err(err) => {
+                    error!("Ignoring device overrides: {}", err);
+                    bundled
+                }
+            },
+            _ => bundled,
+        }
+    });
+}
+
+/// The device table, only the bundled one when `init` wasn't called
+pub fn get() -> &'static Database {
+    DATABASE.get_or_init(bundled)
+}
+
+fn bundled() -> Database {
+    Database::parse(BUNDLED).unwrap_or_else(|err| {
+        error!("Invalid bundled device table: {}", err);
+        Database::default()
+    })
+}
+
+/// IDs are written the way lsusb and udev show them, e.g. "054c"
+mod hex_id {
+    use serde::{de::Error, Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        u16::from_str_radix(&hex, 16).map_err(|err| D::Error::custom(format!("{}: {}", hex, err)))
+    }
+
+    pub mod option {
+        use serde::{Deserialize, Deserializer};
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<u16>, D::Error> {
+            #[derive(Deserialize)]
+            struct Id(#[serde(deserialize_with = "super::deserialize")] u16);
+
+            Ok(Option::<Id>::deserialize(deserializer)?.map(|Id(id)| id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bundled, Database, Quirk};
+
+    #[test]
+    fn test_bundled() {
+        let database = bundled();
+        assert_eq!(database.name(0x054c, 0x0ce6), Some("DualSense"));
+        assert_eq!(database.driver(0x054c, 0x0ce6), Some("playstation"));
+        // Products of a vendor that aren't listed get the vendor's entry
+        assert_eq!(database.driver(0x057e, 0x200e), Some("nintendo"));
+        assert_eq!(database.name(0x057e, 0x2017), Some("SNES Controller"));
+        assert!(database.has_quirk(0x28de, 0x1205, Quirk::Ignore));
+        assert!(!database.has_quirk(0x054c, 0x0ce6, Quirk::Ignore));
+        assert!(database.has_quirk(0x057e, 0x2009, Quirk::UsbDuplicates));
+        assert!(database.has_quirk(0x2dc8, 0x3010, Quirk::Dongle));
+        assert!(!database.has_quirk(0x2dc8, 0x6003, Quirk::Dongle));
+        assert!(!database.has_battery(0x045e, 0x02fe));
+        assert!(database.has_battery(0x1234, 0x5678));
+        assert!(database.lookup(0x1234, 0x5678).is_none());
+    }
+
+    #[test]
+    fn test_override() {
+        let user = Database::parse(
+            r#"{"devices": [
+                {"vendorId": "054c", "productId": "0ce6", "name": "My DualSense", "driver": "playstation"},
+                {"vendorId": "2dc8", "name": "8BitDo", "battery": false}
+            ]}"#,
+        )
+        .unwrap();
+        let database = bundled().merge(user);
+        assert_eq!(database.name(0x054c, 0x0ce6), Some("My DualSense"));
+        assert_eq!(database.name(0x054c, 0x09cc), Some("DualShock 4"));
+        assert_eq!(database.name(0x2dc8, 0x5006), Some("8BitDo"));
+        assert_eq!(database.driver(0x2dc8, 0x5006), None);
+        assert!(!database.has_battery(0x2dc8, 0x5006));
+
+        assert!(Database::parse(r#"{"devices": [{"vendorId": "xyz"}]}"#).is_err());
+        assert!(
+            Database::parse(r#"{"devices": [{"vendorId": "054c", "quirks": ["nope"]}]}"#).is_err()
+        );
+    }
+}
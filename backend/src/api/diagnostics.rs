@@ -0,0 +1,58 @@
+//! Counters of what went wrong while talking to each controller, to tell a flaky connection
+//! apart from a parsing bug when looking at a bug report.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+
+use crate::controller::Controller;
+
+// Bluetooth reports that failed their CRC check, by controller ID
+static CRC_FAILURES: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControllerDiagnostics {
+    pub name: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub bluetooth: bool,
+    pub crc_failures: u64,
+}
+
+pub fn record_crc_failure(controller_id: &str) {
+    let failures = CRC_FAILURES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Ok(mut failures) = failures.lock() {
+        *failures.entry(controller_id.to_string()).or_default() += 1;
+    }
+}
+
+pub fn crc_failures(controller_id: &str) -> u64 {
+    CRC_FAILURES
+        .get()
+        .and_then(|failures| failures.lock().ok()?.get(controller_id).copied())
+        .unwrap_or(0)
+}
+
+/// Drop the counts of a controller that is gone, the next one on its hidraw node starts afresh
+pub fn forget(controller_id: &str) {
+    if let Some(failures) = CRC_FAILURES.get() {
+        if let Ok(mut failures) = failures.lock() {
+            failures.remove(controller_id);
+        }
+    }
+}
+
+pub fn collect(controllers: &[Controller]) -> Vec<ControllerDiagnostics> {
+    controllers
+        .iter()
+        .map(|controller| ControllerDiagnostics {
+            name: controller.name.clone(),
+            vendor_id: controller.vendor_id,
+            product_id: controller.product_id,
+            bluetooth: controller.bluetooth,
+            crc_failures: crc_failures(&controller.id()),
+        })
+        .collect()
+}
@@ -0,0 +1,460 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::Result;
+use log::{debug, error};
+use udev::{Device, Enumerator};
+
+use crate::controller::Controller;
+
+use super::bluetooth::get_bluetooth_address;
+use super::classifier;
+use super::database;
+use super::driver::{self, ControllerDriver};
+use super::power_supply;
+use super::transport::{HidApiTransport, HidDevice, HidTransport};
+
+/// The bits of information that identify a physical controller regardless of whether it
+/// was found through hidapi (hidraw) or through udev (input).
+#[derive(Debug, Default, PartialEq)]
+pub struct Identity {
+    serial_number: Option<String>,
+    bluetooth_address: Option<String>,
+    // devpath of the HID device both the hidraw and the input nodes hang off of,
+    // e.g. "/devices/.../0005:045E:0B13.0001"
+    hid_devpath: Option<String>,
+}
+
+impl Identity {
+    pub fn from_hidapi(device: &HidDevice) -> Self {
+        let bluetooth_address = if device.interface_number == -1 {
+            get_bluetooth_address(device).ok()
+        } else {
+            None
+        };
+        let hid_devpath = hid_devpath_from_hidraw(&device.path);
+
+        Self {
+            serial_number: normalize(device.serial_number.as_deref()),
+            bluetooth_address: normalize(bluetooth_address.as_deref()),
+            hid_devpath,
+        }
+    }
+
+    pub fn from_udev(device: &Device) -> Self {
+        let serial_number = device
+            .property_value("ID_SERIAL_SHORT")
+            .map(|serial_number| serial_number.to_string_lossy().to_string());
+        let bluetooth_address = device
+            .attribute_value("uniq")
+            .map(|uniq| uniq.to_string_lossy().to_string());
+        let hid_devpath = device
+            .parent_with_subsystem("hid")
+            .ok()
+            .flatten()
+            .map(|parent| parent.devpath().to_string_lossy().to_string());
+
+        Self {
+            serial_number: normalize(serial_number.as_deref()),
+            bluetooth_address: normalize(bluetooth_address.as_deref()),
+            hid_devpath,
+        }
+    }
+
+    /// Two entries are the same physical controller when any of their identifiers match.
+    /// On Linux hidapi reports the Bluetooth address as the serial number of Bluetooth
+    /// devices, so serial numbers and addresses are compared with each other as well.
+    pub fn is_same_device(&self, other: &Identity) -> bool {
+        let same =
+            |a: &Option<String>, b: &Option<String>| matches!((a, b), (Some(a), Some(b)) if a == b);
+
+        same(&self.hid_devpath, &other.hid_devpath)
+            || same(&self.serial_number, &other.serial_number)
+            || same(&self.bluetooth_address, &other.bluetooth_address)
+            || same(&self.serial_number, &other.bluetooth_address)
+            || same(&self.bluetooth_address, &other.serial_number)
+    }
+}
+
+fn normalize(value: Option<&str>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty())
+}
+
+/// Resolve "/dev/hidraw5" to the devpath of its HID parent device
+fn hid_devpath_from_hidraw(hidraw_path: &str) -> Option<String> {
+    let name = Path::new(hidraw_path).file_name()?;
+    let device_link = Path::new("/sys/class/hidraw").join(name).join("device");
+    let syspath = fs::canonicalize(device_link).ok()?;
+    let devpath = syspath.strip_prefix("/sys").ok()?;
+    Some(format!("/{}", devpath.display()))
+}
+
+struct HidCandidate {
+    driver: &'static dyn ControllerDriver,
+    device: HidDevice,
+    identity: Identity,
+}
+
+struct UdevCandidate {
+    driver: &'static dyn ControllerDriver,
+    controller: Controller,
+    identity: Identity,
+}
+
+/// Walk the driver registry over both the HID and udev device lists and return every
+/// controller found, with each physical controller reported only once.
+pub fn discover(transport: &dyn HidTransport) -> Result<Vec<Controller>> {
+    // Drivers only get to see game controllers, not the other devices of their vendors
+    let mut hid_devices: Vec<HidDevice> = transport
+        .devices()
+        .into_iter()
+        .filter(classifier::is_game_controller_hid)
+        .collect();
+
+    let mut enumerator = Enumerator::new()?;
+    enumerator.match_subsystem("input")?;
+    let mut udev_devices: Vec<Device> = enumerator
+        .scan_devices()?
+        .filter(classifier::is_game_controller_udev)
+        .collect();
+
+    let mut hid_candidates: Vec<HidCandidate> = Vec::new();
+    let mut udev_candidates: Vec<UdevCandidate> = Vec::new();
+
+    for &driver in driver::registry() {
+        // Devices claimed by a driver are taken out of the pool so that the drivers further
+        // down the registry (i.e. the generic one) never see them.
+        let (claimed, unclaimed): (Vec<_>, Vec<_>) = hid_devices
+            .into_iter()
+            .partition(|device| driver.matches_hid(device));
+        hid_devices = unclaimed;
+
+        for device in driver.dedupe_hid(claimed) {
+            debug!("{} driver claimed {}", driver.name(), device.path);
+            hid_candidates.push(HidCandidate {
+                driver,
+                identity: Identity::from_hidapi(&device),
+                device,
+            });
+        }
+
+        let (claimed, unclaimed): (Vec<_>, Vec<_>) = udev_devices
+            .into_iter()
+            .partition(|device| driver.matches_udev(device));
+        udev_devices = unclaimed;
+
+        let mut identities: HashMap<String, Identity> = HashMap::new();
+        let probed = claimed
+            .iter()
+            .filter_map(|device| {
+                let controller = driver.probe_udev(device)?;
+                identities.insert(controller.id(), Identity::from_udev(device));
+                Some(controller)
+            })
+            .collect();
+        for controller in driver.dedupe_udev(probed) {
+            debug!("{} driver claimed {}", driver.name(), controller.id());
+            let identity = identities.remove(&controller.id()).unwrap_or_default();
+            udev_candidates.push(UdevCandidate {
+                driver,
+                controller,
+                identity,
+            });
+        }
+    }
+
+    // A Bluetooth controller is listed both as a hidraw device and as an input device.
+    // The HID entry wins since its driver reads the battery straight from the reports.
+    udev_candidates.retain(|udev_candidate| {
+        let duplicate = hid_candidates.iter().any(|hid_candidate| {
+            hid_candidate
+                .identity
+                .is_same_device(&udev_candidate.identity)
+        });
+        if duplicate {
+            debug!(
+                "Skipping udev device {}, already found through hidapi",
+                udev_candidate.controller.id()
+            );
+        }
+        !duplicate
+    });
+
+    // A controller whose battery can't be read is still listed, with an unknown battery state
+    let mut found: Vec<(&dyn ControllerDriver, Controller)> = Vec::new();
+    for candidate in hid_candidates {
+        let mut controller = candidate.driver.probe_hid(&candidate.device);
+        if let Err(err) =
+            candidate
+                .driver
+                .identify_hid(&mut controller, &candidate.device, transport)
+        {
+            debug!("Failed to identify {}: {}", controller.name, err);
+        }
+        if let Err(err) = read_battery_hid(
+            candidate.driver,
+            &mut controller,
+            &candidate.device,
+            transport,
+        ) {
+            error!("Failed to read battery of {}: {}", controller.name, err);
+        }
+        if !candidate.driver.is_connected(&controller) {
+            debug!("No controller behind {}", candidate.device.path);
+            continue;
+        }
+        found.push((candidate.driver, controller));
+    }
+    for mut candidate in udev_candidates {
+        if let Err(err) = read_battery_udev(candidate.driver, &mut candidate.controller) {
+            error!(
+                "Failed to read battery of {}: {}",
+                candidate.controller.name, err
+            );
+        }
+        found.push((candidate.driver, candidate.controller));
+    }
+
+    let mut controllers = Vec::new();
+    for &driver in driver::registry() {
+        let (own, others): (Vec<_>, Vec<_>) = found
+            .into_iter()
+            .partition(|(owner, _)| owner.name() == driver.name());
+        found = others;
+        controllers.extend(driver.combine(own.into_iter().map(|(_, c)| c).collect()));
+    }
+
+    Ok(controllers)
+}
+
+/// Re-read the battery of controllers found by a previous `discover` without enumerating
+/// every HID device on the system. Controllers that are gone are left out of the result.
+pub fn refresh(controllers: &[Controller]) -> Result<Vec<Controller>> {
+    refresh_filtered(controllers, HidApiTransport::with_ids)
+}
+
+/// Re-read the controllers through a transport that only lists the vendor/product IDs we
+/// already know about
+fn refresh_filtered<T: HidTransport>(
+    controllers: &[Controller],
+    transport_with_ids: impl FnOnce(HashSet<(u16, u16)>) -> Result<T>,
+) -> Result<Vec<Controller>> {
+    // A combined controller has an ID of its own, its parts are the devices that are read
+    let ids: HashSet<(u16, u16)> = controllers
+        .iter()
+        .flat_map(|controller| std::iter::once(controller).chain(&controller.parts))
+        .map(|controller| (controller.vendor_id, controller.product_id))
+        .collect();
+    let transport = transport_with_ids(ids)?;
+    refresh_with(&transport, controllers)
+}
+
+pub fn refresh_with(
+    transport: &dyn HidTransport,
+    controllers: &[Controller],
+) -> Result<Vec<Controller>> {
+    let devices = transport.devices();
+    let mut refreshed = Vec::new();
+    for controller in controllers {
+        let result = match controller.device_path.as_deref() {
+            _ if !controller.parts.is_empty() => refresh_parts(transport, &devices, controller),
+            Some(device_path) if device_path.starts_with("/dev/") => {
+                refresh_hid(transport, &devices, device_path)
+            }
+            Some(devpath) => refresh_udev(devpath),
+            // Nothing to re-read, e.g. the fake controller used in debug builds
+            None => Ok(Some(controller.clone())),
+        };
+        match result {
+            Ok(Some(controller)) => refreshed.push(controller),
+            Ok(None) => debug!("Controller {} is gone", controller.id()),
+            Err(err) => {
+                error!("Failed to refresh controller {}: {}", controller.id(), err);
+                refreshed.push(controller.clone());
+            }
+        }
+    }
+
+    Ok(refreshed)
+}
+
+fn refresh_hid(
+    transport: &dyn HidTransport,
+    devices: &[HidDevice],
+    device_path: &str,
+) -> Result<Option<Controller>> {
+    let Some(device) = devices.iter().find(|device| device.path == device_path) else {
+        return Ok(None);
+    };
+    let Some(driver) = driver::registry()
+        .iter()
+        .find(|driver| driver.matches_hid(device))
+    else {
+        return Ok(None);
+    };
+    let mut controller = driver.probe_hid(device);
+    if let Err(err) = driver.identify_hid(&mut controller, device, transport) {
+        debug!("Failed to identify {}: {}", controller.name, err);
+    }
+    read_battery_hid(*driver, &mut controller, device, transport)?;
+    Ok(driver.is_connected(&controller).then_some(controller))
+}
+
+/// Re-read each pad of a combined controller. When only one of them is left it is listed on
+/// its own again.
+fn refresh_parts(
+    transport: &dyn HidTransport,
+    devices: &[HidDevice],
+    controller: &Controller,
+) -> Result<Option<Controller>> {
+    let mut parts = Vec::new();
+    for part in &controller.parts {
+        if let Some(device_path) = part.device_path.as_deref() {
+            parts.extend(refresh_hid(transport, devices, device_path)?);
+        }
+    }
+    Ok(match parts.len() {
+        0 => None,
+        1 => parts.pop(),
+        _ => Some(Controller::from_parts(
+            &controller.name,
+            controller.product_id,
+            parts,
+        )),
+    })
+}
+
+fn refresh_udev(devpath: &str) -> Result<Option<Controller>> {
+    let syspath = Path::new("/sys").join(devpath.trim_start_matches('/'));
+    let Ok(device) = Device::from_syspath(&syspath) else {
+        return Ok(None);
+    };
+    let Some(driver) = driver::registry()
+        .iter()
+        .find(|driver| driver.matches_udev(&device))
+    else {
+        return Ok(None);
+    };
+    let Some(mut controller) = driver.probe_udev(&device) else {
+        return Ok(None);
+    };
+    read_battery_udev(*driver, &mut controller)?;
+    Ok(Some(controller))
+}
+
+// Prefer the values reported by the kernel driver, only parse the reports ourselves when
+// there is no power_supply node for the controller
+fn read_battery_hid(
+    driver: &dyn ControllerDriver,
+    controller: &mut Controller,
+    device: &HidDevice,
+    transport: &dyn HidTransport,
+) -> Result<()> {
+    if !database::get().has_battery(controller.vendor_id, controller.product_id)
+        || power_supply::update_controller(controller)
+    {
+        return Ok(());
+    }
+    driver.read_battery_hid(controller, device, transport)
+}
+
+fn read_battery_udev(driver: &dyn ControllerDriver, controller: &mut Controller) -> Result<()> {
+    if !database::get().has_battery(controller.vendor_id, controller.product_id)
+        || power_supply::update_controller(controller)
+    {
+        return Ok(());
+    }
+    driver.read_battery_udev(controller)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{discover, normalize, refresh_filtered, Identity};
+    use crate::api::transport::fixture::Fixture;
+    use crate::api::transport::replay::ReplayTransport;
+    use crate::controller::Controller;
+
+    #[test]
+    fn test_refresh_keeps_combined_controllers() {
+        // Both Joy-Cons of a charging grip, listed as one pair with its own product ID
+        let fixtures: Vec<Fixture> = [0, 1]
+            .into_iter()
+            .map(|interface_number| {
+                let mut fixture = Fixture::named("pro_controller_bt");
+                fixture.device.path = format!("/dev/replay/charging_grip_{}", interface_number);
+                fixture.device.product_id = 0x200e;
+                fixture.device.interface_number = interface_number;
+                fixture
+            })
+            .collect();
+        let controllers: Vec<Controller> =
+            discover(&ReplayTransport::new(fixtures.clone())).unwrap();
+        let pair: Vec<_> = controllers
+            .into_iter()
+            .filter(|controller| controller.id().starts_with("/dev/replay/charging_grip"))
+            .collect();
+        assert_eq!(pair.len(), 1);
+        assert_eq!(pair[0].product_id, 0x2008);
+
+        let refreshed =
+            refresh_filtered(
+                &pair,
+                |ids| Ok(ReplayTransport::new(fixtures).with_ids(ids)),
+            )
+            .unwrap();
+        assert_eq!(refreshed.len(), 1);
+        assert_eq!(refreshed[0].parts.len(), 2);
+    }
+
+    fn identity(serial: Option<&str>, address: Option<&str>, devpath: Option<&str>) -> Identity {
+        Identity {
+            serial_number: normalize(serial),
+            bluetooth_address: normalize(address),
+            hid_devpath: devpath.map(|devpath| devpath.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_is_same_device() {
+        let hidraw = identity(
+            Some("AA:BB:CC:DD:EE:FF"),
+            Some("aa:bb:cc:dd:ee:ff"),
+            Some("/devices/virtual/misc/uhid/0005:045E:0B13.0001"),
+        );
+
+        // Same HID parent
+        let input = identity(
+            None,
+            None,
+            Some("/devices/virtual/misc/uhid/0005:045E:0B13.0001"),
+        );
+        assert!(hidraw.is_same_device(&input));
+
+        // Bluetooth address reported by the input node's "uniq" attribute
+        let input = identity(None, Some("aa:bb:cc:dd:ee:ff"), None);
+        assert!(hidraw.is_same_device(&input));
+
+        // hidapi reports the Bluetooth address as the serial number
+        let hidraw = identity(Some("aa:bb:cc:dd:ee:ff"), None, None);
+        assert!(hidraw.is_same_device(&input));
+
+        let other = identity(
+            Some("11:22:33:44:55:66"),
+            Some("11:22:33:44:55:66"),
+            Some("/devices/virtual/misc/uhid/0005:045E:0B13.0002"),
+        );
+        assert!(!hidraw.is_same_device(&other));
+    }
+
+    #[test]
+    fn test_missing_identifiers_never_match() {
+        let empty = identity(Some(""), None, None);
+        assert!(!empty.is_same_device(&identity(Some(" "), None, None)));
+        assert!(!Identity::default().is_same_device(&Identity::default()));
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::Result;
+use udev::Device;
+
+use crate::controller::{Controller, Status};
+
+use super::transport::{HidDevice, HidTransport};
+use super::{eightbitdo, generic, nintendo, playstation, valve, wiimote, xbox};
+
+/// A driver knows how to recognize one family of controllers, collapse the duplicate
+/// entries the OS reports for a single pad, and read its battery.
+///
+/// Drivers can claim hidraw devices (through a `HidTransport`), udev `input` devices, or both.
+pub trait ControllerDriver: Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Whether this driver handles the given HID device
+    fn matches_hid(&self, _device: &HidDevice) -> bool {
+        false
+    }
+
+    /// HidApi often lists the same physical controller more than once (one entry per
+    /// interface, or once per transport). Keep only the entries that should be probed.
+    fn dedupe_hid(&self, mut devices: Vec<HidDevice>) -> Vec<HidDevice> {
+        devices.dedup_by(|a, b| a.serial_number == b.serial_number);
+        devices
+    }
+
+    /// Build the controller entry from the device descriptor alone, without talking to it
+    fn probe_hid(&self, device: &HidDevice) -> Controller {
+        Controller::from_hidapi(device, "Unknown Controller", 0, Status::Unknown)
+    }
+
+    /// Ask the controller itself what it is when its IDs don't tell, e.g. pads of several
+    /// models that share a product ID. Runs before the battery is read, even when the kernel
+    /// driver provides the battery.
+    fn identify_hid(
+        &self,
+        _controller: &mut Controller,
+        _device: &HidDevice,
+        _transport: &dyn HidTransport,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Open the device and parse its reports to fill in the battery state of a controller
+    /// previously built by `probe_hid`
+    fn read_battery_hid(
+        &self,
+        _controller: &mut Controller,
+        _device: &HidDevice,
+        _transport: &dyn HidTransport,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether a pad is behind the device at all, e.g. a wireless receiver has a device for
+    /// every pad it could serve. Asked after the battery was read.
+    fn is_connected(&self, _controller: &Controller) -> bool {
+        true
+    }
+
+    /// Drop whatever the driver remembers about the device at `device_path`, e.g. its last
+    /// battery report. Called once the device is gone, hidraw nodes get reused by the next pad.
+    fn forget(&self, _device_path: &str) {}
+
+    /// Merge the controllers this driver found that are used together as one pad, keeping
+    /// the originals as its `parts`
+    fn combine(&self, controllers: Vec<Controller>) -> Vec<Controller> {
+        controllers
+    }
+
+    /// Whether this driver handles the given udev `input` device
+    fn matches_udev(&self, _device: &Device) -> bool {
+        false
+    }
+
+    /// Build a controller entry for a udev device. Returns `None` when the device is one
+    /// of the auxiliary nodes the driver is not interested in.
+    fn probe_udev(&self, _device: &Device) -> Option<Controller> {
+        None
+    }
+
+    /// Collapse the udev entries that belong to the same physical controller
+    fn dedupe_udev(&self, mut controllers: Vec<Controller>) -> Vec<Controller> {
+        controllers.dedup_by(|a, b| a.id() == b.id());
+        controllers
+    }
+
+    /// Fill in the battery state of a controller previously built by `probe_udev`
+    fn read_battery_udev(&self, _controller: &mut Controller) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Vendor specific drivers come first, the generic driver only gets the devices nobody else
+// claimed. 8BitDo pads pose as Switch and Xbox controllers, so that driver goes before those.
+static DRIVERS: [&dyn ControllerDriver; 7] = [
+    &eightbitdo::EightBitDoDriver,
+    &playstation::PlayStationDriver,
+    &nintendo::NintendoDriver,
+    &xbox::XboxDriver,
+    &valve::ValveDriver,
+    &wiimote::WiimoteDriver,
+    &generic::GenericDriver,
+];
+
+pub fn registry() -> &'static [&'static dyn ControllerDriver] {
+    &DRIVERS
+}
+
+/// Let every driver forget the device at `device_path`
+pub fn forget(device_path: &str) {
+    for driver in registry() {
+        driver.forget(device_path);
+    }
+}
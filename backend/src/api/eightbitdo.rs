@@ -0,0 +1,198 @@
+use anyhow::Result;
+use log::debug;
+use udev::Device;
+
+use crate::controller::Status;
+
+use super::database::{self, Quirk};
+use super::driver::ControllerDriver;
+use super::transport::{HidDevice, HidTransport};
+use super::{generic, nintendo, xbox, Controller};
+
+pub const VENDOR_ID_8BITDO: u16 = 0x2dc8;
+
+// Start of the Bluetooth address of every 8BitDo pad, also when it poses as a Switch or Xbox
+// controller with the IDs of those
+const BLUETOOTH_OUI_8BITDO: &str = "e4:17:d8";
+
+const UNKNOWN_NAME: &str = "8BitDo Controller";
+
+/// What the mode switch on the back of the pad makes it present itself as
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    // An Xbox 360 pad for xpad over USB and 2.4GHz, an Xbox Wireless Controller over Bluetooth
+    XInput,
+    // A plain HID gamepad with 8BitDo's own IDs
+    DInput,
+    // A Switch Pro Controller
+    Switch,
+}
+
+impl Mode {
+    fn label(&self) -> &'static str {
+        match self {
+            Mode::XInput => "X-input",
+            Mode::DInput => "D-input",
+            Mode::Switch => "Switch",
+        }
+    }
+
+    fn of(device: &HidDevice) -> Mode {
+        if device.vendor_id == VENDOR_ID_8BITDO {
+            return Mode::DInput;
+        }
+        match database::get().driver(device.vendor_id, device.product_id) {
+            Some("nintendo") => Mode::Switch,
+            Some("xbox") => Mode::XInput,
+            _ => Mode::DInput,
+        }
+    }
+}
+
+/// A Bluetooth pad of another vendor's IDs that is really an 8BitDo. Over USB and the 2.4GHz
+/// receivers nothing tells them apart from the originals.
+fn is_posing(device: &HidDevice) -> bool {
+    device.interface_number == -1
+        && device
+            .serial_number
+            .as_deref()
+            .is_some_and(|address| address.to_lowercase().starts_with(BLUETOOTH_OUI_8BITDO))
+}
+
+/// e.g. "8BitDo Pro 2 (D-input)". The names of the IDs a pad poses with would be wrong.
+fn controller_name(
+    vendor_id: u16,
+    product_id: u16,
+    product_string: Option<&str>,
+    mode: Mode,
+) -> String {
+    let name = if vendor_id == VENDOR_ID_8BITDO {
+        database::get()
+            .name(vendor_id, product_id)
+            .or(product_string)
+    } else {
+        None
+    };
+    format!("{} ({})", name.unwrap_or(UNKNOWN_NAME), mode.label())
+}
+
+/// The receivers show up as USB devices, but the pad behind them is wireless
+fn is_dongle(vendor_id: u16, product_id: u16) -> bool {
+    database::get().has_quirk(vendor_id, product_id, Quirk::Dongle)
+}
+
+/// 8BitDo pads in each of their modes: D-input and Switch/Xbox lookalikes over Bluetooth
+/// through hidapi, X-input over USB and 2.4GHz as xpad `input` devices. The battery is read
+/// the way the driver of what the pad poses as would read it.
+pub struct EightBitDoDriver;
+
+impl ControllerDriver for EightBitDoDriver {
+    fn name(&self) -> &'static str {
+        "8bitdo"
+    }
+
+    fn matches_hid(&self, device: &HidDevice) -> bool {
+        database::get().driver(device.vendor_id, device.product_id) == Some(self.name())
+            || is_posing(device)
+    }
+
+    fn probe_hid(&self, device: &HidDevice) -> Controller {
+        let mode = Mode::of(device);
+        let name = controller_name(
+            device.vendor_id,
+            device.product_id,
+            device.product_string.as_deref(),
+            mode,
+        );
+        debug!("Found {} controller: {:?}", name, device);
+        let mut controller = Controller::from_hidapi(device, &name, 0, Status::Unknown);
+        controller.bluetooth |= is_dongle(device.vendor_id, device.product_id);
+        controller
+    }
+
+    fn read_battery_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        transport: &dyn HidTransport,
+    ) -> Result<()> {
+        match Mode::of(device) {
+            Mode::Switch => nintendo::parse_controller_data(controller, device, transport),
+            Mode::XInput => xbox::parse_xbox_controller_data(controller, device, transport),
+            Mode::DInput => generic::get_controller_data(controller, device, transport),
+        }
+    }
+
+    fn matches_udev(&self, device: &Device) -> bool {
+        device
+            .property_value("ID_VENDOR_ID")
+            .is_some_and(|vendor_id| vendor_id.eq_ignore_ascii_case("2dc8"))
+    }
+
+    fn probe_udev(&self, device: &Device) -> Option<Controller> {
+        // Only the input device itself, not its event and joystick nodes
+        if !device.sysname().to_string_lossy().starts_with("input") {
+            return None;
+        }
+        let mut controller = Controller::from_udev(device, UNKNOWN_NAME, 0, Status::Unknown);
+        controller.name = controller_name(
+            controller.vendor_id,
+            controller.product_id,
+            None,
+            Mode::XInput,
+        );
+        controller.bluetooth = is_dongle(controller.vendor_id, controller.product_id);
+        Some(controller)
+    }
+
+    fn read_battery_udev(&self, controller: &mut Controller) -> Result<()> {
+        // xpad has no battery for these. A wired pad is powered by the cable, the battery of a
+        // pad behind a receiver is unknown.
+        controller.capacity = 0;
+        controller.status = if controller.bluetooth {
+            Status::Unknown
+        } else {
+            Status::Charging
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EightBitDoDriver, Mode};
+    use crate::api::driver::ControllerDriver;
+    use crate::api::transport::fixture::Fixture;
+
+    #[test]
+    fn test_modes() {
+        let driver = EightBitDoDriver;
+        let pro_2 = Fixture::named("8bitdo_pro2_bt").device;
+        assert!(driver.matches_hid(&pro_2));
+        assert_eq!(Mode::of(&pro_2), Mode::DInput);
+        assert_eq!(driver.probe_hid(&pro_2).name, "8BitDo Pro 2 (D-input)");
+
+        // The same pad with the switch on S and X, over Bluetooth
+        let mut switch = Fixture::named("pro_controller_bt").device;
+        let mut xbox = Fixture::named("xbox_series_bt").device;
+        assert!(!driver.matches_hid(&switch));
+        assert!(!driver.matches_hid(&xbox));
+        for device in [&mut switch, &mut xbox] {
+            device.serial_number = pro_2.serial_number.clone();
+            assert!(driver.matches_hid(device));
+        }
+        assert_eq!(Mode::of(&switch), Mode::Switch);
+        assert_eq!(driver.probe_hid(&switch).name, "8BitDo Controller (Switch)");
+        assert_eq!(Mode::of(&xbox), Mode::XInput);
+
+        // A pad behind a 2.4GHz receiver is wireless even though it is a USB device
+        let mut receiver = pro_2.clone();
+        receiver.product_id = 0x3010;
+        receiver.interface_number = 0;
+        let controller = driver.probe_hid(&receiver);
+        assert!(controller.bluetooth);
+        assert_eq!(controller.name, "8BitDo Ultimate 2.4G (D-input)");
+        receiver.product_id = 0x6003;
+        assert!(!driver.probe_hid(&receiver).bluetooth);
+    }
+}
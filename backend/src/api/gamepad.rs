@@ -0,0 +1,106 @@
+//! Controller independent model of the state of a gamepad, decoded from its input reports
+//! for the controller tester.
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Button {
+    Cross,
+    Circle,
+    Square,
+    Triangle,
+    L1,
+    R1,
+    L2,
+    R2,
+    Create,
+    Options,
+    L3,
+    R3,
+    Ps,
+    Touchpad,
+    Mute,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Dpad {
+    #[default]
+    Neutral,
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Dpad {
+    /// From a hat switch value, 0 being up and going clockwise. Anything above 7 is neutral.
+    pub fn from_hat(hat: u8) -> Self {
+        match hat {
+            0 => Self::Up,
+            1 => Self::UpRight,
+            2 => Self::Right,
+            3 => Self::DownRight,
+            4 => Self::Down,
+            5 => Self::DownLeft,
+            6 => Self::Left,
+            7 => Self::UpLeft,
+            _ => Self::Neutral,
+        }
+    }
+}
+
+/// Raw stick position, 0 is left/up and 255 right/down
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Stick {
+    pub x: u8,
+    pub y: u8,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct TouchPoint {
+    // Changes with every new finger on the touchpad
+    pub id: u8,
+    pub x: u16,
+    pub y: u16,
+}
+
+/// Motion sensors, not calibrated per controller
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Motion {
+    // Pitch, yaw and roll in degrees per second
+    pub gyro: [f32; 3],
+    // X, Y and Z in g
+    pub accel: [f32; 3],
+    pub timestamp_us: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GamepadState {
+    pub buttons: Vec<Button>,
+    pub dpad: Dpad,
+    pub left_stick: Stick,
+    pub right_stick: Stick,
+    // 0 released to 255 fully pressed
+    pub left_trigger: u8,
+    pub right_trigger: u8,
+    // Only the fingers currently on the touchpad
+    pub touch_points: Vec<TouchPoint>,
+    pub motion: Motion,
+}
+
+/// Named buttons of a bitmask, given the button each bit stands for
+pub fn buttons_from_bits(bits: u32, mapping: &[(u32, Button)]) -> Vec<Button> {
+    mapping
+        .iter()
+        .filter(|(mask, _)| bits & mask != 0)
+        .map(|(_, button)| *button)
+        .collect()
+}
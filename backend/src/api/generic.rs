@@ -1,26 +1,94 @@
+use std::time::{Duration, Instant};
+
 use crate::controller::{Controller, Status};
 
 use super::bluetooth::{get_battery_percentage, get_bluetooth_address};
-use super::nintendo::VENDOR_ID_NINTENDO;
-use super::playstation::DS_VENDOR_ID;
-use super::xbox::MS_VENDOR_ID;
+use super::database::{self, Quirk};
+use super::driver::ControllerDriver;
+use super::report_descriptor::{
+    Field, ReportDescriptor, ReportType, USAGE_ABSOLUTE_STATE_OF_CHARGE, USAGE_BATTERY_STRENGTH,
+    USAGE_CHARGING, USAGE_DISCHARGING, USAGE_PAGE_BATTERY_SYSTEM,
+    USAGE_PAGE_GENERIC_DEVICE_CONTROLS, USAGE_RELATIVE_STATE_OF_CHARGE,
+};
+use super::transport::{HidDevice, HidTransport};
 
 use anyhow::Result;
-use hidapi::{DeviceInfo, HidApi};
-use log::error;
-
-const VALVE_VENDOR_ID: u16 = 0x28de;
-const FOCALTECH_VENDOR_ID: u16 = 0x2808; // touchpad?
-pub const IGNORED_VENDORS: [u16; 5] = [
-    VALVE_VENDOR_ID,
-    FOCALTECH_VENDOR_ID,
-    VENDOR_ID_NINTENDO,
-    DS_VENDOR_ID,
-    MS_VENDOR_ID,
+use log::{debug, error};
+
+// Usages that carry a battery level, in order of preference
+const BATTERY_USAGES: [(u16, u16); 3] = [
+    (USAGE_PAGE_GENERIC_DEVICE_CONTROLS, USAGE_BATTERY_STRENGTH),
+    (USAGE_PAGE_BATTERY_SYSTEM, USAGE_RELATIVE_STATE_OF_CHARGE),
+    (USAGE_PAGE_BATTERY_SYSTEM, USAGE_ABSOLUTE_STATE_OF_CHARGE),
 ];
 
-pub fn get_controller_data(device_info: &DeviceInfo, _hidapi: &HidApi) -> Result<Controller> {
-    let capacity: u8 = match get_bluetooth_address(device_info) {
+const MAX_REPORT_SIZE: usize = 512;
+// Battery input reports can be less frequent than the ones with buttons and sticks
+const BATTERY_REPORT_TIMEOUT: Duration = Duration::from_secs(1);
+const READ_TIMEOUT_MS: i32 = 100;
+
+/// Fallback for HID-class game controllers that no vendor driver claimed. Discovery only
+/// hands game controllers to drivers, see `classifier`.
+pub struct GenericDriver;
+
+impl ControllerDriver for GenericDriver {
+    fn name(&self) -> &'static str {
+        "generic"
+    }
+
+    fn matches_hid(&self, device: &HidDevice) -> bool {
+        // Devices of vendors with their own driver, and the ones to ignore, are listed in the
+        // device table
+        let database = database::get();
+        let claimed = database
+            .driver(device.vendor_id, device.product_id)
+            .is_some_and(|driver| driver != self.name());
+        !claimed && !database.has_quirk(device.vendor_id, device.product_id, Quirk::Ignore)
+    }
+
+    fn probe_hid(&self, device: &HidDevice) -> Controller {
+        // The device table knows better names than some product strings, e.g. Stadia-CG9S-4e9f
+        let name = database::get()
+            .name(device.vendor_id, device.product_id)
+            .or(device.product_string.as_deref())
+            .unwrap_or("Unknown Controller");
+
+        Controller::from_hidapi(device, name, 0, Status::Unknown)
+    }
+
+    fn read_battery_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        transport: &dyn HidTransport,
+    ) -> Result<()> {
+        get_controller_data(controller, device, transport)
+    }
+}
+
+pub fn get_controller_data(
+    controller: &mut Controller,
+    device: &HidDevice,
+    transport: &dyn HidTransport,
+) -> Result<()> {
+    match read_hid_battery(device, transport) {
+        Ok(Some((capacity, status))) => {
+            controller.capacity = capacity;
+            controller.status = status;
+            return Ok(());
+        }
+        Ok(None) => {}
+        Err(err) => debug!("Failed to read HID battery of {}: {}", device.path, err),
+    }
+
+    // Nothing else tells the battery of a wired pad
+    if !controller.bluetooth {
+        debug!("No battery level for {}", device.path);
+        return Ok(());
+    }
+
+    // BlueZ knows the battery of controllers that implement the Battery Service
+    controller.capacity = match get_bluetooth_address(device) {
         Ok(address) => match get_battery_percentage(address) {
             Ok(percentage) => percentage,
             Err(err) => {
@@ -33,13 +101,80 @@ pub fn get_controller_data(device_info: &DeviceInfo, _hidapi: &HidApi) -> Result
             0
         }
     };
+    Ok(())
+}
 
-    let mut name = device_info.product_string().unwrap_or("Unknown Controller");
-    if name.starts_with("Stadia") {
-        // product string is e.g. Stadia-CG9S-4e9f, this would be better
-        name = "Stadia Controller";
-    }
+/// Battery level from the usages declared by the report descriptor, `None` when it has none
+/// or the report carrying it didn't arrive
+fn read_hid_battery(
+    device: &HidDevice,
+    transport: &dyn HidTransport,
+) -> Result<Option<(u8, Status)>> {
+    let mut connection = transport.open(device)?;
+    let descriptor = ReportDescriptor::parse(&connection.report_descriptor()?)?;
+    let Some(level) = BATTERY_USAGES.iter().find_map(|(usage_page, usage)| {
+        descriptor
+            .find(*usage_page, *usage)
+            .filter(|field| field.report_type != ReportType::Output)
+    }) else {
+        return Ok(None);
+    };
 
-    let controller = Controller::from_hidapi(device_info, name, capacity, Status::Unknown);
-    Ok(controller)
+    let mut buf = [0u8; MAX_REPORT_SIZE];
+    let report = match level.report_type {
+        ReportType::Feature => {
+            buf[0] = level.report_id;
+            let len = connection.get_feature_report(&mut buf)?;
+            // hidraw keeps a 0 in front of reports of devices without report IDs
+            if level.report_id == 0 {
+                buf.get(1..len).unwrap_or(&[]).to_vec()
+            } else {
+                buf[..len].to_vec()
+            }
+        }
+        _ => {
+            let deadline = Instant::now() + BATTERY_REPORT_TIMEOUT;
+            let mut report = None;
+            while report.is_none() && Instant::now() < deadline {
+                let len = connection.read_timeout(&mut buf, READ_TIMEOUT_MS)?;
+                if len > 0 && level.value(&buf[..len]).is_some() {
+                    report = Some(buf[..len].to_vec());
+                }
+            }
+            match report {
+                Some(report) => report,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    let Some(capacity) = level.percentage(&report) else {
+        return Ok(None);
+    };
+    Ok(Some((
+        capacity,
+        battery_status(&descriptor, level, &report),
+    )))
+}
+
+/// Charging state from the Battery System flags sent along with the level
+fn battery_status(descriptor: &ReportDescriptor, level: &Field, report: &[u8]) -> Status {
+    let flag = |usage| {
+        descriptor
+            .fields
+            .iter()
+            .filter(|field| {
+                field.report_type == level.report_type && field.report_id == level.report_id
+            })
+            .find(|field| field.usage_page == USAGE_PAGE_BATTERY_SYSTEM && field.usage == usage)
+            .and_then(|field| field.value(report))
+            .is_some_and(|value| value != 0)
+    };
+    if flag(USAGE_CHARGING) {
+        Status::Charging
+    } else if flag(USAGE_DISCHARGING) {
+        Status::Discharging
+    } else {
+        Status::Unknown
+    }
 }
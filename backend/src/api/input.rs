@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use log::{debug, error};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use super::capture::DeviceSelector;
+use super::diagnostics;
+use super::gamepad::GamepadState;
+use super::playstation::{decode_dualsense_input, has_valid_crc, is_dualsense};
+use super::transport::{HidApiTransport, HidTransport};
+
+// Large enough for any input report a controller sends
+const MAX_REPORT_SIZE: usize = 1024;
+// How often the reader checks whether anybody is still listening when the controller is idle
+const READ_TIMEOUT_MS: i32 = 100;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum InputEvent {
+    State { state: GamepadState },
+    Error { message: String },
+}
+
+/// Decode the input reports of a controller on a dedicated thread for as long as the returned
+/// receiver (or a clone of it) is alive. Only the latest state is kept, slow readers skip the
+/// states in between.
+pub fn watch(selector: DeviceSelector) -> watch::Receiver<Option<InputEvent>> {
+    let (sender, receiver) = watch::channel(None);
+    let result = std::thread::Builder::new()
+        .name("input".to_string())
+        .spawn(move || {
+            let result = HidApiTransport::new()
+                .and_then(|transport| read_input(&transport, &selector, &sender));
+            if let Err(err) = result {
+                debug!("Stopped reading input of {:?}: {}", selector, err);
+                sender.send_replace(Some(InputEvent::Error {
+                    message: err.to_string(),
+                }));
+            }
+        });
+    if let Err(err) = result {
+        error!("Failed to start input thread: {}", err);
+    }
+    receiver
+}
+
+fn read_input(
+    transport: &dyn HidTransport,
+    selector: &DeviceSelector,
+    sender: &watch::Sender<Option<InputEvent>>,
+) -> Result<()> {
+    let device = selector.find(transport)?;
+    if !is_dualsense(&device) {
+        return Err(anyhow!("input is only decoded for DualSense controllers"));
+    }
+    let mut connection = transport.open(&device)?;
+
+    let mut buf = [0u8; MAX_REPORT_SIZE];
+    while !sender.is_closed() {
+        let len = connection.read_timeout(&mut buf, READ_TIMEOUT_MS)?;
+        if len == 0 {
+            continue;
+        }
+        if !has_valid_crc(&buf[..len]) {
+            diagnostics::record_crc_failure(&device.path);
+            continue;
+        }
+        if let Some(state) = decode_dualsense_input(&buf[..len])? {
+            sender.send_replace(Some(InputEvent::State { state }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_input, InputEvent};
+    use crate::api::capture::DeviceSelector;
+    use crate::api::gamepad::{Dpad, Stick};
+    use crate::api::transport::fixture::Fixture;
+    use crate::api::transport::replay::ReplayTransport;
+    use tokio::sync::watch;
+
+    #[test]
+    fn test_read_input() {
+        let transport = ReplayTransport::new(vec![
+            Fixture::named("dualsense_usb"),
+            Fixture::named("ds4_usb"),
+        ]);
+        let (sender, mut receiver) = watch::channel(None);
+
+        std::thread::scope(|scope| {
+            let reader = scope.spawn(|| {
+                read_input(
+                    &transport,
+                    &DeviceSelector::Path("/dev/replay/dualsense_usb".to_string()),
+                    &sender,
+                )
+            });
+            while receiver.borrow_and_update().is_none() {
+                std::thread::yield_now();
+            }
+            let Some(InputEvent::State { state }) = receiver.borrow().clone() else {
+                panic!("expected a state");
+            };
+            assert_eq!(state.left_stick, Stick { x: 0x80, y: 0x7f });
+            assert_eq!(state.right_stick, Stick { x: 0x81, y: 0x80 });
+            assert_eq!(state.dpad, Dpad::Neutral);
+            assert!(state.buttons.is_empty());
+
+            // The reader stops once nobody listens anymore
+            drop(receiver);
+            assert!(reader.join().unwrap().is_ok());
+        });
+
+        let (sender, _receiver) = watch::channel(None);
+        let selector = DeviceSelector::Path("/dev/replay/ds4_usb".to_string());
+        assert!(read_input(&transport, &selector, &sender).is_err());
+    }
+}
@@ -1,18 +1,53 @@
-use anyhow::Result;
-use hidapi::{DeviceInfo, HidApi};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{anyhow, Result};
 use log::{debug, error};
-use serde::Deserialize;
+use udev::Enumerator;
 
 use crate::controller::Status;
 
+use super::capture::DeviceSelector;
+use super::database::{self, Quirk};
+use super::driver::ControllerDriver;
+use super::report::InputReport;
+use super::transport::{HidDevice, HidTransport};
 use super::Controller;
 
+use self::protocol::{
+    ControllerType, DeviceInfo, Session, INPUT_FULL, INPUT_SIMPLE, INPUT_SUBCOMMAND_REPLY,
+};
+
+pub mod protocol;
+
 pub const VENDOR_ID_NINTENDO: u16 = 0x057e;
-pub const PRODUCT_ID_NINTENDO_PROCON: u16 = 0x2009;
 pub const PRODUCT_ID_NINTENDO_JOYCON_L: u16 = 0x2006;
 pub const PRODUCT_ID_NINTENDO_JOYCON_R: u16 = 0x2007;
+// joycond uses it for the virtual device of a Joy-Con pair, there is no such physical device
+pub const PRODUCT_ID_NINTENDO_JOYCON_PAIR: u16 = 0x2008;
+// Both Joy-Cons of a charging grip show up as interfaces of the grip, the left one first
+pub const PRODUCT_ID_NINTENDO_CHARGING_GRIP: u16 = 0x200e;
+
+const JOYCON_L_NAME: &str = "Joy-Con L";
+const JOYCON_R_NAME: &str = "Joy-Con R";
+const JOYCON_PAIR_NAME: &str = "Joy-Con (L/R)";
+// The NES and Famicom controllers of Switch Online have the product IDs of the Joy-Cons
+const NES_LEFT_NAME: &str = "NES Controller (L)";
+const NES_RIGHT_NAME: &str = "NES Controller (R)";
+const FAMICOM_I_NAME: &str = "Famicom Controller I";
+const FAMICOM_II_NAME: &str = "Famicom Controller II";
+// Name of the input device joycond creates once both Joy-Cons of a pair are held together
+// and their triggers pressed
+const JOYCOND_COMBINED_NAME: &str = "Nintendo Switch Combined Joy-Cons";
 
 const INPUT_REPORT_SIZE: usize = 362;
+// Battery level in the top 3 bits, charging in bit 4 and the connection info below. It follows
+// the report ID and the timer in every report that has it.
+const INPUT_BATTERY_CONNECTION: usize = 2;
+
+// Type from the device info of each pad with a Joy-Con product ID, by hidraw path. Asked once
+// since it doesn't change while the pad stays connected, `None` for a pad that didn't answer.
+static CONTROLLER_TYPES: OnceLock<Mutex<HashMap<String, Option<ControllerType>>>> = OnceLock::new();
 
 #[macro_export]
 macro_rules! BIT {
@@ -21,35 +56,246 @@ macro_rules! BIT {
     };
 }
 
-#[repr(C, packed)]
-#[derive(Copy, Clone, Debug, Deserialize)]
-struct InputReport {
-    id: u8,
-    timer: u8,
-    bat_con: u8,
+pub struct NintendoDriver;
+
+impl ControllerDriver for NintendoDriver {
+    fn name(&self) -> &'static str {
+        "nintendo"
+    }
+
+    fn matches_hid(&self, device: &HidDevice) -> bool {
+        database::get().driver(device.vendor_id, device.product_id) == Some(self.name())
+    }
+
+    fn dedupe_hid(&self, devices: Vec<HidDevice>) -> Vec<HidDevice> {
+        // HidApi will return 2 copies of the device when the Nintendo Pro Controller is connected via USB.
+        // It will additionally return a 3rd device when the controller is connected via Bluetooth + USB.
+        let (pro_controllers, mut other_controllers): (Vec<_>, Vec<_>) =
+            devices.into_iter().partition(|device| {
+                database::get().has_quirk(device.vendor_id, device.product_id, Quirk::UsbDuplicates)
+            });
+
+        let mut selected = Vec::new();
+        if pro_controllers.len() == 1 || pro_controllers.len() == 2 {
+            // When we only get one device, we know it's connected via Bluetooth.
+            // When we get two devices, we know it's connected only via USB. Both will report the same data, so we'll just return the first one.
+            selected.push(pro_controllers[0].clone());
+        } else if pro_controllers.len() == 3 {
+            // When we get three devices, we know it's connected via USB + Bluetooth.
+            // We'll only return the Bluetooth device because the USB devices will not report any data.
+            if let Some(bt_controller) = pro_controllers
+                .into_iter()
+                .find(|device| device.interface_number == -1)
+            {
+                selected.push(bt_controller);
+            }
+        }
+
+        selected.append(&mut other_controllers);
+        selected
+    }
+
+    fn probe_hid(&self, device: &HidDevice) -> Controller {
+        let name = match (device.product_id, device.interface_number) {
+            (PRODUCT_ID_NINTENDO_CHARGING_GRIP, 0) => JOYCON_L_NAME,
+            (PRODUCT_ID_NINTENDO_CHARGING_GRIP, 1) => JOYCON_R_NAME,
+            _ => database::get()
+                .name(device.vendor_id, device.product_id)
+                .unwrap_or("Nintendo Controller"),
+        };
+        Controller::from_hidapi(device, name, 0, Status::Unknown)
+    }
+
+    fn identify_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        transport: &dyn HidTransport,
+    ) -> Result<()> {
+        if !matches!(
+            device.product_id,
+            PRODUCT_ID_NINTENDO_JOYCON_L | PRODUCT_ID_NINTENDO_JOYCON_R
+        ) {
+            return Ok(());
+        }
+        let controller_type = match cached_controller_type(&device.path) {
+            Some(Some(controller_type)) => controller_type,
+            // Some clones never answer, asking again would block every poll until the timeout
+            Some(None) => return Ok(()),
+            None => {
+                let mut connection = transport.open(device)?;
+                let controller_type = Session::new(connection.as_mut()).controller_type();
+                if let Ok(mut types) = CONTROLLER_TYPES
+                    .get_or_init(|| Mutex::new(HashMap::new()))
+                    .lock()
+                {
+                    types.insert(device.path.clone(), controller_type.as_ref().ok().copied());
+                }
+                controller_type?
+            }
+        };
+        let name = match controller_type {
+            ControllerType::NesLeft => NES_LEFT_NAME,
+            ControllerType::NesRight => NES_RIGHT_NAME,
+            ControllerType::FamicomI => FAMICOM_I_NAME,
+            ControllerType::FamicomII => FAMICOM_II_NAME,
+            _ => return Ok(()),
+        };
+        controller.name = name.to_string();
+        Ok(())
+    }
+
+    fn read_battery_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        transport: &dyn HidTransport,
+    ) -> Result<()> {
+        parse_controller_data(controller, device, transport)
+    }
+
+    fn combine(&self, controllers: Vec<Controller>) -> Vec<Controller> {
+        pair_joycons(controllers, joycond_pairs())
+    }
+
+    fn forget(&self, device_path: &str) {
+        // The next pad on the node may be a Joy-Con where a NES controller was
+        if let Some(types) = CONTROLLER_TYPES.get() {
+            if let Ok(mut types) = types.lock() {
+                types.remove(device_path);
+            }
+        }
+    }
+}
+
+fn cached_controller_type(path: &str) -> Option<Option<ControllerType>> {
+    CONTROLLER_TYPES.get()?.lock().ok()?.get(path).copied()
 }
 
-pub fn parse_controller_data(device_info: &DeviceInfo, hidapi: &HidApi) -> Result<Controller> {
-    let name = match device_info.product_id() {
-        PRODUCT_ID_NINTENDO_JOYCON_L => "Joy-Con L",
-        PRODUCT_ID_NINTENDO_JOYCON_R => "Joy-Con R",
-        PRODUCT_ID_NINTENDO_PROCON => "Pro Controller",
-        _ => "Nintendo Controller",
-    };
-    let mut controller = Controller::from_hidapi(device_info, name, 0, Status::Unknown);
+/// Whether a pad with a Joy-Con product ID is really a NES or Famicom controller, which are
+/// never combined
+fn is_retro(controller: &Controller) -> bool {
+    [
+        NES_LEFT_NAME,
+        NES_RIGHT_NAME,
+        FAMICOM_I_NAME,
+        FAMICOM_II_NAME,
+    ]
+    .contains(&controller.name.as_str())
+}
 
-    let device = device_info.open_device(hidapi)?;
+/// Combine each Joy-Con L with its Joy-Con R: the two halves of a charging grip always go
+/// together, Bluetooth ones only when joycond paired them. Any other Joy-Con is used as a
+/// single pad and stays on its own, as do NES and Famicom controllers.
+fn pair_joycons(controllers: Vec<Controller>, joycond_pairs: usize) -> Vec<Controller> {
+    // Names can be changed in the device table, only the two halves of a grip go by name
+    let (mut lefts, mut others): (Vec<_>, Vec<_>) =
+        controllers
+            .into_iter()
+            .partition(|controller| match controller.product_id {
+                PRODUCT_ID_NINTENDO_CHARGING_GRIP => controller.name == JOYCON_L_NAME,
+                product_id => product_id == PRODUCT_ID_NINTENDO_JOYCON_L && !is_retro(controller),
+            });
+    // joycond doesn't tell which pads it paired, at least pair them the same way every time
+    lefts.sort_by_key(Controller::id);
+    others.sort_by_key(Controller::id);
+
+    let mut joycond_pairs = joycond_pairs;
+    let mut paired = Vec::new();
+    for left in lefts {
+        let in_grip = left.product_id == PRODUCT_ID_NINTENDO_CHARGING_GRIP;
+        let right = others.iter().position(|other| {
+            if in_grip {
+                // Both halves of a grip share its serial number
+                other.product_id == PRODUCT_ID_NINTENDO_CHARGING_GRIP
+                    && other.name == JOYCON_R_NAME
+                    && other.serial_number == left.serial_number
+            } else {
+                other.product_id == PRODUCT_ID_NINTENDO_JOYCON_R && !is_retro(other)
+            }
+        });
+        match right {
+            Some(index) if in_grip || joycond_pairs > 0 => {
+                if !in_grip {
+                    joycond_pairs -= 1;
+                }
+                let right = others.remove(index);
+                paired.push(Controller::from_parts(
+                    JOYCON_PAIR_NAME,
+                    PRODUCT_ID_NINTENDO_JOYCON_PAIR,
+                    vec![left, right],
+                ));
+            }
+            _ => paired.push(left),
+        }
+    }
+    paired.append(&mut others);
+    paired
+}
+
+/// Number of Joy-Con pairs joycond currently combines
+fn joycond_pairs() -> usize {
+    let count = Enumerator::new().and_then(|mut enumerator| {
+        enumerator.match_subsystem("input")?;
+        enumerator.match_attribute("name", JOYCOND_COMBINED_NAME)?;
+        Ok(enumerator.scan_devices()?.count())
+    });
+    match count {
+        Ok(count) => count,
+        Err(err) => {
+            debug!("Failed to look for joycond devices: {}", err);
+            0
+        }
+    }
+}
+
+/// Ask a Switch controller for its firmware version, type, MAC address and colors
+pub fn device_info(transport: &dyn HidTransport, selector: &DeviceSelector) -> Result<DeviceInfo> {
+    let device = selector.find(transport)?;
+    if device.vendor_id != VENDOR_ID_NINTENDO {
+        return Err(anyhow!("{} is not a Nintendo controller", device.path));
+    }
+    let mut connection = transport.open(&device)?;
+    Session::new(connection.as_mut()).device_info()
+}
+
+pub fn parse_controller_data(
+    controller: &mut Controller,
+    device: &HidDevice,
+    transport: &dyn HidTransport,
+) -> Result<()> {
+    let mut device = transport.open(device)?;
     let mut buf = [0u8; INPUT_REPORT_SIZE];
-    let _res = match device.read_timeout(&mut buf[..], 1000) {
+    let mut res = match device.read_timeout(&mut buf[..], 1000) {
         Ok(res) => res,
         Err(e) => {
             error!("Error reading from device: {}", e);
-            return Ok(controller);
+            return Ok(());
+        }
+    };
+    if res > 0 && buf[0] == INPUT_SIMPLE {
+        // Nothing set the pad up yet (hid-nintendo isn't loaded), ask for the full reports
+        // that carry the battery level
+        debug!(
+            "{} is in simple HID mode, enabling full reports",
+            controller.name
+        );
+        Session::new(device.as_mut()).set_report_mode(INPUT_FULL)?;
+        res = device.read_timeout(&mut buf[..], 1000)?;
+    }
+    let report = match InputReport::new(&buf, res) {
+        Ok(report) if has_battery(report.id()) => report,
+        Ok(report) => {
+            debug!("No battery level in report {:#04x}", report.id());
+            return Ok(());
+        }
+        Err(err) => {
+            debug!("No battery level from {}: {}", controller.name, err);
+            return Ok(());
         }
     };
 
-    let input_report: InputReport = bincode::deserialize(&buf[0..3])?;
-    let tmp = input_report.bat_con;
+    let tmp = report.u8(INPUT_BATTERY_CONNECTION)?;
     let _host_powered = tmp & BIT!(0) != 0;
     let battery_charging = tmp & BIT!(4) != 0;
     let tmp = tmp >> 5;
@@ -70,5 +316,220 @@ pub fn parse_controller_data(device_info: &DeviceInfo, hidapi: &HidApi) -> Resul
         }
     }
 
-    Ok(controller)
+    Ok(())
+}
+
+/// The subcommand reply and the full report modes (0x30 to 0x33) start with the timer and
+/// battery
+fn has_battery(report_id: u8) -> bool {
+    report_id == INPUT_SUBCOMMAND_REPLY || (INPUT_FULL..=0x33).contains(&report_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pair_joycons, parse_controller_data, NintendoDriver, NES_LEFT_NAME,
+        PRODUCT_ID_NINTENDO_CHARGING_GRIP, PRODUCT_ID_NINTENDO_JOYCON_L,
+        PRODUCT_ID_NINTENDO_JOYCON_PAIR, PRODUCT_ID_NINTENDO_JOYCON_R, VENDOR_ID_NINTENDO,
+    };
+    use crate::api::driver::ControllerDriver;
+    use crate::api::report::ReportError;
+    use crate::api::transport::fixture::Fixture;
+    use crate::api::transport::replay::ReplayTransport;
+    use crate::api::transport::{HidDevice, HidTransport};
+    use crate::api::uhid::profiles::PRO_CONTROLLER;
+    use crate::controller::{Controller, Status};
+
+    fn joycon(path: &str, product_id: u16, interface_number: i32, serial: &str) -> Controller {
+        let device = HidDevice {
+            path: path.to_string(),
+            vendor_id: VENDOR_ID_NINTENDO,
+            product_id,
+            serial_number: Some(serial.to_string()),
+            product_string: None,
+            interface_number,
+            usage_page: 1,
+            usage: 5,
+        };
+        NintendoDriver.probe_hid(&device)
+    }
+
+    fn summary(controllers: &[Controller]) -> Vec<(String, Vec<String>)> {
+        controllers
+            .iter()
+            .map(|controller| {
+                let parts = controller.parts.iter().map(Controller::id).collect();
+                (controller.id(), parts)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pair_joycons() {
+        let grip = || {
+            vec![
+                joycon("/dev/hidraw1", PRODUCT_ID_NINTENDO_CHARGING_GRIP, 0, "grip"),
+                joycon("/dev/hidraw2", PRODUCT_ID_NINTENDO_CHARGING_GRIP, 1, "grip"),
+            ]
+        };
+        let bluetooth = || {
+            vec![
+                joycon("/dev/hidraw3", PRODUCT_ID_NINTENDO_JOYCON_L, -1, "aa"),
+                joycon("/dev/hidraw4", PRODUCT_ID_NINTENDO_JOYCON_R, -1, "bb"),
+            ]
+        };
+        assert_eq!(grip()[0].name, "Joy-Con L");
+        assert_eq!(grip()[1].name, "Joy-Con R");
+
+        // Joy-Cons in a grip are always used together
+        let paired = pair_joycons(grip(), 0);
+        assert_eq!(
+            summary(&paired),
+            vec![(
+                "/dev/hidraw1".to_string(),
+                vec!["/dev/hidraw1".to_string(), "/dev/hidraw2".to_string()]
+            )]
+        );
+        assert_eq!(paired[0].name, "Joy-Con (L/R)");
+        assert_eq!(paired[0].product_id, PRODUCT_ID_NINTENDO_JOYCON_PAIR);
+
+        // Bluetooth ones are two single pads unless joycond combined them
+        let single = pair_joycons(bluetooth(), 0);
+        assert_eq!(
+            summary(&single),
+            vec![
+                ("/dev/hidraw3".to_string(), vec![]),
+                ("/dev/hidraw4".to_string(), vec![])
+            ]
+        );
+        let mut controllers = grip();
+        controllers.extend(bluetooth());
+        let paired = pair_joycons(controllers, 1);
+        assert_eq!(paired.len(), 2);
+        assert_eq!(paired[1].parts.len(), 2);
+        assert_eq!(paired[1].id(), "/dev/hidraw3");
+    }
+
+    #[test]
+    fn test_battery_report_layout() {
+        // 0x30 report, timer, then the battery: level 1 (25%), charging, on the cable
+        let mut fixture = Fixture::named("pro_controller_bt");
+        fixture.reports.truncate(2);
+        fixture.reports[0].data = vec![0x30, 0x10, 0x31];
+        // Cut off before the battery byte, the rest of the buffer isn't read
+        fixture.reports[1].data.truncate(2);
+        let transport = ReplayTransport::new(vec![fixture]);
+        let device = transport.devices().remove(0);
+        let mut controller = NintendoDriver.probe_hid(&device);
+
+        parse_controller_data(&mut controller, &device, &transport).unwrap();
+        assert_eq!(
+            (controller.capacity, controller.status.clone()),
+            (25, Status::Charging)
+        );
+        let err = parse_controller_data(&mut controller, &device, &transport).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ReportError>(),
+            Some(&ReportError::Short {
+                report_id: 0x30,
+                len: 2,
+                needed: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_simple_mode_is_switched_to_full_reports() {
+        let transport = ReplayTransport::new(vec![Fixture::named("pro_controller_simple_bt")])
+            .respond_with(PRO_CONTROLLER.respond);
+        let device = transport.devices().remove(0);
+        let mut controller = NintendoDriver.probe_hid(&device);
+
+        parse_controller_data(&mut controller, &device, &transport).unwrap();
+        assert_eq!(controller.capacity, 100);
+        assert_eq!(controller.status, Status::Charging);
+
+        let output_reports = transport.output_reports(&device.path);
+        assert_eq!(output_reports.len(), 1);
+        assert_eq!(&output_reports[0][10..12], &[0x03, 0x30]);
+    }
+
+    #[test]
+    fn test_nes_controller_is_identified() {
+        // A NES controller (L) answers with its own type, the rest is the same as a Joy-Con
+        fn respond(output: &[u8], input: &[u8]) -> Option<Vec<u8>> {
+            let mut reply = (PRO_CONTROLLER.respond)(output, input)?;
+            if reply[0] == 0x21 && reply[14] == 0x02 {
+                reply[17] = 0x09;
+            }
+            Some(reply)
+        }
+
+        let mut fixture = Fixture::named("pro_controller_bt");
+        fixture.device.path = "/dev/replay/nes_left_bt".to_string();
+        fixture.device.product_id = PRODUCT_ID_NINTENDO_JOYCON_L;
+        let transport = ReplayTransport::new(vec![fixture]).respond_with(respond);
+        let device = transport.devices().remove(0);
+        let mut controller = NintendoDriver.probe_hid(&device);
+        assert_eq!(controller.name, "Joy-Con L");
+
+        for _ in 0..2 {
+            NintendoDriver
+                .identify_hid(&mut controller, &device, &transport)
+                .unwrap();
+            assert_eq!(controller.name, NES_LEFT_NAME);
+        }
+        // The type is only asked for once
+        assert_eq!(transport.output_reports(&device.path).len(), 1);
+
+        // Never combined with a Joy-Con R, even when joycond paired some Joy-Cons
+        let right = joycon("/dev/hidraw4", PRODUCT_ID_NINTENDO_JOYCON_R, -1, "bb");
+        let paired = pair_joycons(vec![controller, right], 1);
+        assert_eq!(paired.len(), 2);
+        assert!(paired.iter().all(|controller| controller.parts.is_empty()));
+
+        // It leaves, and a Joy-Con L gets its hidraw node
+        NintendoDriver.forget(&device.path);
+        let mut fixture = Fixture::named("pro_controller_bt");
+        fixture.device.path = device.path.clone();
+        fixture.device.product_id = PRODUCT_ID_NINTENDO_JOYCON_L;
+        let transport = ReplayTransport::new(vec![fixture]).respond_with(PRO_CONTROLLER.respond);
+        let mut controller = NintendoDriver.probe_hid(&device);
+        NintendoDriver
+            .identify_hid(&mut controller, &device, &transport)
+            .unwrap();
+        assert_eq!(controller.name, "Joy-Con L");
+        assert_eq!(transport.output_reports(&device.path).len(), 1);
+    }
+
+    #[test]
+    fn test_unanswered_type_query() {
+        // Without a responder the fixture has no 0x21 reply
+        let mut fixture = Fixture::named("pro_controller_bt");
+        fixture.device.path = "/dev/replay/joycon_l_clone_bt".to_string();
+        fixture.device.product_id = PRODUCT_ID_NINTENDO_JOYCON_L;
+        let transport = ReplayTransport::new(vec![fixture]);
+        let device = transport.devices().remove(0);
+        let mut controller = NintendoDriver.probe_hid(&device);
+
+        assert!(NintendoDriver
+            .identify_hid(&mut controller, &device, &transport)
+            .is_err());
+        let attempts = transport.output_reports(&device.path).len();
+        assert_eq!(attempts, 3);
+
+        // Not asked again while the pad stays connected
+        NintendoDriver
+            .identify_hid(&mut controller, &device, &transport)
+            .unwrap();
+        assert_eq!(controller.name, "Joy-Con L");
+        assert_eq!(transport.output_reports(&device.path).len(), attempts);
+
+        // Asked again for the next pad on the node
+        NintendoDriver.forget(&device.path);
+        assert!(NintendoDriver
+            .identify_hid(&mut controller, &device, &transport)
+            .is_err());
+        assert_eq!(transport.output_reports(&device.path).len(), 2 * attempts);
+    }
 }
@@ -0,0 +1,323 @@
+//! Subcommand channel of the Switch controllers: output report 0x01 carries a subcommand and
+//! the controller acknowledges it with input report 0x21. Layouts follow dekuNukem's
+//! Nintendo_Switch_Reverse_Engineering notes, which hid-nintendo is based on as well.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use log::debug;
+use serde::Serialize;
+
+use crate::api::report::{InputReport, ReportError};
+use crate::api::transport::HidConnection;
+
+use super::INPUT_REPORT_SIZE;
+
+const OUTPUT_RUMBLE_AND_SUBCOMMAND: u8 = 0x01;
+const OUTPUT_REPORT_SIZE: usize = 49;
+
+pub const INPUT_SUBCOMMAND_REPLY: u8 = 0x21;
+/// Standard full mode, sent every 15ms (8ms over USB) with the battery level
+pub const INPUT_FULL: u8 = 0x30;
+/// Simple HID mode, the default of a Bluetooth pad that wasn't set up by a driver. It only
+/// sends buttons and sticks on change and has no battery level.
+pub const INPUT_SIMPLE: u8 = 0x3f;
+
+const SUBCOMMAND_DEVICE_INFO: u8 = 0x02;
+const SUBCOMMAND_SET_REPORT_MODE: u8 = 0x03;
+const SUBCOMMAND_SPI_READ: u8 = 0x10;
+
+// Body RGB followed by buttons RGB
+const SPI_COLORS: u32 = 0x6050;
+// Largest chunk the controller returns in one reply
+const SPI_READ_MAX: usize = 0x1d;
+
+// Subcommands always carry rumble data, this one keeps the motors still
+const RUMBLE_NEUTRAL: [u8; 8] = [0x00, 0x01, 0x40, 0x40, 0x00, 0x01, 0x40, 0x40];
+
+// Offsets in the 0x21 reply, the data of the subcommand follows the header
+const REPLY_ACK: usize = 13;
+const REPLY_SUBCOMMAND: usize = 14;
+const REPLY_DATA: usize = 15;
+const ACK: u8 = 0x80;
+
+// Device info in the data of its reply: firmware version, type, a reserved byte, then the MAC
+const DEVICE_INFO_FIRMWARE: usize = REPLY_DATA;
+const DEVICE_INFO_TYPE: usize = REPLY_DATA + 2;
+const DEVICE_INFO_MAC: usize = REPLY_DATA + 4;
+
+// The controller keeps sending regular input reports while working on a subcommand
+const REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+const READ_TIMEOUT_MS: i32 = 100;
+const SUBCOMMAND_RETRIES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ControllerType {
+    JoyConL,
+    JoyConR,
+    ProController,
+    // Switch Online controllers, the NES and Famicom ones use the product IDs of the Joy-Cons
+    FamicomI,
+    FamicomII,
+    NesLeft,
+    NesRight,
+    Snes,
+    N64,
+    Genesis,
+    Unknown,
+}
+
+impl ControllerType {
+    fn from_id(id: u8) -> Self {
+        match id {
+            1 => Self::JoyConL,
+            2 => Self::JoyConR,
+            3 => Self::ProController,
+            7 => Self::FamicomI,
+            8 => Self::FamicomII,
+            9 => Self::NesLeft,
+            10 => Self::NesRight,
+            11 => Self::Snes,
+            12 => Self::N64,
+            13 => Self::Genesis,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// What the controller tells about itself, for the controller details view
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    // e.g. "3.72"
+    pub firmware_version: String,
+    pub controller_type: ControllerType,
+    // e.g. "98:b6:e9:01:02:03"
+    pub mac: String,
+    // "#rrggbb" as stored in the SPI flash
+    pub body_color: String,
+    pub button_color: String,
+}
+
+/// Sends subcommands over one connection. The 4 bit packet counter has to increase with every
+/// output report, the controller drops repeated ones.
+pub struct Session<'a> {
+    connection: &'a mut dyn HidConnection,
+    packet_number: u8,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(connection: &'a mut dyn HidConnection) -> Self {
+        Self {
+            connection,
+            packet_number: 0,
+        }
+    }
+
+    /// Send a subcommand and wait for its acknowledgement, returns the whole 0x21 reply
+    pub fn subcommand(&mut self, id: u8, args: &[u8]) -> Result<Vec<u8>> {
+        let mut report = [0u8; OUTPUT_REPORT_SIZE];
+        if args.len() > OUTPUT_REPORT_SIZE - 11 {
+            return Err(anyhow!("Too many arguments for subcommand {:#04x}", id));
+        }
+        report[0] = OUTPUT_RUMBLE_AND_SUBCOMMAND;
+        report[2..10].copy_from_slice(&RUMBLE_NEUTRAL);
+        report[10] = id;
+        report[11..11 + args.len()].copy_from_slice(args);
+
+        for attempt in 1..=SUBCOMMAND_RETRIES {
+            report[1] = self.packet_number;
+            self.packet_number = (self.packet_number + 1) & 0x0f;
+            self.connection.write(&report)?;
+            match self.read_reply(id)? {
+                Some(data) => return Ok(data),
+                None => debug!(
+                    "No reply to subcommand {:#04x} (attempt {}/{})",
+                    id, attempt, SUBCOMMAND_RETRIES
+                ),
+            }
+        }
+        Err(anyhow!("Subcommand {:#04x} timed out", id))
+    }
+
+    fn read_reply(&mut self, id: u8) -> Result<Option<Vec<u8>>> {
+        let mut buf = [0u8; INPUT_REPORT_SIZE];
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        while Instant::now() < deadline {
+            let len = self.connection.read_timeout(&mut buf, READ_TIMEOUT_MS)?;
+            let Ok(report) = InputReport::new(&buf, len) else {
+                continue;
+            };
+            match report.expect(INPUT_SUBCOMMAND_REPLY, REPLY_DATA) {
+                Ok(()) => {}
+                // A regular input report
+                Err(ReportError::UnexpectedId { .. }) => continue,
+                Err(err) => return Err(err.into()),
+            }
+            // The reply to an earlier attempt
+            if report.u8(REPLY_SUBCOMMAND)? != id {
+                continue;
+            }
+            if report.u8(REPLY_ACK)? & ACK == 0 {
+                return Err(anyhow!("Subcommand {:#04x} was rejected", id));
+            }
+            return Ok(Some(report.bytes(0, report.len())?.to_vec()));
+        }
+        Ok(None)
+    }
+
+    pub fn set_report_mode(&mut self, mode: u8) -> Result<()> {
+        self.subcommand(SUBCOMMAND_SET_REPORT_MODE, &[mode])?;
+        Ok(())
+    }
+
+    /// Read `N` bytes of the SPI flash starting at `address`
+    pub fn read_spi<const N: usize>(&mut self, address: u32) -> Result<[u8; N]> {
+        if N > SPI_READ_MAX {
+            return Err(anyhow!(
+                "Can't read more than {} bytes at once",
+                SPI_READ_MAX
+            ));
+        }
+        let mut args = address.to_le_bytes().to_vec();
+        args.push(N as u8);
+        let reply = self.subcommand(SUBCOMMAND_SPI_READ, &args)?;
+        let report = InputReport::new(&reply, reply.len())?;
+        // The data starts with the address and size that were read
+        if report.bytes(REPLY_DATA, args.len())? != args {
+            return Err(anyhow!("Unexpected reply to SPI read at {:#06x}", address));
+        }
+        Ok(report.array(REPLY_DATA + args.len())?)
+    }
+
+    /// Only the type from the device info, without reading the SPI flash
+    pub fn controller_type(&mut self) -> Result<ControllerType> {
+        let reply = self.subcommand(SUBCOMMAND_DEVICE_INFO, &[])?;
+        let report = InputReport::new(&reply, reply.len())?;
+        Ok(ControllerType::from_id(report.u8(DEVICE_INFO_TYPE)?))
+    }
+
+    pub fn device_info(&mut self) -> Result<DeviceInfo> {
+        let reply = self.subcommand(SUBCOMMAND_DEVICE_INFO, &[])?;
+        let report = InputReport::new(&reply, reply.len())?;
+        let [major, minor] = report.array(DEVICE_INFO_FIRMWARE)?;
+        let mac: [u8; 6] = report.array(DEVICE_INFO_MAC)?;
+        let colors: [u8; 6] = self.read_spi(SPI_COLORS)?;
+        let (body, buttons) = colors.split_at(3);
+        Ok(DeviceInfo {
+            firmware_version: format!("{}.{:02}", major, minor),
+            controller_type: ControllerType::from_id(report.u8(DEVICE_INFO_TYPE)?),
+            mac: hex_string(&mac, ":"),
+            body_color: format!("#{}", hex_string(body, "")),
+            button_color: format!("#{}", hex_string(buttons, "")),
+        })
+    }
+}
+
+fn hex_string(bytes: &[u8], separator: &str) -> String {
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    hex.join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ControllerType, DeviceInfo, Session, INPUT_FULL};
+    use crate::api::report::ReportError;
+    use crate::api::transport::fixture::Fixture;
+    use crate::api::transport::replay::ReplayTransport;
+    use crate::api::transport::HidTransport;
+    use crate::api::uhid::profiles::PRO_CONTROLLER;
+
+    const PATH: &str = "/dev/replay/pro_controller_bt";
+
+    #[test]
+    fn test_device_info() {
+        let transport = ReplayTransport::new(vec![Fixture::named("pro_controller_bt")])
+            .respond_with(PRO_CONTROLLER.respond);
+        let device = transport.devices().remove(0);
+        let mut connection = transport.open(&device).unwrap();
+        let mut session = Session::new(connection.as_mut());
+
+        assert_eq!(
+            session.device_info().unwrap(),
+            DeviceInfo {
+                firmware_version: "3.72".to_string(),
+                controller_type: ControllerType::ProController,
+                mac: "02:c7:00:00:20:09".to_string(),
+                body_color: "#323232".to_string(),
+                button_color: "#ffffff".to_string(),
+            }
+        );
+        session.set_report_mode(INPUT_FULL).unwrap();
+        assert!(session.read_spi::<0x20>(0x6050).is_err());
+
+        let output_reports = transport.output_reports(PATH);
+        let subcommands: Vec<_> = output_reports
+            .iter()
+            .map(|report| (report[0], report[1], report[10]))
+            .collect();
+        assert_eq!(
+            subcommands,
+            vec![(0x01, 0, 0x02), (0x01, 1, 0x10), (0x01, 2, 0x03)]
+        );
+        assert_eq!(&output_reports[1][11..16], &[0x50, 0x60, 0x00, 0x00, 0x06]);
+        assert_eq!(output_reports[2][11], 0x30);
+    }
+
+    #[test]
+    fn test_subcommand_timeout() {
+        // Without a responder nothing acknowledges the subcommand
+        let transport = ReplayTransport::new(vec![Fixture::named("pro_controller_bt")]);
+        let device = transport.devices().remove(0);
+        let mut connection = transport.open(&device).unwrap();
+
+        assert!(Session::new(connection.as_mut()).device_info().is_err());
+        assert_eq!(transport.output_reports(PATH).len(), 3);
+    }
+
+    #[test]
+    fn test_short_reply() {
+        // The reply ends before the subcommand it acknowledges
+        fn respond(output: &[u8], input: &[u8]) -> Option<Vec<u8>> {
+            let mut reply = (PRO_CONTROLLER.respond)(output, input)?;
+            reply.truncate(14);
+            Some(reply)
+        }
+
+        let transport =
+            ReplayTransport::new(vec![Fixture::named("pro_controller_bt")]).respond_with(respond);
+        let device = transport.devices().remove(0);
+        let mut connection = transport.open(&device).unwrap();
+
+        let err = Session::new(connection.as_mut())
+            .controller_type()
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ReportError>(),
+            Some(&ReportError::Short {
+                report_id: 0x21,
+                len: 14,
+                needed: 15
+            })
+        );
+    }
+
+    #[test]
+    fn test_reply_with_unexpected_id() {
+        // Answered with another report than 0x21, which is no reply at all
+        fn respond(output: &[u8], input: &[u8]) -> Option<Vec<u8>> {
+            let mut reply = (PRO_CONTROLLER.respond)(output, input)?;
+            reply[0] = 0x31;
+            Some(reply)
+        }
+
+        let transport =
+            ReplayTransport::new(vec![Fixture::named("pro_controller_bt")]).respond_with(respond);
+        let device = transport.devices().remove(0);
+        let mut connection = transport.open(&device).unwrap();
+
+        assert!(Session::new(connection.as_mut()).controller_type().is_err());
+        assert_eq!(transport.output_reports(PATH).len(), 3);
+    }
+}
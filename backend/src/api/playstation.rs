@@ -1,30 +1,38 @@
 use std::cmp;
 
-use anyhow::Result;
-use hidapi::{DeviceInfo, HidApi};
+use anyhow::{anyhow, Result};
 use log::error;
-use log::info;
+use log::{debug, info};
 use serde::{Deserialize, Serialize};
 
 use crate::controller::Status;
 
+use super::database;
+use super::diagnostics;
+use super::driver::ControllerDriver;
+use super::gamepad::{buttons_from_bits, Button, Dpad, GamepadState, Motion, Stick, TouchPoint};
+use super::report::{InputReport, ReportError};
+use super::transport::{HidConnection, HidDevice, HidTransport};
 use super::Controller;
 
 pub const DS_VENDOR_ID: u16 = 0x054c;
 
-// Dualshock4 product ID before playstation update 5.50
-pub const DS4_OLD_PRODUCT_ID: u16 = 0x05c4;
-// Dualshock4 product ID changed after playstation update 5.50
-pub const DS4_NEW_PRODUCT_ID: u16 = 0x09cc;
-
 const DS4_INPUT_REPORT_USB: u8 = 0x01;
 const DS4_INPUT_REPORT_USB_SIZE: usize = 64;
 const DS4_INPUT_REPORT_BT: u8 = 0x11;
 const DS4_INPUT_REPORT_BT_SIZE: usize = 78;
+// Offset of the battery status in the state shared by the USB and Bluetooth reports
+const DS4_INPUT_STATUS: usize = 29;
 const DS4_STATUS_BATTERY_CAPACITY: u8 = 0b1111;
 const DS4_STATUS0_CABLE_STATE: u8 = 1 << 4;
 const DS4_BATTERY_STATUS_FULL: u8 = 11;
 
+// Bluetooth reports end with a CRC32 of the report prefixed by this seed byte (hid-playstation)
+const PS_INPUT_CRC32_SEED: u8 = 0xa1;
+const PS_CRC32_SIZE: usize = 4;
+// A corrupted report is followed by a good one a few milliseconds later
+const CRC_RETRIES: usize = 3;
+
 // DualSense
 pub const DS_PRODUCT_ID: u16 = 0x0ce6;
 
@@ -35,9 +43,45 @@ const DS_INPUT_REPORT_BT: u8 = 0x31;
 const DS_INPUT_REPORT_BT_SIZE: usize = 78;
 const DS_INPUT_REPORT_USB: u8 = 0x01;
 const DS_INPUT_REPORT_USB_SIZE: usize = 64;
+// Offsets in the state shared by the USB and Bluetooth reports
+const DS_INPUT_X: usize = 0;
+const DS_INPUT_Y: usize = 1;
+const DS_INPUT_RX: usize = 2;
+const DS_INPUT_RY: usize = 3;
+const DS_INPUT_Z: usize = 4;
+const DS_INPUT_RZ: usize = 5;
+const DS_INPUT_BUTTONS: usize = 7;
+const DS_INPUT_GYRO: usize = 15;
+const DS_INPUT_ACCEL: usize = 21;
+const DS_INPUT_SENSOR_TIMESTAMP: usize = 27;
+const DS_INPUT_POINTS: usize = 32;
+const DS_TOUCH_POINT_SIZE: usize = 4;
+const DS_INPUT_STATUS: usize = 52;
 const DS_STATUS_BATTERY_CAPACITY: u8 = 0b1111;
 const DS_STATUS_CHARGING: u8 = 0b1111 << 4;
 const DS_STATUS_CHARGING_SHIFT: u8 = 4;
+const DS_BUTTONS_HAT: u32 = 0b1111;
+const DS_BUTTONS: &[(u32, Button)] = &[
+    (1 << 4, Button::Square),
+    (1 << 5, Button::Cross),
+    (1 << 6, Button::Circle),
+    (1 << 7, Button::Triangle),
+    (1 << 8, Button::L1),
+    (1 << 9, Button::R1),
+    (1 << 10, Button::L2),
+    (1 << 11, Button::R2),
+    (1 << 12, Button::Create),
+    (1 << 13, Button::Options),
+    (1 << 14, Button::L3),
+    (1 << 15, Button::R3),
+    (1 << 16, Button::Ps),
+    (1 << 17, Button::Touchpad),
+    (1 << 18, Button::Mute),
+];
+const DS_TOUCH_POINT_INACTIVE: u8 = 1 << 7;
+// Nominal resolution of the motion sensors, as used by hid-playstation
+const DS_GYRO_RES_PER_DEG_S: f32 = 1024.0;
+const DS_ACC_RES_PER_G: f32 = 8192.0;
 
 // DualShock3
 pub const DS3_PRODUCT_ID: u16 = 0x0268;
@@ -47,25 +91,31 @@ const DS3_INPUT_REPORT_SIZE: usize = 49;
 const DS3_INPUT_REPORT_BATTERY_OFFSET: usize = 30;
 const DS3_INPUT_REPORT_BATTERY_CHARGING: u8 = 0xee;
 const DS3_INPUT_REPORT_CHARGING_BIT: u8 = 0x01;
-
-#[repr(C, packed)]
-#[derive(Copy, Clone, Debug, Deserialize)]
-struct DualSenseTouchPoint {
-    contact: u8,
-    x_lo: u8,
-    _bitfield_align_1: [u8; 0],
-    _bitfield_1: BitfieldUnit<[u8; 1usize]>,
-    y_hi: u8,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Deserialize)]
-struct BitfieldUnit<Storage> {
-    storage: Storage,
-}
-
-#[repr(C, packed)]
-#[derive(Copy, Clone, Debug, Deserialize)]
+// An active pad sends a report every few milliseconds
+const DS3_IDLE_TIMEOUT_MS: i32 = 500;
+const DS3_READ_TIMEOUT_MS: i32 = 2000;
+
+// Operational mode, the same requests hid-sony makes in sixaxis_set_operational_usb/_bt
+const DS3_FEATURE_REPORT_MAC: u8 = 0xf2;
+const DS3_FEATURE_REPORT_MAC_SIZE: usize = 17;
+const DS3_FEATURE_REPORT_HOST_MAC: u8 = 0xf5;
+const DS3_FEATURE_REPORT_HOST_MAC_SIZE: usize = 8;
+const DS3_FEATURE_REPORT_ENABLE_BT: [u8; 5] = [0xf4, 0x42, 0x03, 0x00, 0x00];
+
+// Output report 0x01 with the motors off and the four LEDs solid, see sixaxis_output_report_01
+// in hid-sony
+const DS3_OUTPUT_REPORT: [u8; 36] = [
+    0x01, 0x01, 0xff, 0x00, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x27, 0x10, 0x00, 0x32,
+    0xff, 0x27, 0x10, 0x00, 0x32, 0xff, 0x27, 0x10, 0x00, 0x32, 0xff, 0x27, 0x10, 0x00, 0x32, 0x00,
+    0x00, 0x00, 0x00, 0x00,
+];
+const DS3_OUTPUT_REPORT_LEDS_OFFSET: usize = 10;
+// LED 1 is bit 1, bit 0 is unused
+const DS3_LED_PLAYER_1: u8 = 1 << 1;
+
+/// The state the DualSense sends over USB and Bluetooth, see dualsense_input_report in
+/// hid-playstation
+#[derive(Clone, Debug, PartialEq)]
 struct DualSenseInputReport {
     x: u8,
     y: u8,
@@ -73,75 +123,120 @@ struct DualSenseInputReport {
     ry: u8,
     z: u8,
     rz: u8,
-    seq_number: u8,
-    buttons: [u8; 4usize],
-    reserved: [u8; 4usize],
-    gyro: [u16; 3usize],
-    accel: [u16; 3usize],
+    buttons: u32,
+    gyro: [i16; 3],
+    accel: [i16; 3],
     sensor_timestamp: u32,
-    reserved2: u8,
-    points: [DualSenseTouchPoint; 2usize],
-    reserved3: [u8; 12usize],
+    points: [[u8; DS_TOUCH_POINT_SIZE]; 2],
     status: u8,
-    reserved4: [u8; 10usize],
 }
 
-#[repr(C, packed)]
-#[derive(Copy, Clone, Debug, Deserialize)]
-struct DualShock4InputReportCommon {
-    x: u8,
-    y: u8,
-    rx: u8,
-    ry: u8,
-    buttons: [u8; 3usize],
-    z: u8,
-    rz: u8,
-    sensor_timestamp: u16,
-    sensor_temperature: u8,
-    gyro: [u16; 3usize],
-    accel: [u16; 3usize],
-    reserved2: [u8; 5usize],
-    status: [u8; 2usize],
-    reserved3: u8,
-}
+impl DualSenseInputReport {
+    /// The state starts after the report ID over USB, and after the report ID and a tag over
+    /// Bluetooth
+    fn parse(report: &InputReport) -> Result<Self, ReportError> {
+        let header = match report.id() {
+            DS_INPUT_REPORT_USB => {
+                report.expect(DS_INPUT_REPORT_USB, DS_INPUT_REPORT_USB_SIZE)?;
+                1
+            }
+            DS_INPUT_REPORT_BT => {
+                report.expect(DS_INPUT_REPORT_BT, DS_INPUT_REPORT_BT_SIZE)?;
+                2
+            }
+            report_id => return Err(ReportError::UnexpectedId { report_id }),
+        };
+        let field = |offset: usize| report.u8(header + offset);
+        let axes = |offset: usize| -> Result<[i16; 3], ReportError> {
+            Ok([
+                report.i16_le(header + offset)?,
+                report.i16_le(header + offset + 2)?,
+                report.i16_le(header + offset + 4)?,
+            ])
+        };
+        let point =
+            |index: usize| report.array(header + DS_INPUT_POINTS + index * DS_TOUCH_POINT_SIZE);
+        Ok(Self {
+            x: field(DS_INPUT_X)?,
+            y: field(DS_INPUT_Y)?,
+            rx: field(DS_INPUT_RX)?,
+            ry: field(DS_INPUT_RY)?,
+            z: field(DS_INPUT_Z)?,
+            rz: field(DS_INPUT_RZ)?,
+            buttons: report.u32_le(header + DS_INPUT_BUTTONS)?,
+            gyro: axes(DS_INPUT_GYRO)?,
+            accel: axes(DS_INPUT_ACCEL)?,
+            sensor_timestamp: report.u32_le(header + DS_INPUT_SENSOR_TIMESTAMP)?,
+            points: [point(0)?, point(1)?],
+            status: field(DS_INPUT_STATUS)?,
+        })
+    }
 
-#[repr(C, packed)]
-#[derive(Copy, Clone, Debug, Deserialize)]
-struct Dualshock4InputReportBT {
-    report_id: u8, /* 0x11 */
-    reserved: [u8; 2usize],
-    common: DualShock4InputReportCommon,
-    num_touch_reports: u8,
-    touch_reports: [DualShock4TouchReport; 4usize],
-    reserved2: [u8; 2usize],
-    crc32: u32,
+    fn gamepad_state(&self) -> GamepadState {
+        GamepadState {
+            buttons: buttons_from_bits(self.buttons, DS_BUTTONS),
+            dpad: Dpad::from_hat((self.buttons & DS_BUTTONS_HAT) as u8),
+            left_stick: Stick {
+                x: self.x,
+                y: self.y,
+            },
+            right_stick: Stick {
+                x: self.rx,
+                y: self.ry,
+            },
+            left_trigger: self.z,
+            right_trigger: self.rz,
+            touch_points: self.points.iter().filter_map(touch_point).collect(),
+            motion: Motion {
+                gyro: self.gyro.map(|value| value as f32 / DS_GYRO_RES_PER_DEG_S),
+                accel: self.accel.map(|value| value as f32 / DS_ACC_RES_PER_G),
+                // Counted in units of 0.33µs
+                timestamp_us: self.sensor_timestamp / 3,
+            },
+        }
+    }
 }
 
-#[repr(C, packed)]
-#[derive(Copy, Clone, Debug, Deserialize)]
-struct Dualshock4InputReportUSB {
-    report_id: u8, /* 0x01 */
-    common: DualShock4InputReportCommon,
-    num_touch_reports: u8,
-    touch_reports: [DualShock4TouchReport; 3usize],
-    reserved: [u8; 3usize],
+/// `None` when no finger is on this point. The 12 bit coordinates are split around the third
+/// byte, which holds the high bits of x and the low bits of y.
+fn touch_point(point: &[u8; DS_TOUCH_POINT_SIZE]) -> Option<TouchPoint> {
+    let [contact, x_lo, x_hi_y_lo, y_hi] = *point;
+    if contact & DS_TOUCH_POINT_INACTIVE != 0 {
+        return None;
+    }
+    Some(TouchPoint {
+        id: contact & !DS_TOUCH_POINT_INACTIVE,
+        x: x_lo as u16 | ((x_hi_y_lo & 0x0f) as u16) << 8,
+        y: (x_hi_y_lo >> 4) as u16 | (y_hi as u16) << 4,
+    })
 }
 
-#[repr(C, packed)]
-#[derive(Copy, Clone, Debug, Deserialize)]
-struct DualShock4TouchReport {
-    timestamp: u8,
-    points: [DualShock4TouchPoint; 2usize],
+/// The battery part of the DualShock 4 input reports, see dualshock4_input_report_common in
+/// hid-playstation
+#[derive(Clone, Debug, PartialEq)]
+struct DualShock4InputReport {
+    status: u8,
 }
 
-#[repr(C, packed)]
-#[derive(Copy, Clone, Debug, Deserialize)]
-struct DualShock4TouchPoint {
-    contact: u8,
-    x_lo: u8,
-    _bitfield_align_1: [u8; 0],
-    _bitfield_1: BitfieldUnit<[u8; 1usize]>,
-    y_hi: u8,
+impl DualShock4InputReport {
+    /// The state starts after the report ID over USB, and after the report ID and two more
+    /// bytes over Bluetooth
+    fn parse(report: &InputReport) -> Result<Self, ReportError> {
+        let header = match report.id() {
+            DS4_INPUT_REPORT_USB => {
+                report.expect(DS4_INPUT_REPORT_USB, DS4_INPUT_REPORT_USB_SIZE)?;
+                1
+            }
+            DS4_INPUT_REPORT_BT => {
+                report.expect(DS4_INPUT_REPORT_BT, DS4_INPUT_REPORT_BT_SIZE)?;
+                3
+            }
+            report_id => return Err(ReportError::UnexpectedId { report_id }),
+        };
+        Ok(Self {
+            status: report.u8(header + DS4_INPUT_STATUS)?,
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -151,32 +246,64 @@ struct BatteryInfo {
     status: Status,
 }
 
+pub struct PlayStationDriver;
+
+impl ControllerDriver for PlayStationDriver {
+    fn name(&self) -> &'static str {
+        "playstation"
+    }
+
+    fn matches_hid(&self, device: &HidDevice) -> bool {
+        database::get().driver(device.vendor_id, device.product_id) == Some(self.name())
+    }
+
+    fn probe_hid(&self, device: &HidDevice) -> Controller {
+        let name = database::get()
+            .name(device.vendor_id, device.product_id)
+            .unwrap_or("DualShock 4");
+        debug!("Found {} controller: {:?}", name, device);
+        Controller::from_hidapi(device, name, 0, Status::Unknown)
+    }
+
+    fn read_battery_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        transport: &dyn HidTransport,
+    ) -> Result<()> {
+        match device.product_id {
+            DS3_PRODUCT_ID => parse_dualshock3_controller_data(controller, device, transport),
+            DS_PRODUCT_ID | DS_EDGE_PRODUCT_ID => {
+                parse_dualsense_controller_data(controller, device, transport)
+            }
+            _ => parse_dualshock_controller_data(controller, device, transport),
+        }
+    }
+
+    fn forget(&self, device_path: &str) {
+        diagnostics::forget(device_path);
+    }
+}
+
 pub fn parse_dualshock_controller_data(
-    device_info: &DeviceInfo,
-    hidapi: &HidApi,
-) -> Result<Controller> {
-    let device = device_info.open_device(hidapi)?;
-    let mut controller = Controller::from_hidapi(device_info, "DualShock 4", 0, Status::Unknown);
-    let mut buf = vec![0u8; DS4_INPUT_REPORT_BT_SIZE];
-    let res = device.read(&mut buf[..])?;
-    let mut battery_data: u8 = 0;
-    let mut cable_state: u8 = 0;
-    if !controller.bluetooth && buf[0] == DS4_INPUT_REPORT_USB && res == DS4_INPUT_REPORT_USB_SIZE {
-        let usb_report: Dualshock4InputReportUSB = bincode::deserialize(&buf)?;
-        let ds4_report: DualShock4InputReportCommon = usb_report.common;
-        battery_data = ds4_report.status[0] & DS4_STATUS_BATTERY_CAPACITY;
-        cable_state = ds4_report.status[0] & DS4_STATUS0_CABLE_STATE;
-    } else if controller.bluetooth
-        && buf[0] == DS4_INPUT_REPORT_BT
-        && res == DS4_INPUT_REPORT_BT_SIZE
+    controller: &mut Controller,
+    device: &HidDevice,
+    transport: &dyn HidTransport,
+) -> Result<()> {
+    let mut device = transport.open(device)?;
+    let mut buf = [0u8; DS4_INPUT_REPORT_BT_SIZE];
+    let res = read_intact_report(device.as_mut(), &mut buf[..], controller)?;
+    let ds4_report = match InputReport::new(&buf, res)
+        .and_then(|report| DualShock4InputReport::parse(&report))
     {
-        let bt_report: Dualshock4InputReportBT = bincode::deserialize(&buf)?;
-        let ds4_report: DualShock4InputReportCommon = bt_report.common;
-        battery_data = ds4_report.status[0] & DS4_STATUS_BATTERY_CAPACITY;
-        cable_state = ds4_report.status[0] & DS4_STATUS0_CABLE_STATE;
-    } else {
-        error!("Unhandled report ID: {}", buf[0]);
-    }
+        Ok(ds4_report) => ds4_report,
+        Err(err) => {
+            error!("Unhandled report from {}: {}", controller.name, err);
+            return Ok(());
+        }
+    };
+    let battery_data = ds4_report.status & DS4_STATUS_BATTERY_CAPACITY;
+    let cable_state = ds4_report.status & DS4_STATUS0_CABLE_STATE;
 
     let mut charging_status: u8 = 0x0;
     if cable_state > 0 {
@@ -191,31 +318,28 @@ pub fn parse_dualshock_controller_data(
     let battery_status = get_battery_status(charging_status, battery_data);
     controller.capacity = battery_status.capacity;
     controller.status = battery_status.status;
-    Ok(controller)
+    Ok(())
 }
 
 pub fn parse_dualsense_controller_data(
-    device_info: &DeviceInfo,
-    hidapi: &HidApi,
-    name: &str,
-) -> Result<Controller> {
-    let mut controller = Controller::from_hidapi(device_info, name, 0, Status::Unknown);
-    let device = device_info.open_device(hidapi)?;
-
-    // Read data from device_info
-    let mut buf = [0u8; DS_INPUT_REPORT_BT_SIZE];
-    let res = device.read(&mut buf[..])?;
+    controller: &mut Controller,
+    device: &HidDevice,
+    transport: &dyn HidTransport,
+) -> Result<()> {
+    let mut device = transport.open(device)?;
 
-    let ds_report: DualSenseInputReport;
-    if !controller.bluetooth && buf[0] == DS_INPUT_REPORT_USB && res == DS_INPUT_REPORT_USB_SIZE {
-        ds_report = bincode::deserialize(&buf[1..])?;
-    } else if controller.bluetooth && buf[0] == DS_INPUT_REPORT_BT && res == DS_INPUT_REPORT_BT_SIZE
-    {
-        ds_report = bincode::deserialize(&buf[2..])?;
-    } else {
-        error!("Unhandled report ID: {}", buf[0]);
-        return Ok(controller);
-    }
+    // Read data from device
+    let mut buf = [0u8; DS_INPUT_REPORT_BT_SIZE];
+    let res = read_intact_report(device.as_mut(), &mut buf[..], controller)?;
+
+    let ds_report =
+        match InputReport::new(&buf, res).and_then(|report| DualSenseInputReport::parse(&report)) {
+            Ok(ds_report) => ds_report,
+            Err(err) => {
+                error!("Unhandled report from {}: {}", controller.name, err);
+                return Ok(());
+            }
+        };
 
     let battery_data = ds_report.status & DS_STATUS_BATTERY_CAPACITY;
     let charging_status = (ds_report.status & DS_STATUS_CHARGING) >> DS_STATUS_CHARGING_SHIFT;
@@ -223,7 +347,77 @@ pub fn parse_dualsense_controller_data(
     controller.capacity = battery_status.capacity;
     controller.status = battery_status.status;
 
-    Ok(controller)
+    Ok(())
+}
+
+/// Read an input report, skipping Bluetooth reports that fail their CRC check. Those are
+/// counted in the diagnostics of the controller.
+fn read_intact_report(
+    device: &mut dyn HidConnection,
+    buf: &mut [u8],
+    controller: &Controller,
+) -> Result<usize> {
+    for _ in 0..CRC_RETRIES {
+        let len = device.read(buf)?;
+        if has_valid_crc(&buf[..len]) {
+            return Ok(len);
+        }
+        debug!("Discarding corrupted report from {}", controller.id());
+        diagnostics::record_crc_failure(&controller.id());
+    }
+    Err(anyhow!(
+        "no intact report from {} in {} tries",
+        controller.id(),
+        CRC_RETRIES
+    ))
+}
+
+/// Whether a report arrived intact. Only the DualShock 4 and DualSense Bluetooth reports carry
+/// a CRC, every other report passes.
+pub fn has_valid_crc(report: &[u8]) -> bool {
+    let is_bt_report = matches!(
+        (report.first(), report.len()),
+        (Some(&DS4_INPUT_REPORT_BT), DS4_INPUT_REPORT_BT_SIZE)
+            | (Some(&DS_INPUT_REPORT_BT), DS_INPUT_REPORT_BT_SIZE)
+    );
+    if !is_bt_report {
+        return true;
+    }
+    let (data, crc) = report.split_at(report.len() - PS_CRC32_SIZE);
+    let crc = u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]);
+    crc32(PS_INPUT_CRC32_SEED, data) == crc
+}
+
+/// CRC-32 (IEEE) of `data` prefixed by `seed`
+fn crc32(seed: u8, data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in std::iter::once(&seed).chain(data) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+pub fn is_dualsense(device: &HidDevice) -> bool {
+    device.vendor_id == DS_VENDOR_ID
+        && matches!(device.product_id, DS_PRODUCT_ID | DS_EDGE_PRODUCT_ID)
+}
+
+/// Decode a DualSense input report as read from hidraw, over USB or Bluetooth. Returns `None`
+/// for reports without the full state, like the short one sent over Bluetooth until the kernel
+/// driver enables the full report.
+pub fn decode_dualsense_input(buf: &[u8]) -> Result<Option<GamepadState>> {
+    match DualSenseInputReport::parse(&InputReport::new(buf, buf.len())?) {
+        Ok(report) => Ok(Some(report.gamepad_state())),
+        Err(ReportError::Short { .. } | ReportError::UnexpectedId { .. }) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
 }
 
 fn get_battery_status(charging_status: u8, battery_data: u8) -> BatteryInfo {
@@ -256,25 +450,30 @@ fn get_battery_status(charging_status: u8, battery_data: u8) -> BatteryInfo {
 }
 
 pub fn parse_dualshock3_controller_data(
-    device_info: &DeviceInfo,
-    hidapi: &HidApi,
-    name: &str,
-) -> Result<Controller> {
-    let mut controller = Controller::from_hidapi(device_info, name, 0, Status::Unknown);
-    let device = device_info.open_device(hidapi)?;
-
-    // Read data from device_info
+    controller: &mut Controller,
+    device: &HidDevice,
+    transport: &dyn HidTransport,
+) -> Result<()> {
+    let bluetooth = device.interface_number == -1;
+    let mut device = transport.open(device)?;
+
     // If the DualShock 3 controller is not "activated", if its LEDs are blinking, it will not
-    // respond to reads, so we will timeout after 5s
+    // send any reports until it is put in operational mode
     let mut buf = [0u8; DS3_INPUT_REPORT_SIZE];
-    let res = device.read_timeout(&mut buf[..], 2000)?;
+    let mut res = device.read_timeout(&mut buf[..], DS3_IDLE_TIMEOUT_MS)?;
 
     if res == 0 {
-        info!("Inactive DualShock 3 controller");
-        return Ok(controller);
+        info!("Inactive DualShock 3 controller, activating it");
+        activate_dualshock3(device.as_mut(), bluetooth)?;
+        res = device.read_timeout(&mut buf[..], DS3_READ_TIMEOUT_MS)?;
+    }
+    if res == 0 {
+        info!("DualShock 3 controller didn't respond to activation");
+        return Ok(());
     }
 
-    if buf[1] == 0xff {
+    let report = InputReport::new(&buf, res)?;
+    if report.u8(1) == Ok(0xff) {
         /* Comment coppied from the linux driver at drivers/hid/hid-sony.c
          * When connected via Bluetooth the Sixaxis occasionally sends
          * a report with the second byte 0xff and the rest zeroed.
@@ -283,23 +482,46 @@ pub fn parse_dualshock3_controller_data(
          * controller must be ignored to avoid generating false input
          * events.
          */
-        return Ok(controller);
+        return Ok(());
     }
 
-    let battery_data: u8;
-    if buf[0] == DS3_INPUT_REPORT && res == DS3_INPUT_REPORT_SIZE
+    let battery_data = match report
+        .expect(DS3_INPUT_REPORT, DS3_INPUT_REPORT_SIZE)
+        .and_then(|_| report.u8(DS3_INPUT_REPORT_BATTERY_OFFSET))
     {
-        battery_data = buf[DS3_INPUT_REPORT_BATTERY_OFFSET];
-    } else {
-        error!("Unhandled report ID: {}", buf[0]);
-        return Ok(controller);
-    }
+        Ok(battery_data) => battery_data,
+        Err(err) => {
+            error!("Unhandled report from {}: {}", controller.name, err);
+            return Ok(());
+        }
+    };
 
     let battery_status = get_ds3_battery_status(battery_data);
     controller.capacity = battery_status.capacity;
     controller.status = battery_status.status;
 
-    Ok(controller)
+    Ok(())
+}
+
+/// Put a DualShock 3 in operational mode so it starts sending input reports, and light the LED
+/// of player 1 instead of the blinking ones
+fn activate_dualshock3(device: &mut dyn HidConnection, bluetooth: bool) -> Result<()> {
+    if bluetooth {
+        device.send_feature_report(&DS3_FEATURE_REPORT_ENABLE_BT)?;
+    } else {
+        // Over USB reading these reports is what enables the controller
+        let mut buf = [0u8; DS3_FEATURE_REPORT_MAC_SIZE];
+        buf[0] = DS3_FEATURE_REPORT_MAC;
+        device.get_feature_report(&mut buf)?;
+        let mut buf = [0u8; DS3_FEATURE_REPORT_HOST_MAC_SIZE];
+        buf[0] = DS3_FEATURE_REPORT_HOST_MAC;
+        device.get_feature_report(&mut buf)?;
+    }
+
+    let mut report = DS3_OUTPUT_REPORT;
+    report[DS3_OUTPUT_REPORT_LEDS_OFFSET] = DS3_LED_PLAYER_1;
+    device.write(&report)?;
+    Ok(())
 }
 
 fn get_ds3_battery_status(battery_data: u8) -> BatteryInfo {
@@ -308,15 +530,25 @@ fn get_ds3_battery_status(battery_data: u8) -> BatteryInfo {
      * sixaxis_parse_report() from drivers/hid/hid-sony.c
      */
 
-    let mut battery_info = BatteryInfo{capacity:75, status:Status::Unknown};
+    let mut battery_info = BatteryInfo {
+        capacity: 75,
+        status: Status::Unknown,
+    };
     if battery_data >= DS3_INPUT_REPORT_BATTERY_CHARGING {
         //if the controller is charging, it does not report exact battery capacity
         battery_info.status = match battery_data & DS3_INPUT_REPORT_CHARGING_BIT {
             0 => Status::Charging,
-            _ => {battery_info.capacity = 100; Status::Unknown},
+            _ => {
+                battery_info.capacity = 100;
+                Status::Unknown
+            }
         };
     } else {
-        let index: usize = if battery_data <= 5 {battery_data.into()} else {5};
+        let index: usize = if battery_data <= 5 {
+            battery_data.into()
+        } else {
+            5
+        };
         let dualshock3_battery_capacity_values = [0, 1, 25, 50, 75, 100];
         battery_info.capacity = dualshock3_battery_capacity_values[index];
         battery_info.status = Status::Discharging;
@@ -327,14 +559,249 @@ fn get_ds3_battery_status(battery_data: u8) -> BatteryInfo {
 
 #[cfg(test)]
 mod tests {
-    use crate::api::playstation::{DualSenseInputReport, DS_INPUT_REPORT_USB_SIZE};
+    use crate::api::diagnostics;
+    use crate::api::gamepad::{Button, Dpad, Stick, TouchPoint};
+    use crate::api::playstation::{
+        crc32, decode_dualsense_input, has_valid_crc, parse_dualsense_controller_data,
+        parse_dualshock3_controller_data, parse_dualshock_controller_data, DualSenseInputReport,
+        DualShock4InputReport, DS3_INPUT_REPORT_BATTERY_OFFSET, DS_INPUT_REPORT_USB_SIZE,
+    };
+    use crate::api::report::{InputReport, ReportError};
+    use crate::api::transport::fixture::{Fixture, Report, ReportKind};
+    use crate::api::transport::replay::ReplayTransport;
+    use crate::api::transport::HidTransport;
+    use crate::controller::{Controller, Status};
+
+    fn input_report(data: &[u8]) -> InputReport<'_> {
+        InputReport::new(data, data.len()).unwrap()
+    }
 
     #[test]
-    fn test_dualsense_input_report_struct_size() {
-        // Common input report size equals the size of the USB report minus 1 byte for the ReportID
+    fn test_input_report_layouts() {
+        // The battery status of the first recorded report of each, see the battery tests
+        let status = |name: &str| {
+            let fixture = Fixture::named(name);
+            let report = input_report(&fixture.reports[0].data);
+            match name {
+                "dualsense_usb" | "dualsense_bt" => {
+                    DualSenseInputReport::parse(&report).unwrap().status
+                }
+                _ => DualShock4InputReport::parse(&report).unwrap().status,
+            }
+        };
+        assert_eq!(status("dualsense_usb"), 0x06);
+        assert_eq!(status("dualsense_bt"), 0x13);
+        assert_eq!(status("ds4_usb"), 0x15);
+        assert_eq!(status("ds4_bt"), 0x08);
+
+        // Reports cut short, and the short report a DualSense sends over Bluetooth at first
+        let usb = Fixture::named("dualsense_usb").reports[0].data.clone();
+        let report = InputReport::new(&usb, DS_INPUT_REPORT_USB_SIZE - 1).unwrap();
+        assert_eq!(
+            DualSenseInputReport::parse(&report),
+            Err(ReportError::Short {
+                report_id: 0x01,
+                len: DS_INPUT_REPORT_USB_SIZE - 1,
+                needed: DS_INPUT_REPORT_USB_SIZE
+            })
+        );
+        let mut bt = usb.clone();
+        bt[0] = 0x31;
+        assert!(matches!(
+            DualSenseInputReport::parse(&input_report(&bt)),
+            Err(ReportError::Short { .. })
+        ));
+        let ds4_bt = Fixture::named("ds4_bt").reports[0].data.clone();
+        assert!(matches!(
+            DualShock4InputReport::parse(&input_report(&ds4_bt[..40])),
+            Err(ReportError::Short { .. })
+        ));
+        // A DualShock 4 report isn't a DualSense one
+        assert_eq!(
+            DualSenseInputReport::parse(&input_report(&ds4_bt)),
+            Err(ReportError::UnexpectedId { report_id: 0x11 })
+        );
         assert_eq!(
-            std::mem::size_of::<DualSenseInputReport>(),
-            DS_INPUT_REPORT_USB_SIZE - 1
+            DualShock4InputReport::parse(&input_report(&[0x05; 64])),
+            Err(ReportError::UnexpectedId { report_id: 0x05 })
         );
     }
+
+    #[test]
+    fn test_dualshock3_report_layout() {
+        // A report cut short leaves the battery unknown instead of reading stale bytes
+        let mut fixture = Fixture::named("ds3_usb");
+        fixture.reports.truncate(1);
+        let ds3_report = &mut fixture.reports[0].data;
+        assert_eq!(ds3_report[DS3_INPUT_REPORT_BATTERY_OFFSET], 0x03);
+        ds3_report.truncate(DS3_INPUT_REPORT_BATTERY_OFFSET);
+        let transport = ReplayTransport::new(vec![fixture]);
+        let device = &transport.devices()[0];
+
+        let mut controller = Controller::from_hidapi(device, "DualShock3", 0, Status::Unknown);
+        parse_dualshock3_controller_data(&mut controller, device, &transport).unwrap();
+        assert_eq!(
+            (controller.capacity, controller.status),
+            (0, Status::Unknown)
+        );
+    }
+
+    #[test]
+    fn test_decode_dualsense_input() {
+        let mut report = [0u8; DS_INPUT_REPORT_USB_SIZE];
+        report[..7].copy_from_slice(&[0x01, 0x00, 0xff, 0x80, 0x7f, 0x10, 0xff]);
+        // Hat down-left, cross and triangle, R1, PS
+        report[8..11].copy_from_slice(&[0xa5, 0x02, 0x01]);
+        // Gyro pitch 1024 (1°/s), accel y -8192 (-1g)
+        report[16..18].copy_from_slice(&1024i16.to_le_bytes());
+        report[24..26].copy_from_slice(&(-8192i16).to_le_bytes());
+        report[28..32].copy_from_slice(&300u32.to_le_bytes());
+        // First touch point active at (1919, 1079), second one lifted
+        report[33..37].copy_from_slice(&[0x05, 0x7f, 0x77, 0x43]);
+        report[37] = 0x86;
+
+        let state = decode_dualsense_input(&report).unwrap().unwrap();
+        assert_eq!(
+            state.buttons,
+            vec![Button::Cross, Button::Triangle, Button::R1, Button::Ps]
+        );
+        assert_eq!(state.dpad, Dpad::DownLeft);
+        assert_eq!(state.left_stick, Stick { x: 0x00, y: 0xff });
+        assert_eq!(state.right_stick, Stick { x: 0x80, y: 0x7f });
+        assert_eq!((state.left_trigger, state.right_trigger), (0x10, 0xff));
+        assert_eq!(
+            state.touch_points,
+            vec![TouchPoint {
+                id: 5,
+                x: 1919,
+                y: 1079
+            }]
+        );
+        assert_eq!(state.motion.gyro, [1.0, 0.0, 0.0]);
+        assert_eq!(state.motion.accel, [0.0, -1.0, 0.0]);
+        assert_eq!(state.motion.timestamp_us, 100);
+
+        // Bluetooth reports hold the same state one byte further
+        let bt = Fixture::named("dualsense_bt");
+        let state = decode_dualsense_input(&bt.reports[0].data)
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.dpad, Dpad::Neutral);
+        assert!(state.buttons.is_empty());
+
+        // The short Bluetooth report isn't decoded
+        assert_eq!(decode_dualsense_input(&[0x01; 10]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_crc32() {
+        // The standard check value, with the seed as first byte
+        assert_eq!(crc32(b'1', b"23456789"), 0xcbf4_3926);
+        for name in ["dualsense_bt", "ds4_bt"] {
+            let fixture = Fixture::named(name);
+            for report in &fixture.reports {
+                assert!(has_valid_crc(&report.data), "{}", name);
+                let mut corrupted = report.data.clone();
+                corrupted[10] ^= 0x01;
+                assert!(!has_valid_crc(&corrupted), "{}", name);
+            }
+        }
+        // USB reports have no CRC
+        let fixture = Fixture::named("dualsense_usb");
+        assert!(has_valid_crc(&fixture.reports[0].data));
+    }
+
+    #[test]
+    fn test_corrupted_reports_are_skipped() {
+        for name in ["dualsense_bt", "ds4_bt"] {
+            let mut fixture = Fixture::named(name);
+            fixture.device.path = format!("/dev/replay/{}_corrupted", name);
+            // A corrupted copy of the last report (lower battery) comes first
+            let mut corrupted = fixture.reports[1].clone();
+            corrupted.data[20] ^= 0xff;
+            fixture.reports.insert(0, corrupted.clone());
+            let transport = ReplayTransport::new(vec![fixture.clone()]);
+            let device = &transport.devices()[0];
+            let parse = match name {
+                "dualsense_bt" => parse_dualsense_controller_data,
+                _ => parse_dualshock_controller_data,
+            };
+
+            let mut controller = Controller::from_hidapi(device, name, 0, Status::Unknown);
+            parse(&mut controller, device, &transport).unwrap();
+            assert_eq!(
+                (controller.capacity, controller.status.clone()),
+                match name {
+                    "dualsense_bt" => (35, Status::Charging),
+                    _ => (85, Status::Discharging),
+                },
+                "{}",
+                name
+            );
+            assert_eq!(diagnostics::crc_failures(&controller.id()), 1);
+
+            // Giving up once every try is corrupted
+            fixture.reports = vec![corrupted; 3];
+            let transport = ReplayTransport::new(vec![fixture]);
+            assert!(parse(&mut controller, device, &transport).is_err());
+            assert_eq!(diagnostics::crc_failures(&controller.id()), 4);
+
+            // The pad leaves and another one gets its hidraw node
+            crate::api::forget(&controller);
+            let fixture = Fixture {
+                device: device.clone(),
+                ..Fixture::named(name)
+            };
+            let transport = ReplayTransport::new(vec![fixture]);
+            let mut controller = Controller::from_hidapi(device, name, 0, Status::Unknown);
+            parse(&mut controller, device, &transport).unwrap();
+            assert_eq!(diagnostics::crc_failures(&controller.id()), 0);
+        }
+    }
+
+    #[test]
+    fn test_dualshock3_activation() {
+        // The pad only starts streaming once its LEDs were set
+        fn respond(output_report: &[u8], _next: &[u8]) -> Option<Vec<u8>> {
+            let ds3_usb = Fixture::named("ds3_usb");
+            (output_report[0] == 0x01).then(|| ds3_usb.reports[0].data.clone())
+        }
+
+        for bluetooth in [false, true] {
+            let mut fixture = Fixture::named("ds3_usb");
+            fixture.reports = [0xf2, 0xf5]
+                .into_iter()
+                .map(|report_id| Report {
+                    kind: ReportKind::Feature,
+                    time_ms: None,
+                    data: vec![report_id, 0x00],
+                })
+                .collect();
+            if bluetooth {
+                fixture.device.interface_number = -1;
+            }
+            let transport = ReplayTransport::new(vec![fixture]).respond_with(respond);
+            let device = &transport.devices()[0];
+
+            let mut controller = Controller::from_hidapi(device, "DualShock3", 0, Status::Unknown);
+            parse_dualshock3_controller_data(&mut controller, device, &transport).unwrap();
+            assert_eq!(
+                (controller.capacity, controller.status),
+                (50, Status::Discharging)
+            );
+
+            let output_reports = transport.output_reports(&device.path);
+            assert_eq!(output_reports.len(), 1);
+            assert_eq!((output_reports[0][0], output_reports[0][10]), (0x01, 0x02));
+            let sent_feature_reports = transport.sent_feature_reports(&device.path);
+            if bluetooth {
+                assert_eq!(
+                    sent_feature_reports,
+                    vec![vec![0xf4, 0x42, 0x03, 0x00, 0x00]]
+                );
+            } else {
+                assert!(sent_feature_reports.is_empty());
+            }
+        }
+    }
 }
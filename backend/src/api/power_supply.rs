@@ -0,0 +1,231 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use log::debug;
+
+use crate::controller::{Controller, Status};
+
+const SYSFS_ROOT: &str = "/sys";
+
+/// Battery state as reported by the kernel driver (hid-playstation, hid-nintendo, xpadneo,
+/// xone, hid-steam, hid-wiimote, ...) under `/sys/class/power_supply`.
+#[derive(Debug, PartialEq)]
+pub struct PowerSupply {
+    pub capacity: u8,
+    pub status: Status,
+}
+
+/// Fill in the battery state of a controller from its kernel power_supply node.
+/// Returns `false` when the controller has no such node and its reports need to be parsed instead.
+pub fn update_controller(controller: &mut Controller) -> bool {
+    let Some(path) = find(Path::new(SYSFS_ROOT), controller) else {
+        return false;
+    };
+
+    match read(&path) {
+        Ok(power_supply) => {
+            debug!(
+                "Read battery of {} from {}: {:?}",
+                controller.name,
+                path.display(),
+                power_supply
+            );
+            controller.capacity = power_supply.capacity;
+            controller.status = power_supply.status;
+            true
+        }
+        Err(err) => {
+            debug!("Failed to read {}: {}", path.display(), err);
+            false
+        }
+    }
+}
+
+/// Find the power_supply node of a controller by walking up from its hidraw (hidapi) or
+/// input (udev) device towards the device the kernel driver attached the battery to.
+pub fn find(sysfs: &Path, controller: &Controller) -> Option<PathBuf> {
+    let device_path = controller.device_path.as_deref()?;
+    let start = match device_path.strip_prefix("/dev/") {
+        // e.g. /dev/hidraw5 -> /sys/class/hidraw/hidraw5/device
+        Some(node) => sysfs.join("class/hidraw").join(node).join("device"),
+        // udev devpath, e.g. /devices/.../input/input23
+        None => sysfs.join(device_path.trim_start_matches('/')),
+    };
+    let start = fs::canonicalize(start).ok()?;
+    let devices = fs::canonicalize(sysfs.join("devices")).ok()?;
+
+    start
+        .ancestors()
+        .take_while(|dir| dir.starts_with(&devices) && *dir != devices)
+        .find_map(device_battery)
+}
+
+fn device_battery(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir.join("power_supply"))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            let is_battery = read_attribute(path, "type").as_deref() == Some("Battery");
+            // Never mistake the system (laptop) battery for the controller's
+            let is_system = read_attribute(path, "scope").as_deref() == Some("System");
+            is_battery && !is_system
+        })
+}
+
+pub fn read(path: &Path) -> Result<PowerSupply> {
+    let capacity = match read_attribute(path, "capacity") {
+        Some(capacity) => capacity.parse::<u8>()?.min(100),
+        None => {
+            let level = read_attribute(path, "capacity_level")
+                .ok_or_else(|| anyhow!("no capacity or capacity_level attribute"))?;
+            capacity_from_level(&level)
+                .ok_or_else(|| anyhow!("unknown capacity_level {}", level))?
+        }
+    };
+    let status = match read_attribute(path, "status").as_deref() {
+        // "Full" is only reported while plugged in
+        Some("Charging") | Some("Full") => Status::Charging,
+        Some("Discharging") => Status::Discharging,
+        _ => Status::Unknown,
+    };
+
+    Ok(PowerSupply { capacity, status })
+}
+
+fn capacity_from_level(level: &str) -> Option<u8> {
+    match level {
+        "Full" => Some(100),
+        "High" => Some(75),
+        "Normal" => Some(50),
+        "Low" => Some(20),
+        "Critical" => Some(5),
+        _ => None,
+    }
+}
+
+fn read_attribute(path: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(path.join(attribute))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, read, PowerSupply};
+    use crate::controller::{Controller, Status};
+    use std::{fs, os::unix::fs::symlink, path::Path, time::SystemTime};
+
+    const HID_DEVICE: &str = "devices/virtual/misc/uhid/0005:054C:0CE6.0001";
+
+    fn controller(device_path: &str) -> Controller {
+        Controller {
+            name: "DualSense".to_string(),
+            product_id: 0x0ce6,
+            vendor_id: 0x054c,
+            capacity: 0,
+            status: Status::Unknown,
+            bluetooth: true,
+            serial_number: None,
+            device_path: Some(device_path.to_string()),
+            gip: "NA".to_string(),
+            extension: None,
+            parts: Vec::new(),
+        }
+    }
+
+    fn write_attributes(dir: &Path, attributes: &[(&str, &str)]) {
+        fs::create_dir_all(dir).unwrap();
+        for (name, value) in attributes {
+            fs::write(dir.join(name), format!("{}\n", value)).unwrap();
+        }
+    }
+
+    /// Lay out a minimal sysfs tree with a DualSense that has a hidraw node, an input node
+    /// and a battery registered by hid-playstation
+    fn fake_sysfs(battery: &[(&str, &str)]) -> std::path::PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let sysfs = std::env::temp_dir().join(format!("test_sysfs_{}", timestamp));
+        let hid_device = sysfs.join(HID_DEVICE);
+
+        let hidraw = hid_device.join("hidraw/hidraw3");
+        fs::create_dir_all(&hidraw).unwrap();
+        symlink("../..", hidraw.join("device")).unwrap();
+        fs::create_dir_all(sysfs.join("class/hidraw")).unwrap();
+        symlink(&hidraw, sysfs.join("class/hidraw/hidraw3")).unwrap();
+
+        fs::create_dir_all(hid_device.join("input/input23")).unwrap();
+        write_attributes(
+            &hid_device.join("power_supply/ps-controller-battery-aa:bb:cc:dd:ee:ff"),
+            battery,
+        );
+        sysfs
+    }
+
+    #[test]
+    fn test_find_and_read() {
+        let sysfs = fake_sysfs(&[
+            ("type", "Battery"),
+            ("scope", "Device"),
+            ("capacity", "65"),
+            ("status", "Discharging"),
+        ]);
+        let expected = sysfs
+            .join(HID_DEVICE)
+            .join("power_supply/ps-controller-battery-aa:bb:cc:dd:ee:ff");
+
+        // From the hidraw node hidapi reports
+        let path = find(&sysfs, &controller("/dev/hidraw3")).unwrap();
+        assert_eq!(path, fs::canonicalize(&expected).unwrap());
+        assert_eq!(
+            read(&path).unwrap(),
+            PowerSupply {
+                capacity: 65,
+                status: Status::Discharging
+            }
+        );
+
+        // From the input device udev reports
+        let devpath = format!("/{}/input/input23", HID_DEVICE);
+        assert_eq!(find(&sysfs, &controller(&devpath)), Some(path));
+
+        // Unknown devices have no battery
+        assert_eq!(find(&sysfs, &controller("/dev/hidraw9")), None);
+        let mut no_path = controller("");
+        no_path.device_path = None;
+        assert_eq!(find(&sysfs, &no_path), None);
+
+        fs::remove_dir_all(sysfs).unwrap();
+    }
+
+    #[test]
+    fn test_capacity_level() {
+        let sysfs = fake_sysfs(&[
+            ("type", "Battery"),
+            ("capacity_level", "Low"),
+            ("status", "Full"),
+        ]);
+        let path = find(&sysfs, &controller("/dev/hidraw3")).unwrap();
+        assert_eq!(
+            read(&path).unwrap(),
+            PowerSupply {
+                capacity: 20,
+                status: Status::Charging
+            }
+        );
+        fs::remove_dir_all(sysfs).unwrap();
+    }
+
+    #[test]
+    fn test_ignores_system_battery() {
+        let sysfs = fake_sysfs(&[("type", "Battery"), ("scope", "System"), ("capacity", "80")]);
+        assert_eq!(find(&sysfs, &controller("/dev/hidraw3")), None);
+        fs::remove_dir_all(sysfs).unwrap();
+    }
+}
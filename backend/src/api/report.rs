@@ -0,0 +1,150 @@
+//! Bounds-checked access to the input reports read from a device. Drivers describe their
+//! reports as field offsets and read them through `InputReport`, which only sees the bytes the
+//! read returned, so a report that was cut short fails with an error instead of yielding the
+//! stale contents of the buffer.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReportError {
+    /// The read returned nothing
+    Empty,
+    /// The report ends before a field, e.g. `needed` bytes for a field that `len` bytes don't hold
+    Short {
+        report_id: u8,
+        len: usize,
+        needed: usize,
+    },
+    /// Another report than the one the layout describes
+    UnexpectedId { report_id: u8 },
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportError::Empty => write!(f, "empty report"),
+            ReportError::Short {
+                report_id,
+                len,
+                needed,
+            } => write!(
+                f,
+                "report {:#04x} is {} bytes long, {} needed",
+                report_id, len, needed
+            ),
+            ReportError::UnexpectedId { report_id } => {
+                write!(f, "unexpected report {:#04x}", report_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+/// One report as read from hidraw, starting with its report ID. Offsets count from the start
+/// of the report, the ID included.
+#[derive(Clone, Copy, Debug)]
+pub struct InputReport<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> InputReport<'a> {
+    /// The first `len` bytes of `buf`, `len` being what the read into it returned
+    pub fn new(buf: &'a [u8], len: usize) -> Result<Self, ReportError> {
+        match &buf[..len.min(buf.len())] {
+            [] => Err(ReportError::Empty),
+            data => Ok(Self { data }),
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Check that this is report `id` and that it holds at least `size` bytes
+    pub fn expect(&self, id: u8, size: usize) -> Result<(), ReportError> {
+        if self.id() != id {
+            return Err(ReportError::UnexpectedId {
+                report_id: self.id(),
+            });
+        }
+        self.bytes(0, size).map(|_| ())
+    }
+
+    pub fn bytes(&self, offset: usize, size: usize) -> Result<&'a [u8], ReportError> {
+        offset
+            .checked_add(size)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(ReportError::Short {
+                report_id: self.id(),
+                len: self.len(),
+                needed: offset.saturating_add(size),
+            })
+    }
+
+    pub fn array<const N: usize>(&self, offset: usize) -> Result<[u8; N], ReportError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.bytes(offset, N)?);
+        Ok(array)
+    }
+
+    pub fn u8(&self, offset: usize) -> Result<u8, ReportError> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    pub fn u16_le(&self, offset: usize) -> Result<u16, ReportError> {
+        self.array(offset).map(u16::from_le_bytes)
+    }
+
+    pub fn i16_le(&self, offset: usize) -> Result<i16, ReportError> {
+        self.array(offset).map(i16::from_le_bytes)
+    }
+
+    pub fn u32_le(&self, offset: usize) -> Result<u32, ReportError> {
+        self.array(offset).map(u32::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputReport, ReportError};
+
+    #[test]
+    fn test_bounds() {
+        // Only the 4 bytes the read returned count, not the rest of the buffer
+        let buf = [0x30, 0x01, 0x02, 0x03, 0xff, 0xff];
+        let report = InputReport::new(&buf, 4).unwrap();
+        assert_eq!(report.id(), 0x30);
+        assert_eq!(report.len(), 4);
+        assert_eq!(report.u8(3), Ok(0x03));
+        assert_eq!(report.u16_le(1), Ok(0x0201));
+        assert_eq!(report.i16_le(2), Ok(0x0302));
+        assert_eq!(
+            report.u32_le(1),
+            Err(ReportError::Short {
+                report_id: 0x30,
+                len: 4,
+                needed: 5
+            })
+        );
+        assert!(report.u8(usize::MAX).is_err());
+
+        assert_eq!(report.expect(0x30, 4), Ok(()));
+        assert!(matches!(
+            report.expect(0x30, 5),
+            Err(ReportError::Short { .. })
+        ));
+        assert_eq!(
+            report.expect(0x21, 4),
+            Err(ReportError::UnexpectedId { report_id: 0x30 })
+        );
+
+        assert_eq!(InputReport::new(&buf, 0).unwrap_err(), ReportError::Empty);
+        // A length past the buffer is capped
+        assert_eq!(InputReport::new(&buf, 10).unwrap().len(), 6);
+    }
+}
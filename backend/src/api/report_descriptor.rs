@@ -0,0 +1,401 @@
+//! Just enough of a HID report descriptor parser to find where a usage lives in the reports
+//! of a device: which report, at which bit, and its logical range. Collections, units and
+//! delimiters are skipped.
+
+use anyhow::{anyhow, Result};
+
+pub const USAGE_PAGE_GENERIC_DEVICE_CONTROLS: u16 = 0x06;
+pub const USAGE_BATTERY_STRENGTH: u16 = 0x20;
+
+pub const USAGE_PAGE_BATTERY_SYSTEM: u16 = 0x85;
+pub const USAGE_CHARGING: u16 = 0x44;
+pub const USAGE_DISCHARGING: u16 = 0x45;
+pub const USAGE_RELATIVE_STATE_OF_CHARGE: u16 = 0x64;
+pub const USAGE_ABSOLUTE_STATE_OF_CHARGE: u16 = 0x65;
+
+// Item types and the tags used here, HID 1.11 section 6.2.2
+const TYPE_MAIN: u8 = 0;
+const TYPE_GLOBAL: u8 = 1;
+const TYPE_LOCAL: u8 = 2;
+
+const MAIN_INPUT: u8 = 0x8;
+const MAIN_OUTPUT: u8 = 0x9;
+const MAIN_FEATURE: u8 = 0xb;
+
+const GLOBAL_USAGE_PAGE: u8 = 0x0;
+const GLOBAL_LOGICAL_MINIMUM: u8 = 0x1;
+const GLOBAL_LOGICAL_MAXIMUM: u8 = 0x2;
+const GLOBAL_REPORT_SIZE: u8 = 0x7;
+const GLOBAL_REPORT_ID: u8 = 0x8;
+const GLOBAL_REPORT_COUNT: u8 = 0x9;
+const GLOBAL_PUSH: u8 = 0xa;
+const GLOBAL_POP: u8 = 0xb;
+
+const LOCAL_USAGE: u8 = 0x0;
+const LOCAL_USAGE_MINIMUM: u8 = 0x1;
+const LOCAL_USAGE_MAXIMUM: u8 = 0x2;
+
+const LONG_ITEM: u8 = 0xfe;
+// Bit 0 of the data of a main item
+const CONSTANT: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportType {
+    Input,
+    Output,
+    Feature,
+}
+
+/// One value in a report
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub report_type: ReportType,
+    // 0 when the device doesn't use report IDs
+    pub report_id: u8,
+    pub usage_page: u16,
+    pub usage: u16,
+    // From the start of the report data, after the report ID
+    pub bit_offset: usize,
+    pub bit_size: usize,
+    pub logical_minimum: i32,
+    pub logical_maximum: i32,
+}
+
+impl Field {
+    /// Read the value from a report as returned by hidapi, i.e. starting with the report ID
+    /// when the device uses them. `None` when the report is another one or too short.
+    pub fn value(&self, report: &[u8]) -> Option<i32> {
+        let data = if self.report_id == 0 {
+            report
+        } else if report.first() == Some(&self.report_id) {
+            &report[1..]
+        } else {
+            return None;
+        };
+        if self.bit_size == 0
+            || self.bit_size > 32
+            || self.bit_offset + self.bit_size > data.len() * 8
+        {
+            return None;
+        }
+        let mut raw = 0u64;
+        for bit in 0..self.bit_size {
+            let position = self.bit_offset + bit;
+            if data[position / 8] & (1 << (position % 8)) != 0 {
+                raw |= 1 << bit;
+            }
+        }
+        // Negative logical minimums mean the value is signed
+        let value = if self.logical_minimum < 0 && raw & (1 << (self.bit_size - 1)) != 0 {
+            raw as i64 - (1i64 << self.bit_size)
+        } else {
+            raw as i64
+        };
+        Some(value as i32)
+    }
+
+    /// The value as a percentage of the logical range
+    pub fn percentage(&self, report: &[u8]) -> Option<u8> {
+        let value = self.value(report)? as i64;
+        let (minimum, maximum) = (self.logical_minimum as i64, self.logical_maximum as i64);
+        if maximum <= minimum {
+            return None;
+        }
+        let percentage = ((value - minimum) * 100 + (maximum - minimum) / 2) / (maximum - minimum);
+        Some(percentage.clamp(0, 100) as u8)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReportDescriptor {
+    pub fields: Vec<Field>,
+    // Every report that is declared, including the ones with only padding
+    reports: Vec<(ReportType, u8)>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Globals {
+    usage_page: u16,
+    logical_minimum: i32,
+    logical_maximum: i32,
+    report_size: usize,
+    report_id: u8,
+    report_count: usize,
+}
+
+impl ReportDescriptor {
+    pub fn parse(descriptor: &[u8]) -> Result<Self> {
+        let mut fields = Vec::new();
+        let mut reports = Vec::new();
+        let mut globals = Globals::default();
+        let mut stack = Vec::new();
+        // Usages with their page, a 4 byte usage carries its own page in the high half
+        let mut usages: Vec<(u16, u16)> = Vec::new();
+        let mut usage_minimum: Option<(u16, u16)> = None;
+        // Bits used so far by each report
+        let mut offsets: Vec<(ReportType, u8, usize)> = Vec::new();
+
+        let mut i = 0;
+        while i < descriptor.len() {
+            let prefix = descriptor[i];
+            if prefix == LONG_ITEM {
+                let size = *descriptor.get(i + 1).unwrap_or(&0) as usize;
+                i += 3 + size;
+                continue;
+            }
+            let size = match prefix & 0b11 {
+                3 => 4,
+                size => size as usize,
+            };
+            let data = descriptor
+                .get(i + 1..i + 1 + size)
+                .ok_or_else(|| anyhow!("Item at offset {} is cut off", i))?;
+            i += 1 + size;
+
+            let unsigned = data
+                .iter()
+                .rev()
+                .fold(0u32, |value, byte| (value << 8) | *byte as u32);
+            let signed = match size {
+                1 => data[0] as i8 as i32,
+                2 => i16::from_le_bytes([data[0], data[1]]) as i32,
+                4 => unsigned as i32,
+                _ => 0,
+            };
+            let usage = |globals: &Globals| match size {
+                4 => ((unsigned >> 16) as u16, unsigned as u16),
+                _ => (globals.usage_page, unsigned as u16),
+            };
+
+            let tag = prefix >> 4;
+            match (prefix >> 2) & 0b11 {
+                TYPE_MAIN => {
+                    let report_type = match tag {
+                        MAIN_INPUT => Some(ReportType::Input),
+                        MAIN_OUTPUT => Some(ReportType::Output),
+                        MAIN_FEATURE => Some(ReportType::Feature),
+                        // Collections and End Collection
+                        _ => None,
+                    };
+                    if let Some(report_type) = report_type {
+                        if !reports.contains(&(report_type, globals.report_id)) {
+                            reports.push((report_type, globals.report_id));
+                        }
+                        let offset = match offsets
+                            .iter_mut()
+                            .find(|(kind, id, _)| *kind == report_type && *id == globals.report_id)
+                        {
+                            Some((_, _, offset)) => offset,
+                            None => {
+                                offsets.push((report_type, globals.report_id, 0));
+                                &mut offsets.last_mut().unwrap().2
+                            }
+                        };
+                        for index in 0..globals.report_count {
+                            let usage = match usage_minimum {
+                                Some((page, minimum)) if usages.is_empty() => {
+                                    Some((page, minimum.saturating_add(index as u16)))
+                                }
+                                // The last usage repeats for the remaining values
+                                _ => usages.get(index).or(usages.last()).copied(),
+                            };
+                            // Padding has no usage
+                            if let Some((usage_page, usage)) =
+                                usage.filter(|_| unsigned & CONSTANT == 0)
+                            {
+                                fields.push(Field {
+                                    report_type,
+                                    report_id: globals.report_id,
+                                    usage_page,
+                                    usage,
+                                    bit_offset: *offset,
+                                    bit_size: globals.report_size,
+                                    logical_minimum: globals.logical_minimum,
+                                    logical_maximum: globals.logical_maximum,
+                                });
+                            }
+                            *offset += globals.report_size;
+                        }
+                    }
+                    usages.clear();
+                    usage_minimum = None;
+                }
+                TYPE_GLOBAL => match tag {
+                    GLOBAL_USAGE_PAGE => globals.usage_page = unsigned as u16,
+                    GLOBAL_LOGICAL_MINIMUM => globals.logical_minimum = signed,
+                    GLOBAL_LOGICAL_MAXIMUM => {
+                        // Unsigned when the minimum isn't negative, e.g. 0x26 0xff 0x00
+                        globals.logical_maximum = if globals.logical_minimum >= 0 && signed < 0 {
+                            unsigned as i32
+                        } else {
+                            signed
+                        }
+                    }
+                    GLOBAL_REPORT_SIZE => globals.report_size = unsigned as usize,
+                    GLOBAL_REPORT_ID => globals.report_id = unsigned as u8,
+                    GLOBAL_REPORT_COUNT => globals.report_count = unsigned as usize,
+                    GLOBAL_PUSH => stack.push(globals),
+                    GLOBAL_POP => {
+                        globals = stack
+                            .pop()
+                            .ok_or_else(|| anyhow!("Pop without a matching push"))?
+                    }
+                    _ => {}
+                },
+                TYPE_LOCAL => match tag {
+                    LOCAL_USAGE => usages.push(usage(&globals)),
+                    LOCAL_USAGE_MINIMUM => usage_minimum = Some(usage(&globals)),
+                    // Only the minimum is needed to number the usages
+                    LOCAL_USAGE_MAXIMUM => {}
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        Ok(Self { fields, reports })
+    }
+
+    pub fn find(&self, usage_page: u16, usage: u16) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|field| field.usage_page == usage_page && field.usage == usage)
+    }
+
+    /// The IDs of the reports of one type, in the order they are declared
+    pub fn report_ids(&self, report_type: ReportType) -> Vec<u8> {
+        self.reports
+            .iter()
+            .filter(|(kind, _)| *kind == report_type)
+            .map(|(_, id)| *id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, ReportDescriptor, ReportType};
+
+    #[test]
+    fn test_report_ids() {
+        let report_descriptor = [
+            0x05, 0x01, // Usage Page (Generic Desktop)
+            0x09, 0x05, // Usage (Game Pad)
+            0xa1, 0x01, // Collection (Application)
+            0x85, 0x01, //   Report ID (1)
+            0x09, 0x30, //   Usage (X)
+            0x75, 0x08, //   Report Size (8)
+            0x95, 0x01, //   Report Count (1)
+            0x81, 0x02, //   Input (Data, Var, Abs)
+            0x85, 0x05, //   Report ID (5)
+            0x06, 0x00, 0xff, // Usage Page (Vendor Defined)
+            0x09, 0x01, //   Usage (1)
+            0xb1, 0x02, //   Feature (Data, Var, Abs)
+            0x09, 0x02, //   Usage (2)
+            0xb1, 0x02, //   Feature (Data, Var, Abs)
+            0x85, 0x20, //   Report ID (32)
+            0x09, 0x03, //   Usage (3)
+            0x91, 0x02, //   Output (Data, Var, Abs)
+            0x09, 0x04, //   Usage (4)
+            0xb1, 0x02, //   Feature (Data, Var, Abs)
+            0xc0, // End Collection
+        ];
+        let descriptor = ReportDescriptor::parse(&report_descriptor).unwrap();
+        assert_eq!(descriptor.report_ids(ReportType::Feature), vec![0x05, 0x20]);
+        assert_eq!(descriptor.report_ids(ReportType::Input), vec![0x01]);
+        assert_eq!(descriptor.report_ids(ReportType::Output), vec![0x20]);
+        assert!(ReportDescriptor::parse(&[]).unwrap().fields.is_empty());
+        // Report Size announces 1 byte that isn't there
+        assert!(ReportDescriptor::parse(&[0x75]).is_err());
+    }
+
+    #[test]
+    fn test_battery_fields() {
+        let report_descriptor = [
+            0x05, 0x01, // Usage Page (Generic Desktop)
+            0x09, 0x05, // Usage (Game Pad)
+            0xa1, 0x01, // Collection (Application)
+            0x85, 0x01, //   Report ID (1)
+            0x05, 0x09, //   Usage Page (Button)
+            0x19, 0x01, //   Usage Minimum (1)
+            0x29, 0x0c, //   Usage Maximum (12)
+            0x15, 0x00, //   Logical Minimum (0)
+            0x25, 0x01, //   Logical Maximum (1)
+            0x75, 0x01, //   Report Size (1)
+            0x95, 0x0c, //   Report Count (12)
+            0x81, 0x02, //   Input (Data, Var, Abs)
+            0x95, 0x04, //   Report Count (4)
+            0x81, 0x03, //   Input (Const), padding
+            0x05, 0x06, //   Usage Page (Generic Device Controls)
+            0x09, 0x20, //   Usage (Battery Strength)
+            0x26, 0xff, 0x00, // Logical Maximum (255)
+            0x75, 0x08, //   Report Size (8)
+            0x95, 0x01, //   Report Count (1)
+            0x81, 0x02, //   Input (Data, Var, Abs)
+            0x85, 0x02, //   Report ID (2)
+            0x05, 0x85, //   Usage Page (Battery System)
+            0x09, 0x44, //   Usage (Charging)
+            0x09, 0x45, //   Usage (Discharging)
+            0x25, 0x01, //   Logical Maximum (1)
+            0x75, 0x01, //   Report Size (1)
+            0x95, 0x02, //   Report Count (2)
+            0xb1, 0x02, //   Feature (Data, Var, Abs)
+            0x95, 0x06, //   Report Count (6)
+            0xb1, 0x03, //   Feature (Const), padding
+            0x0b, 0x64, 0x00, 0x85, 0x00, // Usage (Battery System: Relative State Of Charge)
+            0x25, 0x64, //   Logical Maximum (100)
+            0x75, 0x08, //   Report Size (8)
+            0x95, 0x01, //   Report Count (1)
+            0xb1, 0x02, //   Feature (Data, Var, Abs)
+            0xc0, // End Collection
+        ];
+        let descriptor = ReportDescriptor::parse(&report_descriptor).unwrap();
+
+        let button = descriptor.find(0x09, 12).unwrap();
+        assert_eq!((button.bit_offset, button.bit_size), (11, 1));
+        let battery = descriptor.find(0x06, 0x20).unwrap();
+        assert_eq!(
+            battery,
+            &Field {
+                report_type: ReportType::Input,
+                report_id: 1,
+                usage_page: 0x06,
+                usage: 0x20,
+                bit_offset: 16,
+                bit_size: 8,
+                logical_minimum: 0,
+                logical_maximum: 255,
+            }
+        );
+        assert_eq!(battery.value(&[0x01, 0x00, 0x08, 0xbf]), Some(0xbf));
+        assert_eq!(battery.percentage(&[0x01, 0x00, 0x08, 0xbf]), Some(75));
+        // Another report, or one that is too short
+        assert_eq!(battery.value(&[0x02, 0x00, 0x08, 0xbf]), None);
+        assert_eq!(battery.value(&[0x01, 0x00]), None);
+
+        let charging = descriptor.find(0x85, 0x44).unwrap();
+        let discharging = descriptor.find(0x85, 0x45).unwrap();
+        let charge = descriptor.find(0x85, 0x64).unwrap();
+        assert_eq!(charge.bit_offset, 8);
+        let report = [0x02, 0b01, 42];
+        assert_eq!(charging.value(&report), Some(1));
+        assert_eq!(discharging.value(&report), Some(0));
+        assert_eq!(charge.percentage(&report), Some(42));
+    }
+
+    #[test]
+    fn test_signed_values() {
+        let report_descriptor = [
+            0x09, 0x30, // Usage (X)
+            0x15, 0x81, // Logical Minimum (-127)
+            0x25, 0x7f, // Logical Maximum (127)
+            0x75, 0x08, // Report Size (8)
+            0x95, 0x01, // Report Count (1)
+            0x81, 0x02, // Input (Data, Var, Abs)
+        ];
+        let descriptor = ReportDescriptor::parse(&report_descriptor).unwrap();
+        let x = &descriptor.fields[0];
+        assert_eq!(x.report_id, 0);
+        assert_eq!(x.value(&[0xff]), Some(-1));
+        assert_eq!(x.percentage(&[0x00]), Some(50));
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use hidapi::{DeviceInfo, HidApi};
+use serde::{Deserialize, Serialize};
+
+pub mod fixture;
+#[cfg(test)]
+pub mod replay;
+
+// HID_MAX_DESCRIPTOR_SIZE in the kernel
+const MAX_REPORT_DESCRIPTOR_SIZE: usize = 4096;
+
+/// Descriptor of a HID device, independent of how the device is accessed
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HidDevice {
+    // hidraw node, e.g. "/dev/hidraw5"
+    pub path: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    #[serde(default)]
+    pub product_string: Option<String>,
+    // -1 when connected over Bluetooth
+    pub interface_number: i32,
+    #[serde(default)]
+    pub usage_page: u16,
+    #[serde(default)]
+    pub usage: u16,
+}
+
+impl From<&DeviceInfo> for HidDevice {
+    fn from(device_info: &DeviceInfo) -> Self {
+        Self {
+            path: String::from_utf8_lossy(device_info.path().to_bytes()).to_string(),
+            vendor_id: device_info.vendor_id(),
+            product_id: device_info.product_id(),
+            serial_number: device_info
+                .serial_number()
+                .map(|serial_number| serial_number.to_string()),
+            product_string: device_info
+                .product_string()
+                .map(|product_string| product_string.to_string()),
+            interface_number: device_info.interface_number(),
+            usage_page: device_info.usage_page(),
+            usage: device_info.usage(),
+        }
+    }
+}
+
+/// Where HID devices come from: hidapi for real hardware, recorded reports in tests
+pub trait HidTransport {
+    fn devices(&self) -> Vec<HidDevice>;
+
+    fn open(&self, device: &HidDevice) -> Result<Box<dyn HidConnection>>;
+}
+
+/// An open HID device
+pub trait HidConnection {
+    /// Read an input report, returns 0 when nothing arrived before the timeout.
+    /// A negative timeout blocks until a report arrives.
+    fn read_timeout(&mut self, buf: &mut [u8], timeout_ms: i32) -> Result<usize>;
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.read_timeout(buf, -1)
+    }
+
+    /// Send an output report, `data[0]` holds the report ID
+    fn write(&mut self, data: &[u8]) -> Result<usize>;
+
+    /// Get a feature report, `buf[0]` holds the report ID
+    fn get_feature_report(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Send a feature report, `data[0]` holds the report ID
+    fn send_feature_report(&mut self, data: &[u8]) -> Result<()>;
+
+    /// The raw HID report descriptor
+    fn report_descriptor(&mut self) -> Result<Vec<u8>>;
+}
+
+pub struct HidApiTransport {
+    hidapi: HidApi,
+}
+
+impl HidApiTransport {
+    /// Enumerate every HID device on the system
+    pub fn new() -> Result<Self> {
+        Self::with_ids([(0, 0)])
+    }
+
+    /// Only enumerate the devices with the given vendor/product IDs
+    pub fn with_ids(ids: impl IntoIterator<Item = (u16, u16)>) -> Result<Self> {
+        // hidapi panics when mixing `new()` and `new_without_enumerate()` in the same process,
+        // so always start from an empty device list
+        let mut hidapi = HidApi::new_without_enumerate()?;
+        for (vendor_id, product_id) in ids {
+            hidapi.add_devices(vendor_id, product_id)?;
+        }
+        Ok(Self { hidapi })
+    }
+}
+
+impl HidTransport for HidApiTransport {
+    fn devices(&self) -> Vec<HidDevice> {
+        self.hidapi.device_list().map(HidDevice::from).collect()
+    }
+
+    fn open(&self, device: &HidDevice) -> Result<Box<dyn HidConnection>> {
+        let device_info = self
+            .hidapi
+            .device_list()
+            .find(|device_info| device_info.path().to_bytes() == device.path.as_bytes())
+            .ok_or_else(|| anyhow!("{} is gone", device.path))?;
+        Ok(Box::new(device_info.open_device(&self.hidapi)?))
+    }
+}
+
+impl HidConnection for hidapi::HidDevice {
+    fn read_timeout(&mut self, buf: &mut [u8], timeout_ms: i32) -> Result<usize> {
+        Ok(hidapi::HidDevice::read_timeout(self, buf, timeout_ms)?)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize> {
+        Ok(hidapi::HidDevice::write(self, data)?)
+    }
+
+    fn get_feature_report(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(hidapi::HidDevice::get_feature_report(self, buf)?)
+    }
+
+    fn send_feature_report(&mut self, data: &[u8]) -> Result<()> {
+        Ok(hidapi::HidDevice::send_feature_report(self, data)?)
+    }
+
+    fn report_descriptor(&mut self) -> Result<Vec<u8>> {
+        let mut buf = [0u8; MAX_REPORT_DESCRIPTOR_SIZE];
+        let len = hidapi::HidDevice::get_report_descriptor(self, &mut buf)?;
+        Ok(buf[..len].to_vec())
+    }
+}
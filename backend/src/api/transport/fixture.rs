@@ -0,0 +1,64 @@
+use std::{fs::File, path::Path};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::HidDevice;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportKind {
+    Input,
+    Feature,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub kind: ReportKind,
+    // Milliseconds since the capture started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<u64>,
+    #[serde(with = "hex")]
+    pub data: Vec<u8>,
+}
+
+/// Reports recorded from one HID device, either hand written for tests or captured from a
+/// real controller
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Fixture {
+    pub device: HidDevice,
+    // Unix timestamp of the capture
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "hex")]
+    pub report_descriptor: Vec<u8>,
+    pub reports: Vec<Report>,
+}
+
+impl Fixture {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+/// Report bytes are stored as hex strings so fixtures stay readable and diffable
+mod hex {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: Vec<String> = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&hex.join(" "))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
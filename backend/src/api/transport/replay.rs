@@ -0,0 +1,272 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+
+use super::fixture::{Fixture, ReportKind};
+use super::{HidConnection, HidDevice, HidTransport};
+
+/// Directory holding the recorded fixtures, one device per file
+pub const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/hid");
+
+impl Fixture {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Load a fixture from `FIXTURES_DIR` by name, e.g. "dualsense_usb"
+    pub fn named(name: &str) -> Self {
+        Self::load(Path::new(FIXTURES_DIR).join(format!("{}.json", name))).unwrap()
+    }
+}
+
+/// Input report sent back in reply to an output report, given the next recorded input report
+/// (empty when the recording ran out)
+pub type Responder = fn(&[u8], &[u8]) -> Option<Vec<u8>>;
+
+// Reports sent to a device, shared by every connection opened on it
+type Recording = Arc<Mutex<Vec<Vec<u8>>>>;
+
+struct ReplayDevice {
+    device: HidDevice,
+    report_descriptor: Arc<Vec<u8>>,
+    // Shared by every connection opened on the device
+    input_reports: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    feature_reports: Arc<Vec<Vec<u8>>>,
+    output_reports: Recording,
+    sent_feature_reports: Recording,
+}
+
+/// Serves recorded reports in place of real devices. Each device hands out its input reports
+/// in order, across connections, until the recording runs out. Feature reports are looked up
+/// by report ID. Output reports are kept, and answered when a responder is set. Feature
+/// reports sent to the device are kept as well.
+pub struct ReplayTransport {
+    devices: Vec<ReplayDevice>,
+    respond: Option<Responder>,
+}
+
+impl ReplayTransport {
+    pub fn new(fixtures: Vec<Fixture>) -> Self {
+        let devices = fixtures
+            .into_iter()
+            .map(|fixture| {
+                let (input_reports, feature_reports): (Vec<_>, Vec<_>) = fixture
+                    .reports
+                    .into_iter()
+                    .partition(|report| report.kind == ReportKind::Input);
+                ReplayDevice {
+                    device: fixture.device,
+                    report_descriptor: Arc::new(fixture.report_descriptor),
+                    input_reports: Arc::new(Mutex::new(
+                        input_reports
+                            .into_iter()
+                            .map(|report| report.data)
+                            .collect(),
+                    )),
+                    feature_reports: Arc::new(
+                        feature_reports
+                            .into_iter()
+                            .map(|report| report.data)
+                            .collect(),
+                    ),
+                    output_reports: Arc::new(Mutex::new(Vec::new())),
+                    sent_feature_reports: Arc::new(Mutex::new(Vec::new())),
+                }
+            })
+            .collect();
+        Self {
+            devices,
+            respond: None,
+        }
+    }
+
+    /// Only list the devices with the given vendor/product IDs, like `HidApiTransport::with_ids`.
+    /// A product ID of 0 matches every product of the vendor.
+    pub fn with_ids(mut self, ids: impl IntoIterator<Item = (u16, u16)>) -> Self {
+        let ids: Vec<(u16, u16)> = ids.into_iter().collect();
+        self.devices.retain(|replay_device| {
+            let device = &replay_device.device;
+            ids.iter().any(|&(vendor_id, product_id)| {
+                vendor_id == device.vendor_id
+                    && (product_id == 0 || product_id == device.product_id)
+            })
+        });
+        self
+    }
+
+    /// Answer output reports the way the device would, e.g. acknowledge subcommands
+    pub fn respond_with(mut self, respond: Responder) -> Self {
+        self.respond = Some(respond);
+        self
+    }
+
+    /// The output reports written to a device so far
+    pub fn output_reports(&self, path: &str) -> Vec<Vec<u8>> {
+        self.recorded(path, |replay_device| &replay_device.output_reports)
+    }
+
+    /// The feature reports sent to a device so far
+    pub fn sent_feature_reports(&self, path: &str) -> Vec<Vec<u8>> {
+        self.recorded(path, |replay_device| &replay_device.sent_feature_reports)
+    }
+
+    fn recorded(&self, path: &str, reports: fn(&ReplayDevice) -> &Recording) -> Vec<Vec<u8>> {
+        self.devices
+            .iter()
+            .find(|replay_device| replay_device.device.path == path)
+            .and_then(|replay_device| reports(replay_device).lock().ok())
+            .map(|reports| reports.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl HidTransport for ReplayTransport {
+    fn devices(&self) -> Vec<HidDevice> {
+        self.devices
+            .iter()
+            .map(|replay_device| replay_device.device.clone())
+            .collect()
+    }
+
+    fn open(&self, device: &HidDevice) -> Result<Box<dyn HidConnection>> {
+        let replay_device = self
+            .devices
+            .iter()
+            .find(|replay_device| replay_device.device.path == device.path)
+            .ok_or_else(|| anyhow!("{} is gone", device.path))?;
+        Ok(Box::new(ReplayConnection {
+            report_descriptor: replay_device.report_descriptor.clone(),
+            input_reports: replay_device.input_reports.clone(),
+            feature_reports: replay_device.feature_reports.clone(),
+            output_reports: replay_device.output_reports.clone(),
+            sent_feature_reports: replay_device.sent_feature_reports.clone(),
+            respond: self.respond,
+        }))
+    }
+}
+
+struct ReplayConnection {
+    report_descriptor: Arc<Vec<u8>>,
+    input_reports: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    feature_reports: Arc<Vec<Vec<u8>>>,
+    output_reports: Recording,
+    sent_feature_reports: Recording,
+    respond: Option<Responder>,
+}
+
+impl HidConnection for ReplayConnection {
+    fn read_timeout(&mut self, buf: &mut [u8], _timeout_ms: i32) -> Result<usize> {
+        let mut input_reports = self
+            .input_reports
+            .lock()
+            .map_err(|err| anyhow!("{}", err))?;
+        // Once the recording runs out the device behaves as if it went quiet
+        let Some(report) = input_reports.pop_front() else {
+            return Ok(0);
+        };
+        let len = report.len().min(buf.len());
+        buf[..len].copy_from_slice(&report[..len]);
+        Ok(len)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize> {
+        self.output_reports
+            .lock()
+            .map_err(|err| anyhow!("{}", err))?
+            .push(data.to_vec());
+        if let Some(respond) = self.respond {
+            let mut input_reports = self
+                .input_reports
+                .lock()
+                .map_err(|err| anyhow!("{}", err))?;
+            let next = input_reports.front().cloned().unwrap_or_default();
+            // The reply comes before whatever the device was about to send
+            if let Some(reply) = respond(data, &next) {
+                input_reports.push_front(reply);
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn get_feature_report(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let report_id = *buf.first().ok_or_else(|| anyhow!("empty buffer"))?;
+        let report = self
+            .feature_reports
+            .iter()
+            .find(|report| report.first() == Some(&report_id))
+            .ok_or_else(|| anyhow!("no recorded feature report {:#04x}", report_id))?;
+        let len = report.len().min(buf.len());
+        buf[..len].copy_from_slice(&report[..len]);
+        Ok(len)
+    }
+
+    fn send_feature_report(&mut self, data: &[u8]) -> Result<()> {
+        self.sent_feature_reports
+            .lock()
+            .map_err(|err| anyhow!("{}", err))?
+            .push(data.to_vec());
+        Ok(())
+    }
+
+    fn report_descriptor(&mut self) -> Result<Vec<u8>> {
+        Ok(self.report_descriptor.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fixture, HidTransport, ReplayTransport};
+
+    #[test]
+    fn test_replay() {
+        let fixture: Fixture = serde_json::from_str(
+            r#"{
+                "device": {
+                    "path": "/dev/replay/test",
+                    "vendorId": 1356,
+                    "productId": 3302,
+                    "interfaceNumber": 3
+                },
+                "reports": [
+                    {"kind": "input", "data": "01 02 03"},
+                    {"kind": "feature", "data": "05 aa bb"},
+                    {"kind": "input", "data": "01 04 05"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&fixture.reports[1]).unwrap(),
+            serde_json::json!({"kind": "feature", "data": "05 aa bb"})
+        );
+
+        let transport = ReplayTransport::new(vec![fixture]);
+        let devices = transport.devices();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].serial_number, None);
+
+        let mut buf = [0u8; 8];
+        let mut connection = transport.open(&devices[0]).unwrap();
+        assert_eq!(connection.read(&mut buf).unwrap(), 3);
+        assert_eq!(buf[..3], [0x01, 0x02, 0x03]);
+
+        buf[0] = 0x05;
+        assert_eq!(connection.get_feature_report(&mut buf).unwrap(), 3);
+        assert_eq!(buf[..3], [0x05, 0xaa, 0xbb]);
+        buf[0] = 0x06;
+        assert!(connection.get_feature_report(&mut buf).is_err());
+
+        // Reads continue where the previous connection stopped
+        let mut connection = transport.open(&devices[0]).unwrap();
+        assert_eq!(connection.read(&mut buf).unwrap(), 3);
+        assert_eq!(buf[..3], [0x01, 0x04, 0x05]);
+        assert_eq!(connection.read_timeout(&mut buf, 100).unwrap(), 0);
+    }
+}
@@ -0,0 +1,561 @@
+//! Virtual HID devices created through `/dev/uhid`, so tests can go through the kernel
+//! drivers, hidraw, discovery and the HTTP/websocket API the way a real controller does.
+//! Creating them needs write access to `/dev/uhid`, so those tests are ignored by default and
+//! run with `cargo test -- --ignored`.
+
+pub mod profiles;
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use log::{debug, error};
+
+use self::profiles::Profile;
+
+const UHID_PATH: &str = "/dev/uhid";
+const SYSFS_HIDRAW: &str = "/sys/class/hidraw";
+
+// sizeof(struct uhid_event) in linux/uhid.h, the largest member being uhid_create2_req
+const EVENT_SIZE: usize = 4376;
+// Offset of the union following the __u32 event type
+const PAYLOAD: usize = 4;
+
+// enum uhid_event_type
+const UHID_DESTROY: u32 = 1;
+const UHID_START: u32 = 2;
+const UHID_OUTPUT: u32 = 6;
+const UHID_GET_REPORT: u32 = 9;
+const UHID_GET_REPORT_REPLY: u32 = 10;
+const UHID_CREATE2: u32 = 11;
+const UHID_INPUT2: u32 = 12;
+const UHID_SET_REPORT: u32 = 13;
+const UHID_SET_REPORT_REPLY: u32 = 14;
+
+// enum uhid_report_type
+const UHID_FEATURE_REPORT: u8 = 0;
+
+const BUS_USB: u16 = 0x03;
+const O_NONBLOCK: i32 = 0o4000;
+const EIO: u16 = 5;
+
+// Controllers send input reports every few milliseconds and the kernel drivers rely on it
+const INPUT_INTERVAL: Duration = Duration::from_millis(8);
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+// Probing includes a USB handshake for the Pro Controller
+const HIDRAW_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether virtual devices can be created, i.e. the uhid module is loaded and we may use it
+pub fn available() -> bool {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(UHID_PATH)
+        .is_ok()
+}
+
+/// A controller that exists until dropped. It keeps sending its current input report, which
+/// tests change to script battery levels and cable plugs.
+pub struct VirtualDevice {
+    // Identifies the device in sysfs, unlike uniq the kernel drivers leave it alone
+    phys: String,
+    hidraw: PathBuf,
+    file: Arc<File>,
+    input: Arc<Mutex<Vec<u8>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl VirtualDevice {
+    /// Create the device and wait for the kernel to probe it and create its hidraw node
+    pub fn create(profile: &'static Profile, input: Vec<u8>) -> Result<Self> {
+        let file = Arc::new(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(O_NONBLOCK)
+                .open(UHID_PATH)?,
+        );
+        let phys = format!(
+            "controller-tools/{:04x}:{:04x}/{}",
+            profile.vendor_id,
+            profile.product_id,
+            std::process::id()
+        );
+
+        // struct uhid_create2_req
+        let mut create = event(UHID_CREATE2);
+        put(&mut create, PAYLOAD, profile.name.as_bytes());
+        put(&mut create, PAYLOAD + 128, phys.as_bytes());
+        put(
+            &mut create,
+            PAYLOAD + 256,
+            &(profile.report_descriptor.len() as u16).to_ne_bytes(),
+        );
+        put(&mut create, PAYLOAD + 258, &BUS_USB.to_ne_bytes());
+        put(
+            &mut create,
+            PAYLOAD + 260,
+            &(profile.vendor_id as u32).to_ne_bytes(),
+        );
+        put(
+            &mut create,
+            PAYLOAD + 264,
+            &(profile.product_id as u32).to_ne_bytes(),
+        );
+        put(&mut create, PAYLOAD + 276, profile.report_descriptor);
+        (&*file).write_all(&create)?;
+
+        let input = Arc::new(Mutex::new(input));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (file, input, stop) = (file.clone(), input.clone(), stop.clone());
+            std::thread::Builder::new()
+                .name("uhid".to_string())
+                .spawn(move || run(profile, &file, &input, &stop))?
+        };
+
+        let mut device = Self {
+            phys,
+            hidraw: PathBuf::new(),
+            file,
+            input,
+            stop,
+            thread: Some(thread),
+        };
+        let started = Instant::now();
+        device.hidraw = loop {
+            if let Some(hidraw) = find_hidraw(&device.phys) {
+                break hidraw;
+            }
+            if started.elapsed() > HIDRAW_TIMEOUT {
+                return Err(anyhow!("no hidraw node showed up for {}", profile.name));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        Ok(device)
+    }
+
+    /// Name of the kernel driver bound to the device, e.g. "playstation" or "hid-generic"
+    pub fn driver(&self) -> Option<String> {
+        let driver = fs::read_link(self.hidraw.join("device/driver")).ok()?;
+        Some(driver.file_name()?.to_string_lossy().to_string())
+    }
+
+    /// Send `report` from now on instead of the previous input report
+    pub fn set_input(&self, report: Vec<u8>) {
+        if let Ok(mut input) = self.input.lock() {
+            *input = report;
+        }
+    }
+}
+
+impl Drop for VirtualDevice {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        // Closing the file destroys the device as well, but only once every reference is gone
+        let _ = (&*self.file).write_all(&event(UHID_DESTROY));
+    }
+}
+
+fn event(kind: u32) -> Vec<u8> {
+    let mut event = vec![0u8; EVENT_SIZE];
+    put(&mut event, 0, &kind.to_ne_bytes());
+    event
+}
+
+fn put(event: &mut [u8], offset: usize, data: &[u8]) {
+    event[offset..offset + data.len()].copy_from_slice(data);
+}
+
+fn find_hidraw(phys: &str) -> Option<PathBuf> {
+    fs::read_dir(SYSFS_HIDRAW)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            fs::read_to_string(path.join("device/uevent"))
+                .map(|uevent| {
+                    uevent
+                        .lines()
+                        .any(|line| line == format!("HID_PHYS={}", phys))
+                })
+                .unwrap_or(false)
+        })
+}
+
+fn write_input(file: &File, report: &[u8]) -> Result<()> {
+    // struct uhid_input2_req
+    let mut input = event(UHID_INPUT2);
+    put(&mut input, PAYLOAD, &(report.len() as u16).to_ne_bytes());
+    put(&mut input, PAYLOAD + 2, report);
+    Ok((&*file).write_all(&input)?)
+}
+
+/// Answer the kernel's requests and keep the input reports coming until told to stop
+fn run(profile: &Profile, file: &File, input: &Mutex<Vec<u8>>, stop: &AtomicBool) {
+    let mut buf = vec![0u8; EVENT_SIZE];
+    let mut started = false;
+    let mut last_input: Option<Instant> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        match (&*file).read(&mut buf) {
+            Ok(len) if len >= PAYLOAD => {
+                let kind = u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]);
+                started |= kind == UHID_START;
+                if let Err(err) = handle_event(profile, file, input, kind, &buf) {
+                    debug!("Failed to handle uhid event {}: {}", kind, err);
+                }
+                continue;
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+            Err(err) => {
+                error!("Failed to read from {}: {}", UHID_PATH, err);
+                return;
+            }
+        }
+
+        // The kernel rejects input until the driver started the device
+        if started && last_input.is_none_or(|last| last.elapsed() >= INPUT_INTERVAL) {
+            let report = input.lock().map(|input| input.clone()).unwrap_or_default();
+            if let Err(err) = write_input(file, &report) {
+                debug!("Failed to send input report: {}", err);
+            }
+            last_input = Some(Instant::now());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn handle_event(
+    profile: &Profile,
+    file: &File,
+    input: &Mutex<Vec<u8>>,
+    kind: u32,
+    buf: &[u8],
+) -> Result<()> {
+    match kind {
+        UHID_GET_REPORT => {
+            // struct uhid_get_report_req: id, rnum, rtype
+            let id = &buf[PAYLOAD..PAYLOAD + 4];
+            let report = match buf[PAYLOAD + 5] {
+                UHID_FEATURE_REPORT => (profile.feature_report)(buf[PAYLOAD + 4]),
+                _ => None,
+            };
+            // struct uhid_get_report_reply_req: id, err, size, data
+            let mut reply = event(UHID_GET_REPORT_REPLY);
+            put(&mut reply, PAYLOAD, id);
+            match report {
+                Some(report) => {
+                    put(
+                        &mut reply,
+                        PAYLOAD + 6,
+                        &(report.len() as u16).to_ne_bytes(),
+                    );
+                    put(&mut reply, PAYLOAD + 8, &report);
+                }
+                None => put(&mut reply, PAYLOAD + 4, &EIO.to_ne_bytes()),
+            }
+            (&*file).write_all(&reply)?;
+        }
+        UHID_SET_REPORT => {
+            // Accept whatever is set, e.g. the DualShock 3 LEDs
+            let mut reply = event(UHID_SET_REPORT_REPLY);
+            put(&mut reply, PAYLOAD, &buf[PAYLOAD..PAYLOAD + 4]);
+            (&*file).write_all(&reply)?;
+        }
+        UHID_OUTPUT => {
+            // struct uhid_output_req: data[UHID_DATA_MAX], size, rtype
+            let size = u16::from_ne_bytes([buf[PAYLOAD + 4096], buf[PAYLOAD + 4097]]) as usize;
+            let output = &buf[PAYLOAD..PAYLOAD + size.min(4096)];
+            let current = input.lock().map(|input| input.clone()).unwrap_or_default();
+            if let Some(reply) = (profile.respond)(output, &current) {
+                write_input(file, &reply)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::profiles::{
+        dualsense_input, dualshock3_input, dualshock4_input, pro_controller_input, Profile,
+        DUALSENSE, DUALSHOCK3, DUALSHOCK4, PRO_CONTROLLER,
+    };
+    use super::{available, VirtualDevice};
+    use crate::api::transport::fixture::{Fixture, Report, ReportKind};
+    use crate::api::transport::replay::ReplayTransport;
+    use crate::api::transport::HidDevice;
+    use crate::controller::Status;
+    use crate::hotplug::Inventory;
+    use crate::poller::{self, Poller};
+    use crate::settings::SettingsService;
+    use crate::AppState;
+    use axum::body::Body;
+    use axum::http::Request;
+    use futures::StreamExt;
+    use serde_json::Value;
+    use std::{future::IntoFuture, sync::Arc, time::Duration};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+    use tower::ServiceExt;
+
+    // Long enough for a few input reports to reach the kernel driver
+    const SETTLE_DELAY: Duration = Duration::from_millis(200);
+    const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    type Events = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    /// An input report and the battery state it should be reported as
+    struct Step {
+        report: Vec<u8>,
+        capacity: u8,
+        status: Status,
+    }
+
+    fn step(report: Vec<u8>, capacity: u8, status: Status) -> Step {
+        Step {
+            report,
+            capacity,
+            status,
+        }
+    }
+
+    fn dualsense_script() -> Vec<Step> {
+        vec![
+            step(dualsense_input(0x06), 65, Status::Discharging),
+            step(dualsense_input(0x02), 25, Status::Discharging),
+            // Cable plugged in
+            step(dualsense_input(0x12), 25, Status::Charging),
+            step(dualsense_input(0x17), 75, Status::Charging),
+        ]
+    }
+
+    fn dualshock4_script() -> Vec<Step> {
+        vec![
+            step(dualshock4_input(0x05), 55, Status::Discharging),
+            step(dualshock4_input(0x01), 15, Status::Discharging),
+            // Cable plugged in
+            step(dualshock4_input(0x11), 15, Status::Charging),
+            step(dualshock4_input(0x18), 85, Status::Charging),
+        ]
+    }
+
+    fn dualshock3_script() -> Vec<Step> {
+        vec![
+            step(dualshock3_input(0x04), 75, Status::Discharging),
+            step(dualshock3_input(0x03), 50, Status::Discharging),
+            step(dualshock3_input(0x01), 1, Status::Discharging),
+        ]
+    }
+
+    fn pro_controller_script() -> Vec<Step> {
+        vec![
+            step(pro_controller_input(0x80), 100, Status::Discharging),
+            // Cable plugged in
+            step(pro_controller_input(0x91), 100, Status::Charging),
+        ]
+    }
+
+    fn is_same_model(controller: &Value, profile: &Profile) -> bool {
+        controller["vendorId"] == profile.vendor_id && controller["productId"] == profile.product_id
+    }
+
+    fn has_battery(controller: &Value, step: &Step) -> bool {
+        controller["capacity"] == step.capacity
+            && controller["status"] == serde_json::to_value(&step.status).unwrap()
+    }
+
+    /// Wait for the next event on the /events websocket that satisfies `predicate`
+    async fn next_event(events: &mut Events, predicate: impl Fn(&Value) -> bool) -> Value {
+        tokio::time::timeout(EVENT_TIMEOUT, async {
+            while let Some(message) = events.next().await {
+                let Message::Text(text) = message.unwrap() else {
+                    continue;
+                };
+                let event: Value = serde_json::from_str(&text).unwrap();
+                if predicate(&event) {
+                    return event;
+                }
+            }
+            panic!("events websocket closed");
+        })
+        .await
+        .expect("timed out waiting for event")
+    }
+
+    async fn get_controllers(state: Arc<AppState>) -> Vec<Value> {
+        let response = crate::app(state)
+            .oneshot(Request::get("/controllers").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Plug in a virtual controller, play `script` on it and check that both /controllers and
+    /// the /events websocket follow along, then unplug it
+    async fn run_script(profile: &'static Profile, script: Vec<Step>) {
+        assert!(available(), "/dev/uhid is not available");
+        let device = VirtualDevice::create(profile, script[0].report.clone()).unwrap();
+        // hidapi can't tell the USB interface of a virtual device, so without the kernel driver
+        // (and its power_supply node) the pad would be taken for a Bluetooth one
+        let driver = device.driver();
+        assert!(
+            profile
+                .drivers
+                .contains(&driver.as_deref().unwrap_or_default()),
+            "{} is bound to {:?} instead of {:?}",
+            profile.name,
+            driver,
+            profile.drivers
+        );
+
+        let state = Arc::new(AppState {
+            settings_service: SettingsService::new(&String::new()).await.unwrap(),
+            inventory: Arc::new(Inventory::new()),
+            poller: Poller::new(),
+            capture_directory: std::env::temp_dir(),
+        });
+        poller::spawn(state.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, crate::app(state.clone())).into_future());
+        let (mut events, _) = tokio_tungstenite::connect_async(format!("ws://{}/events", address))
+            .await
+            .unwrap();
+
+        state.inventory.rescan().await.unwrap();
+        next_event(&mut events, |event| {
+            event["event"] == "connected" && is_same_model(&event["controller"], profile)
+        })
+        .await;
+
+        for step in &script {
+            device.set_input(step.report.clone());
+            tokio::time::sleep(SETTLE_DELAY).await;
+
+            let controllers = get_controllers(state.clone()).await;
+            let controller = controllers
+                .iter()
+                .find(|controller| is_same_model(controller, profile))
+                .expect("controller missing from /controllers");
+            assert!(
+                has_battery(controller, step),
+                "expected {}% {:?}, got {}",
+                step.capacity,
+                step.status,
+                controller
+            );
+
+            state.poller.wake();
+            next_event(&mut events, |event| {
+                event["event"] == "updated"
+                    && event["controllers"].as_array().is_some_and(|controllers| {
+                        controllers.iter().any(|controller| {
+                            is_same_model(controller, profile) && has_battery(controller, step)
+                        })
+                    })
+            })
+            .await;
+        }
+
+        drop(device);
+        state.inventory.rescan().await.unwrap();
+        next_event(&mut events, |event| {
+            event["event"] == "disconnected" && is_same_model(&event["controller"], profile)
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs /dev/uhid and the kernel driver"]
+    async fn test_virtual_dualsense() {
+        run_script(&DUALSENSE, dualsense_script()).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs /dev/uhid and the kernel driver"]
+    async fn test_virtual_dualshock4() {
+        run_script(&DUALSHOCK4, dualshock4_script()).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs /dev/uhid and the kernel driver"]
+    async fn test_virtual_dualshock3() {
+        run_script(&DUALSHOCK3, dualshock3_script()).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs /dev/uhid and the kernel driver"]
+    async fn test_virtual_pro_controller() {
+        run_script(&PRO_CONTROLLER, pro_controller_script()).await;
+    }
+
+    /// The scripts are parsed the same way by our own drivers when no kernel driver takes over
+    #[test]
+    fn test_scripts_replay() {
+        let scripts = [
+            (&DUALSENSE, dualsense_script()),
+            (&DUALSHOCK4, dualshock4_script()),
+            (&DUALSHOCK3, dualshock3_script()),
+            (&PRO_CONTROLLER, pro_controller_script()),
+        ];
+        for (profile, script) in scripts {
+            let device = HidDevice {
+                path: format!("/dev/replay/{:04x}", profile.product_id),
+                vendor_id: profile.vendor_id,
+                product_id: profile.product_id,
+                serial_number: Some(profile.name.to_string()),
+                product_string: Some(profile.name.to_string()),
+                interface_number: 0,
+                // Generic Desktop Game Pad, as hidapi reads it from the report descriptor
+                usage_page: 0x01,
+                usage: 0x05,
+            };
+            for step in script {
+                let fixture = Fixture {
+                    device: device.clone(),
+                    captured_at: None,
+                    report_descriptor: profile.report_descriptor.to_vec(),
+                    reports: vec![Report {
+                        kind: ReportKind::Input,
+                        time_ms: None,
+                        data: step.report.clone(),
+                    }],
+                };
+                let controllers =
+                    crate::api::controllers_from(&ReplayTransport::new(vec![fixture])).unwrap();
+                let controller = controllers
+                    .iter()
+                    .find(|controller| controller.device_path.as_ref() == Some(&device.path))
+                    .unwrap();
+                assert_eq!(
+                    (controller.capacity, &controller.status),
+                    (step.capacity, &step.status),
+                    "{}",
+                    profile.name
+                );
+            }
+        }
+    }
+}
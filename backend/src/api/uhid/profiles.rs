@@ -0,0 +1,362 @@
+//! What the virtual devices pretend to be: the USB report descriptors and IDs of real
+//! controllers, the feature reports their kernel drivers ask for while probing, and the
+//! replies to the output reports they send.
+
+use super::super::nintendo::VENDOR_ID_NINTENDO;
+use super::super::playstation::{DS3_PRODUCT_ID, DS_PRODUCT_ID, DS_VENDOR_ID};
+
+pub struct Profile {
+    pub name: &'static str,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Kernel HID drivers that handle the device, as named in /sys/bus/hid/drivers
+    pub drivers: &'static [&'static str],
+    pub report_descriptor: &'static [u8],
+    /// Reply to a GET_REPORT request for a feature report, `None` fails the request
+    pub feature_report: fn(u8) -> Option<Vec<u8>>,
+    /// Input report sent back in reply to an output report, given the current input report
+    pub respond: fn(&[u8], &[u8]) -> Option<Vec<u8>>,
+}
+
+pub static DUALSENSE: Profile = Profile {
+    name: "Sony Interactive Entertainment DualSense Wireless Controller",
+    vendor_id: DS_VENDOR_ID,
+    product_id: DS_PRODUCT_ID,
+    drivers: &["playstation"],
+    report_descriptor: DUALSENSE_REPORT_DESCRIPTOR,
+    feature_report: dualsense_feature_report,
+    respond: |_, _| None,
+};
+
+pub static DUALSHOCK4: Profile = Profile {
+    name: "Sony Interactive Entertainment Wireless Controller",
+    vendor_id: DS_VENDOR_ID,
+    // After PlayStation update 5.50
+    product_id: 0x09cc,
+    // hid-playstation took over the DualShock 4 from hid-sony in Linux 6.2
+    drivers: &["playstation", "sony"],
+    report_descriptor: DUALSHOCK4_REPORT_DESCRIPTOR,
+    feature_report: dualshock4_feature_report,
+    respond: |_, _| None,
+};
+
+pub static DUALSHOCK3: Profile = Profile {
+    name: "Sony PLAYSTATION(R)3 Controller",
+    vendor_id: DS_VENDOR_ID,
+    product_id: DS3_PRODUCT_ID,
+    drivers: &["sony"],
+    report_descriptor: DUALSHOCK3_REPORT_DESCRIPTOR,
+    feature_report: dualshock3_feature_report,
+    respond: |_, _| None,
+};
+
+pub static PRO_CONTROLLER: Profile = Profile {
+    name: "Nintendo Co., Ltd. Pro Controller",
+    vendor_id: VENDOR_ID_NINTENDO,
+    product_id: 0x2009,
+    drivers: &["nintendo"],
+    report_descriptor: PRO_CONTROLLER_REPORT_DESCRIPTOR,
+    feature_report: |_| None,
+    respond: pro_controller_respond,
+};
+
+/// USB input report 0x01 of a DualSense with sticks centered and nothing pressed.
+/// `status` holds the battery level (0-10) in the low and the charging state in the high nibble.
+pub fn dualsense_input(status: u8) -> Vec<u8> {
+    let mut report = vec![0u8; 64];
+    report[0] = 0x01;
+    report[1..5].fill(0x80);
+    // Hat switch released
+    report[8] = 0x08;
+    report[53] = status;
+    report
+}
+
+/// USB input report 0x01 of a DualShock 4. `status0` holds the battery level (0-11) in the low
+/// nibble and the cable state in bit 4.
+pub fn dualshock4_input(status0: u8) -> Vec<u8> {
+    let mut report = vec![0u8; 64];
+    report[0] = 0x01;
+    report[1..5].fill(0x80);
+    report[5] = 0x08;
+    report[30] = status0;
+    report
+}
+
+/// USB input report 0x01 of a DualShock 3, `battery` is 0-5 or 0xee/0xef while charging
+pub fn dualshock3_input(battery: u8) -> Vec<u8> {
+    let mut report = vec![0u8; 49];
+    report[0] = 0x01;
+    report[6..10].fill(0x80);
+    report[30] = battery;
+    report
+}
+
+/// Standard full input report 0x30 of a Pro Controller. `bat_con` holds the battery level in
+/// the high nibble, charging in bit 4 and external power in bit 0.
+pub fn pro_controller_input(bat_con: u8) -> Vec<u8> {
+    let mut report = vec![0u8; 64];
+    report[0] = 0x30;
+    report[2] = bat_con;
+    report[6..12].copy_from_slice(&STICKS_CENTERED);
+    report
+}
+
+// Every profile gets its own MAC address, the kernel drivers refuse duplicates
+const DUALSENSE_MAC: [u8; 6] = [0x02, 0xc7, 0x00, 0x00, 0x0c, 0xe6];
+const DUALSHOCK4_MAC: [u8; 6] = [0x02, 0xc7, 0x00, 0x00, 0x09, 0xcc];
+const DUALSHOCK3_MAC: [u8; 6] = [0x02, 0xc7, 0x00, 0x00, 0x02, 0x68];
+const PRO_CONTROLLER_MAC: [u8; 6] = [0x02, 0xc7, 0x00, 0x00, 0x20, 0x09];
+
+// Both sticks at 0x800, packed as two 12 bit values per stick
+const STICKS_CENTERED: [u8; 6] = [0x00, 0x08, 0x80, 0x00, 0x08, 0x80];
+
+/// A feature report of `size` bytes made of little endian 16 bit `values` after the report ID
+fn calibration(report_id: u8, size: usize, values: &[i16]) -> Vec<u8> {
+    let mut report = vec![0u8; size];
+    report[0] = report_id;
+    for (i, value) in values.iter().enumerate() {
+        report[1 + 2 * i..3 + 2 * i].copy_from_slice(&value.to_le_bytes());
+    }
+    report
+}
+
+fn reversed(mac: [u8; 6]) -> [u8; 6] {
+    let mut mac = mac;
+    mac.reverse();
+    mac
+}
+
+fn dualsense_feature_report(report_id: u8) -> Option<Vec<u8>> {
+    match report_id {
+        // Calibration: gyro biases, gyro plus/minus pairs, gyro speed, accelerometer ranges
+        0x05 => Some(calibration(
+            0x05,
+            41,
+            &[
+                0, 0, 0, 8000, -8000, 8000, -8000, 8000, -8000, 540, 540, 8192, -8192, 8192, -8192,
+                8192, -8192,
+            ],
+        )),
+        // Pairing info, the MAC address is stored backwards
+        0x09 => {
+            let mut report = vec![0u8; 20];
+            report[0] = 0x09;
+            report[1..7].copy_from_slice(&reversed(DUALSENSE_MAC));
+            Some(report)
+        }
+        // Firmware info
+        0x20 => {
+            let mut report = vec![0u8; 64];
+            report[0] = 0x20;
+            Some(report)
+        }
+        _ => None,
+    }
+}
+
+fn dualshock4_feature_report(report_id: u8) -> Option<Vec<u8>> {
+    match report_id {
+        // Calibration, over USB all gyro "plus" values come before the "minus" ones
+        0x02 => Some(calibration(
+            0x02,
+            37,
+            &[
+                0, 0, 0, 8000, 8000, 8000, -8000, -8000, -8000, 540, 540, 8192, -8192, 8192, -8192,
+                8192, -8192,
+            ],
+        )),
+        // MAC address
+        0x81 => {
+            let mut report = vec![0u8; 7];
+            report[0] = 0x81;
+            report[1..7].copy_from_slice(&reversed(DUALSHOCK4_MAC));
+            Some(report)
+        }
+        // Firmware info
+        0xa3 => {
+            let mut report = vec![0u8; 49];
+            report[0] = 0xa3;
+            Some(report)
+        }
+        _ => None,
+    }
+}
+
+fn dualshock3_feature_report(report_id: u8) -> Option<Vec<u8>> {
+    match report_id {
+        // Controller MAC address, also the first step of putting it in operational mode
+        0xf2 => {
+            let mut report = vec![0u8; 17];
+            report[..4].copy_from_slice(&[0xf2, 0xff, 0xff, 0x00]);
+            report[4..10].copy_from_slice(&DUALSHOCK3_MAC);
+            Some(report)
+        }
+        // Paired host MAC address
+        0xf5 => {
+            let mut report = vec![0u8; 8];
+            report[0] = 0xf5;
+            report[1] = 0x01;
+            Some(report)
+        }
+        _ => None,
+    }
+}
+
+// SPI flash regions read by hid-nintendo: IMU calibration, left/right stick calibration and
+// body/button colors. Everything else reads as erased flash, meaning no user calibration.
+const PRO_CONTROLLER_FLASH: &[(u32, &[u8])] = &[
+    (
+        0x6020,
+        &[
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x40, 0x00, 0x40, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x3b, 0x34, 0x3b, 0x34, 0x3b, 0x34,
+        ],
+    ),
+    (
+        0x603d,
+        &[0x00, 0x05, 0x50, 0x00, 0x08, 0x80, 0x00, 0x05, 0x50],
+    ),
+    (
+        0x6046,
+        &[0x00, 0x08, 0x80, 0x00, 0x05, 0x50, 0x00, 0x05, 0x50],
+    ),
+    (0x6050, &[0x32, 0x32, 0x32, 0xff, 0xff, 0xff]),
+];
+
+fn pro_controller_flash(address: u32) -> u8 {
+    PRO_CONTROLLER_FLASH
+        .iter()
+        .find_map(|(start, data)| {
+            let offset = address.checked_sub(*start)? as usize;
+            data.get(offset).copied()
+        })
+        .unwrap_or(0xff)
+}
+
+/// Acknowledge the USB handshake (0x80 commands) and answer subcommands (0x01) with a 0x21
+/// report, which is how hid-nintendo brings the controller up
+fn pro_controller_respond(output: &[u8], input: &[u8]) -> Option<Vec<u8>> {
+    let mut reply = vec![0u8; 64];
+    match output {
+        // Commands 0x04 and 0x05 (stop/start the USB timeout) aren't acknowledged
+        [0x80, command @ 0x01..=0x03, ..] => {
+            reply[..2].copy_from_slice(&[0x81, *command]);
+        }
+        [0x01, ..] if output.len() > 10 && input.len() >= 13 => {
+            let subcommand = output[10];
+            let args = &output[11..];
+            reply[0] = 0x21;
+            // Timer, battery, buttons and sticks as in the current input report
+            reply[1..13].copy_from_slice(&input[1..13]);
+            reply[13] = 0x80;
+            reply[14] = subcommand;
+            let data = match subcommand {
+                // Device info: firmware version, Pro Controller type, MAC address, colors in SPI
+                0x02 => {
+                    let mut data = vec![0x03, 0x48, 0x03, 0x02];
+                    data.extend_from_slice(&PRO_CONTROLLER_MAC);
+                    data.extend_from_slice(&[0x01, 0x02]);
+                    data
+                }
+                // SPI flash read, echoing the address and size before the data
+                0x10 if args.len() >= 5 => {
+                    let address = u32::from_le_bytes([args[0], args[1], args[2], args[3]]);
+                    let size = args[4];
+                    let mut data = args[..5].to_vec();
+                    data.extend((address..address + size as u32).map(pro_controller_flash));
+                    data
+                }
+                _ => Vec::new(),
+            };
+            let len = data.len().min(reply.len() - 15);
+            reply[15..15 + len].copy_from_slice(&data[..len]);
+        }
+        _ => return None,
+    }
+    Some(reply)
+}
+
+const DUALSENSE_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, 0x09, 0x05, 0xa1, 0x01, 0x85, 0x01, 0x09, 0x30, 0x09, 0x31, 0x09, 0x32, 0x09, 0x35,
+    0x09, 0x33, 0x09, 0x34, 0x15, 0x00, 0x26, 0xff, 0x00, 0x75, 0x08, 0x95, 0x06, 0x81, 0x02, 0x06,
+    0x00, 0xff, 0x09, 0x20, 0x95, 0x01, 0x81, 0x02, 0x05, 0x01, 0x09, 0x39, 0x15, 0x00, 0x25, 0x07,
+    0x35, 0x00, 0x46, 0x3b, 0x01, 0x65, 0x14, 0x75, 0x04, 0x95, 0x01, 0x81, 0x42, 0x65, 0x00, 0x05,
+    0x09, 0x19, 0x01, 0x29, 0x0f, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x0f, 0x81, 0x02, 0x06,
+    0x00, 0xff, 0x09, 0x21, 0x95, 0x0d, 0x81, 0x02, 0x06, 0x00, 0xff, 0x09, 0x22, 0x15, 0x00, 0x26,
+    0xff, 0x00, 0x75, 0x08, 0x95, 0x34, 0x81, 0x02, 0x85, 0x02, 0x09, 0x23, 0x95, 0x2f, 0x91, 0x02,
+    0x85, 0x05, 0x09, 0x33, 0x95, 0x28, 0xb1, 0x02, 0x85, 0x08, 0x09, 0x34, 0x95, 0x2f, 0xb1, 0x02,
+    0x85, 0x09, 0x09, 0x24, 0x95, 0x13, 0xb1, 0x02, 0x85, 0x0a, 0x09, 0x25, 0x95, 0x1a, 0xb1, 0x02,
+    0x85, 0x20, 0x09, 0x26, 0x95, 0x3f, 0xb1, 0x02, 0x85, 0x21, 0x09, 0x27, 0x95, 0x04, 0xb1, 0x02,
+    0x85, 0x22, 0x09, 0x40, 0x95, 0x3f, 0xb1, 0x02, 0x85, 0x80, 0x09, 0x28, 0x95, 0x3f, 0xb1, 0x02,
+    0x85, 0x81, 0x09, 0x29, 0x95, 0x3f, 0xb1, 0x02, 0x85, 0x82, 0x09, 0x2a, 0x95, 0x09, 0xb1, 0x02,
+    0x85, 0x83, 0x09, 0x2b, 0x95, 0x3f, 0xb1, 0x02, 0x85, 0x84, 0x09, 0x2c, 0x95, 0x3f, 0xb1, 0x02,
+    0x85, 0x85, 0x09, 0x2d, 0x95, 0x02, 0xb1, 0x02, 0x85, 0xa0, 0x09, 0x2e, 0x95, 0x01, 0xb1, 0x02,
+    0x85, 0xe0, 0x09, 0x2f, 0x95, 0x3f, 0xb1, 0x02, 0x85, 0xf0, 0x09, 0x30, 0x95, 0x3f, 0xb1, 0x02,
+    0x85, 0xf1, 0x09, 0x31, 0x95, 0x3f, 0xb1, 0x02, 0x85, 0xf2, 0x09, 0x32, 0x95, 0x0f, 0xb1, 0x02,
+    0x85, 0xf4, 0x09, 0x35, 0x95, 0x3f, 0xb1, 0x02, 0x85, 0xf5, 0x09, 0x36, 0x95, 0x03, 0xb1, 0x02,
+    0xc0,
+];
+
+const DUALSHOCK4_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, 0x09, 0x05, 0xa1, 0x01, 0x85, 0x01, 0x09, 0x30, 0x09, 0x31, 0x09, 0x32, 0x09, 0x35,
+    0x15, 0x00, 0x26, 0xff, 0x00, 0x75, 0x08, 0x95, 0x04, 0x81, 0x02, 0x09, 0x39, 0x15, 0x00, 0x25,
+    0x07, 0x35, 0x00, 0x46, 0x3b, 0x01, 0x65, 0x14, 0x75, 0x04, 0x95, 0x01, 0x81, 0x42, 0x65, 0x00,
+    0x05, 0x09, 0x19, 0x01, 0x29, 0x0e, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x0e, 0x81, 0x02,
+    0x06, 0x00, 0xff, 0x09, 0x20, 0x75, 0x06, 0x95, 0x01, 0x15, 0x00, 0x25, 0x7f, 0x81, 0x02, 0x05,
+    0x01, 0x09, 0x33, 0x09, 0x34, 0x15, 0x00, 0x26, 0xff, 0x00, 0x75, 0x08, 0x95, 0x02, 0x81, 0x02,
+    0x06, 0x00, 0xff, 0x09, 0x21, 0x95, 0x36, 0x81, 0x02, 0x85, 0x05, 0x09, 0x22, 0x95, 0x1f, 0x91,
+    0x02, 0x85, 0x04, 0x09, 0x23, 0x95, 0x24, 0xb1, 0x02, 0x85, 0x02, 0x09, 0x24, 0x95, 0x24, 0xb1,
+    0x02, 0x85, 0x08, 0x09, 0x25, 0x95, 0x03, 0xb1, 0x02, 0x85, 0x10, 0x09, 0x26, 0x95, 0x04, 0xb1,
+    0x02, 0x85, 0x11, 0x09, 0x27, 0x95, 0x02, 0xb1, 0x02, 0x85, 0x12, 0x06, 0x02, 0xff, 0x09, 0x21,
+    0x95, 0x0f, 0xb1, 0x02, 0x85, 0x13, 0x09, 0x22, 0x95, 0x16, 0xb1, 0x02, 0x85, 0x14, 0x06, 0x05,
+    0xff, 0x09, 0x20, 0x95, 0x10, 0xb1, 0x02, 0x85, 0x15, 0x09, 0x21, 0x95, 0x2c, 0xb1, 0x02, 0x06,
+    0x80, 0xff, 0x85, 0x80, 0x09, 0x20, 0x95, 0x06, 0xb1, 0x02, 0x85, 0x81, 0x09, 0x21, 0x95, 0x06,
+    0xb1, 0x02, 0x85, 0x82, 0x09, 0x22, 0x95, 0x05, 0xb1, 0x02, 0x85, 0x83, 0x09, 0x23, 0x95, 0x01,
+    0xb1, 0x02, 0x85, 0x84, 0x09, 0x24, 0x95, 0x04, 0xb1, 0x02, 0x85, 0x85, 0x09, 0x25, 0x95, 0x06,
+    0xb1, 0x02, 0x85, 0x86, 0x09, 0x26, 0x95, 0x06, 0xb1, 0x02, 0x85, 0x87, 0x09, 0x27, 0x95, 0x23,
+    0xb1, 0x02, 0x85, 0x88, 0x09, 0x28, 0x95, 0x22, 0xb1, 0x02, 0x85, 0x89, 0x09, 0x29, 0x95, 0x02,
+    0xb1, 0x02, 0x85, 0x90, 0x09, 0x30, 0x95, 0x05, 0xb1, 0x02, 0x85, 0x91, 0x09, 0x31, 0x95, 0x03,
+    0xb1, 0x02, 0x85, 0x92, 0x09, 0x32, 0x95, 0x03, 0xb1, 0x02, 0x85, 0x93, 0x09, 0x33, 0x95, 0x0c,
+    0xb1, 0x02, 0x85, 0xa0, 0x09, 0x40, 0x95, 0x06, 0xb1, 0x02, 0x85, 0xa1, 0x09, 0x41, 0x95, 0x01,
+    0xb1, 0x02, 0x85, 0xa2, 0x09, 0x42, 0x95, 0x01, 0xb1, 0x02, 0x85, 0xa3, 0x09, 0x43, 0x95, 0x30,
+    0xb1, 0x02, 0x85, 0xa4, 0x09, 0x44, 0x95, 0x0d, 0xb1, 0x02, 0x85, 0xa5, 0x09, 0x45, 0x95, 0x15,
+    0xb1, 0x02, 0x85, 0xa6, 0x09, 0x46, 0x95, 0x15, 0xb1, 0x02, 0x85, 0xf0, 0x09, 0x47, 0x95, 0x3f,
+    0xb1, 0x02, 0x85, 0xf1, 0x09, 0x48, 0x95, 0x3f, 0xb1, 0x02, 0x85, 0xf2, 0x09, 0x49, 0x95, 0x0f,
+    0xb1, 0x02, 0x85, 0xa7, 0x09, 0x4a, 0x95, 0x01, 0xb1, 0x02, 0x85, 0xa8, 0x09, 0x4b, 0x95, 0x01,
+    0xb1, 0x02, 0x85, 0xa9, 0x09, 0x4c, 0x95, 0x08, 0xb1, 0x02, 0x85, 0xaa, 0x09, 0x4e, 0x95, 0x01,
+    0xb1, 0x02, 0x85, 0xab, 0x09, 0x4f, 0x95, 0x39, 0xb1, 0x02, 0x85, 0xac, 0x09, 0x50, 0x95, 0x39,
+    0xb1, 0x02, 0x85, 0xad, 0x09, 0x51, 0x95, 0x0b, 0xb1, 0x02, 0x85, 0xae, 0x09, 0x52, 0x95, 0x01,
+    0xb1, 0x02, 0x85, 0xaf, 0x09, 0x53, 0x95, 0x02, 0xb1, 0x02, 0x85, 0xb0, 0x09, 0x54, 0x95, 0x3f,
+    0xb1, 0x02, 0xc0,
+];
+
+// hid-sony replaces this with its own fixed up descriptor, it only has to parse
+const DUALSHOCK3_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, 0x09, 0x04, 0xa1, 0x01, 0xa1, 0x02, 0x85, 0x01, 0x75, 0x08, 0x95, 0x01, 0x15, 0x00,
+    0x26, 0xff, 0x00, 0x81, 0x03, 0x75, 0x01, 0x95, 0x13, 0x15, 0x00, 0x25, 0x01, 0x35, 0x00, 0x45,
+    0x01, 0x05, 0x09, 0x19, 0x01, 0x29, 0x13, 0x81, 0x02, 0x75, 0x01, 0x95, 0x0d, 0x06, 0x00, 0xff,
+    0x81, 0x03, 0x15, 0x00, 0x26, 0xff, 0x00, 0x05, 0x01, 0x09, 0x01, 0xa1, 0x00, 0x75, 0x08, 0x95,
+    0x04, 0x35, 0x00, 0x46, 0xff, 0x00, 0x09, 0x30, 0x09, 0x31, 0x09, 0x32, 0x09, 0x35, 0x81, 0x02,
+    0xc0, 0x05, 0x01, 0x95, 0x13, 0x09, 0x01, 0x81, 0x02, 0x95, 0x0c, 0x81, 0x01, 0x75, 0x10, 0x95,
+    0x04, 0x26, 0xff, 0x03, 0x46, 0xff, 0x03, 0x09, 0x01, 0x81, 0x02, 0xc0, 0xa1, 0x02, 0x85, 0x02,
+    0x75, 0x08, 0x95, 0x30, 0x09, 0x01, 0xb1, 0x02, 0xc0, 0xa1, 0x02, 0x85, 0xee, 0x75, 0x08, 0x95,
+    0x30, 0x09, 0x01, 0xb1, 0x02, 0xc0, 0xa1, 0x02, 0x85, 0xef, 0x75, 0x08, 0x95, 0x30, 0x09, 0x01,
+    0xb1, 0x02, 0xc0, 0xc0,
+];
+
+const PRO_CONTROLLER_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, 0x15, 0x00, 0x09, 0x04, 0xa1, 0x01, 0x85, 0x30, 0x05, 0x01, 0x05, 0x09, 0x19, 0x01,
+    0x29, 0x0a, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x0a, 0x55, 0x00, 0x65, 0x00, 0x81, 0x02,
+    0x05, 0x09, 0x19, 0x0b, 0x29, 0x0e, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x04, 0x81, 0x02,
+    0x75, 0x01, 0x95, 0x02, 0x81, 0x03, 0x0b, 0x01, 0x00, 0x01, 0x00, 0xa1, 0x00, 0x0b, 0x30, 0x00,
+    0x01, 0x00, 0x0b, 0x31, 0x00, 0x01, 0x00, 0x0b, 0x32, 0x00, 0x01, 0x00, 0x0b, 0x35, 0x00, 0x01,
+    0x00, 0x15, 0x00, 0x27, 0xff, 0xff, 0x00, 0x00, 0x75, 0x10, 0x95, 0x04, 0x81, 0x02, 0xc0, 0x0b,
+    0x39, 0x00, 0x01, 0x00, 0x15, 0x00, 0x25, 0x07, 0x35, 0x00, 0x46, 0x3b, 0x01, 0x65, 0x14, 0x75,
+    0x04, 0x95, 0x01, 0x81, 0x02, 0x05, 0x09, 0x19, 0x0f, 0x29, 0x12, 0x15, 0x00, 0x25, 0x01, 0x75,
+    0x01, 0x95, 0x04, 0x81, 0x02, 0x75, 0x08, 0x95, 0x34, 0x81, 0x03, 0x06, 0x00, 0xff, 0x85, 0x21,
+    0x09, 0x01, 0x75, 0x08, 0x95, 0x3f, 0x81, 0x03, 0x85, 0x81, 0x09, 0x02, 0x75, 0x08, 0x95, 0x3f,
+    0x81, 0x03, 0x85, 0x01, 0x09, 0x03, 0x75, 0x08, 0x95, 0x3f, 0x91, 0x83, 0x85, 0x10, 0x09, 0x04,
+    0x75, 0x08, 0x95, 0x3f, 0x91, 0x83, 0x85, 0x80, 0x09, 0x05, 0x75, 0x08, 0x95, 0x3f, 0x91, 0x83,
+    0x85, 0x82, 0x09, 0x06, 0x75, 0x08, 0x95, 0x3f, 0x91, 0x83, 0xc0,
+];
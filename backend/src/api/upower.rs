@@ -0,0 +1,584 @@
+use anyhow::{anyhow, Result};
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
+use dbus::blocking::Connection;
+use dbus::message::{MatchRule, SignalArgs};
+use dbus::Path as DBusPath;
+use log::{debug, error, info};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::controller::{Controller, Status};
+
+const UPOWER_SERVICE: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const DEVICES_PATH: &str = "/org/freedesktop/UPower/devices";
+const UPOWER_INTERFACE: &str = "org.freedesktop.UPower";
+const DEVICE_INTERFACE: &str = "org.freedesktop.UPower.Device";
+const DBUS_TIMEOUT: Duration = Duration::from_millis(5000);
+
+// org.freedesktop.UPower.Device State values
+const STATE_CHARGING: u32 = 1;
+const STATE_DISCHARGING: u32 = 2;
+const STATE_EMPTY: u32 = 3;
+const STATE_FULLY_CHARGED: u32 = 4;
+const STATE_PENDING_CHARGE: u32 = 5;
+const STATE_PENDING_DISCHARGE: u32 = 6;
+
+// org.freedesktop.UPower.Device BatteryLevel values, for devices that only report coarse levels
+const LEVEL_UNKNOWN: u32 = 0;
+const LEVEL_NONE: u32 = 1;
+const LEVEL_LOW: u32 = 3;
+const LEVEL_CRITICAL: u32 = 4;
+const LEVEL_NORMAL: u32 = 6;
+const LEVEL_HIGH: u32 = 7;
+const LEVEL_FULL: u32 = 8;
+
+// UPower devices keyed by object path. Only populated while `spawn_watcher` is running.
+static DEVICE_CACHE: OnceLock<Mutex<HashMap<String, UPowerDevice>>> = OnceLock::new();
+
+/// A battery as exposed by UPower's `org.freedesktop.UPower.Device`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UPowerDevice {
+    pub path: String,
+    // e.g. "gip0.0" for xone, "ps-controller-battery-aa:bb:cc:dd:ee:ff" for hid-playstation or
+    // "/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF" for BlueZ
+    pub native_path: String,
+    pub serial: String,
+    pub model: String,
+    pub is_present: bool,
+    pub percentage: f64,
+    pub state: u32,
+    pub battery_level: u32,
+}
+
+impl UPowerDevice {
+    fn from_properties(path: &str, properties: &PropMap) -> Self {
+        let mut device = Self {
+            path: path.to_string(),
+            is_present: true,
+            ..Default::default()
+        };
+        device.apply(properties);
+        device
+    }
+
+    /// Update the device with the (possibly partial) properties from a PropertiesChanged signal
+    fn apply(&mut self, properties: &PropMap) {
+        if let Some(native_path) = prop_cast::<String>(properties, "NativePath") {
+            self.native_path = native_path.clone();
+        }
+        if let Some(serial) = prop_cast::<String>(properties, "Serial") {
+            self.serial = serial.clone();
+        }
+        if let Some(model) = prop_cast::<String>(properties, "Model") {
+            self.model = model.clone();
+        }
+        if let Some(is_present) = prop_cast::<bool>(properties, "IsPresent") {
+            self.is_present = *is_present;
+        }
+        if let Some(percentage) = prop_cast::<f64>(properties, "Percentage") {
+            self.percentage = *percentage;
+        }
+        if let Some(state) = prop_cast::<u32>(properties, "State") {
+            self.state = *state;
+        }
+        if let Some(battery_level) = prop_cast::<u32>(properties, "BatteryLevel") {
+            self.battery_level = *battery_level;
+        }
+    }
+
+    pub fn capacity(&self) -> Result<u8> {
+        if !self.is_present {
+            return Err(anyhow!("{} has no battery present", self.path));
+        }
+        match self.battery_level {
+            // The device reports an actual percentage
+            LEVEL_UNKNOWN | LEVEL_NONE => Ok(self.percentage.clamp(0.0, 100.0).round() as u8),
+            LEVEL_FULL => Ok(100),
+            LEVEL_HIGH => Ok(75),
+            LEVEL_NORMAL => Ok(50),
+            LEVEL_LOW => Ok(20),
+            LEVEL_CRITICAL => Ok(5),
+            level => Err(anyhow!("{} has unknown battery level {}", self.path, level)),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self.state {
+            // "Pending charge" means plugged in but not charging
+            STATE_CHARGING | STATE_FULLY_CHARGED | STATE_PENDING_CHARGE => Status::Charging,
+            STATE_DISCHARGING | STATE_EMPTY | STATE_PENDING_DISCHARGE => Status::Discharging,
+            _ => Status::Unknown,
+        }
+    }
+
+    /// How well the device matches a controller, lower is better. Native paths are built from
+    /// the kernel's power_supply name, which holds the GIP device or Bluetooth address, while
+    /// the model name is only a last resort.
+    fn match_rank(&self, controller: &Controller) -> Option<u8> {
+        let native_path = self.native_path.to_lowercase().replace('_', ":");
+        let serial_number = controller
+            .serial_number
+            .as_deref()
+            .map(|serial_number| serial_number.trim().to_lowercase())
+            .filter(|serial_number| !serial_number.is_empty());
+
+        if controller.gip.starts_with("gip") && self.native_path == controller.gip {
+            return Some(0);
+        }
+        if let Some(serial_number) = &serial_number {
+            if native_path.contains(serial_number.as_str()) {
+                return Some(0);
+            }
+            if self.serial.trim().eq_ignore_ascii_case(serial_number) {
+                return Some(1);
+            }
+        }
+        if !self.model.is_empty() && self.model.eq_ignore_ascii_case(&controller.name) {
+            return Some(2);
+        }
+        None
+    }
+}
+
+/// Pick the UPower device of a controller. A match on the model name alone only counts when
+/// it is unambiguous.
+pub fn find_device<'a>(
+    devices: impl IntoIterator<Item = &'a UPowerDevice>,
+    controller: &Controller,
+) -> Option<&'a UPowerDevice> {
+    let mut matches: Vec<(u8, &UPowerDevice)> = devices
+        .into_iter()
+        .filter_map(|device| Some((device.match_rank(controller)?, device)))
+        .collect();
+    matches.sort_by_key(|(rank, _)| *rank);
+
+    match matches.as_slice() {
+        [(2, _), (2, _), ..] => None,
+        [(_, device), ..] => Some(*device),
+        [] => None,
+    }
+}
+
+/// Fill in the battery state of a controller from UPower
+pub fn update_controller(controller: &mut Controller) -> Result<()> {
+    let device = match cached_device(controller) {
+        Some(device) => device,
+        None => {
+            let upower = UPower::new()?;
+            let devices = upower.devices()?;
+            find_device(&devices, controller)
+                .cloned()
+                .ok_or_else(|| anyhow!("no UPower device for {}", controller.name))?
+        }
+    };
+    debug!("Found UPower device for {}: {:?}", controller.name, device);
+
+    controller.capacity = device.capacity()?;
+    controller.status = device.status();
+    Ok(())
+}
+
+fn cached_device(controller: &Controller) -> Option<UPowerDevice> {
+    let cache = DEVICE_CACHE.get()?.lock().ok()?;
+    find_device(cache.values(), controller).cloned()
+}
+
+/// Minimal UPower D-Bus client
+pub struct UPower {
+    connection: Connection,
+}
+
+impl UPower {
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_connection(Connection::new_system()?))
+    }
+
+    pub fn from_connection(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    pub fn devices(&self) -> Result<Vec<UPowerDevice>> {
+        let proxy = self
+            .connection
+            .with_proxy(UPOWER_SERVICE, UPOWER_PATH, DBUS_TIMEOUT);
+        let (paths,): (Vec<DBusPath<'static>>,) =
+            proxy.method_call(UPOWER_INTERFACE, "EnumerateDevices", ())?;
+
+        paths.iter().map(|path| self.device(path)).collect()
+    }
+
+    pub fn device(&self, path: &str) -> Result<UPowerDevice> {
+        let proxy = self
+            .connection
+            .with_proxy(UPOWER_SERVICE, path, DBUS_TIMEOUT);
+        let properties = proxy.get_all(DEVICE_INTERFACE)?;
+        Ok(UPowerDevice::from_properties(path, &properties))
+    }
+
+    /// Follow the devices UPower knows about, calling `on_change` with every device as it is
+    /// first seen or changes, and with `None` once it goes away. Never returns unless the
+    /// connection fails.
+    pub fn watch_devices<F>(&self, on_change: F) -> Result<()>
+    where
+        F: Fn(&str, Option<&UPowerDevice>) + Send + Sync + 'static,
+    {
+        let on_change = Arc::new(on_change);
+        let devices: Arc<Mutex<HashMap<String, UPowerDevice>>> = Arc::default();
+        // Properties of added devices are fetched outside of the signal callbacks
+        let added: Arc<Mutex<Vec<String>>> = Arc::default();
+
+        let rule = MatchRule::new_signal(UPOWER_INTERFACE, "DeviceAdded");
+        let pending = added.clone();
+        self.connection.add_match(
+            rule,
+            move |(path,): (DBusPath<'static>,), _: &Connection, _: &_| {
+                if let Ok(mut pending) = pending.lock() {
+                    pending.push(path.to_string());
+                }
+                true
+            },
+        )?;
+
+        let rule = MatchRule::new_signal(UPOWER_INTERFACE, "DeviceRemoved");
+        let (known, callback) = (devices.clone(), on_change.clone());
+        self.connection.add_match(
+            rule,
+            move |(path,): (DBusPath<'static>,), _: &Connection, _: &_| {
+                if let Ok(mut known) = known.lock() {
+                    known.remove(&path.to_string());
+                }
+                callback(&path, None);
+                true
+            },
+        )?;
+
+        let rule =
+            PropertiesPropertiesChanged::match_rule(None, None).with_namespaced_path(DEVICES_PATH);
+        let (known, callback) = (devices.clone(), on_change.clone());
+        self.connection.add_match(
+            rule,
+            move |signal: PropertiesPropertiesChanged, _: &Connection, message| {
+                if signal.interface_name != DEVICE_INTERFACE {
+                    return true;
+                }
+                let Some(path) = message.path() else {
+                    return true;
+                };
+                let Ok(mut known) = known.lock() else {
+                    return true;
+                };
+                if let Some(device) = known.get_mut(&*path) {
+                    device.apply(&signal.changed_properties);
+                    callback(&path, Some(device));
+                }
+                true
+            },
+        )?;
+
+        // Only list the devices once the signals are subscribed to so none falls in between
+        added
+            .lock()
+            .map_err(|err| anyhow!("{}", err))?
+            .extend(self.devices()?.into_iter().map(|device| device.path));
+
+        loop {
+            let pending = std::mem::take(&mut *added.lock().map_err(|err| anyhow!("{}", err))?);
+            for path in pending {
+                match self.device(&path) {
+                    Ok(device) => {
+                        on_change(&path, Some(&device));
+                        if let Ok(mut known) = devices.lock() {
+                            known.insert(path, device);
+                        }
+                    }
+                    Err(err) => error!("Failed to read UPower device {}: {}", path, err),
+                }
+            }
+            self.connection.process(Duration::from_secs(60))?;
+        }
+    }
+}
+
+/// Keep a cache of the UPower devices, calling `on_change` after every update.
+/// Runs on its own thread since the blocking D-Bus connection needs to be polled.
+pub fn spawn_watcher<F>(on_change: F)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let result = std::thread::Builder::new()
+        .name("upower".to_string())
+        .spawn(move || {
+            let cache = DEVICE_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+            let result = UPower::new().and_then(|upower| {
+                info!("Watching UPower devices");
+                upower.watch_devices(move |path, device| {
+                    debug!("UPower device {} is now {:?}", path, device);
+                    if let Ok(mut cache) = cache.lock() {
+                        match device {
+                            Some(device) => cache.insert(path.to_string(), device.clone()),
+                            None => cache.remove(path),
+                        };
+                    }
+                    on_change();
+                })
+            });
+            // The cache would go stale without the watcher
+            if let Ok(mut cache) = cache.lock() {
+                cache.clear();
+            }
+            if let Err(err) = result {
+                error!("UPower watcher stopped: {}", err);
+            }
+        });
+    if let Err(err) = result {
+        error!("Failed to spawn UPower thread: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_device, UPower, UPowerDevice, DEVICE_INTERFACE, UPOWER_INTERFACE};
+    use crate::api::test_bus::{prop, TestBus};
+    use crate::controller::{Controller, Status};
+    use dbus::arg::PropMap;
+    use dbus::blocking::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+    use dbus::blocking::Connection;
+    use dbus::channel::{MatchingReceiver, Sender};
+    use dbus::message::{MatchRule, SignalArgs};
+    use dbus::{Message, Path};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    const GIP_PATH: &str = "/org/freedesktop/UPower/devices/battery_gip0x0";
+    const DUALSENSE_PATH: &str =
+        "/org/freedesktop/UPower/devices/gaming_input_ps_controller_battery_aao3abbo3ccodd";
+
+    fn device(native_path: &str, serial: &str, model: &str) -> UPowerDevice {
+        UPowerDevice {
+            path: format!("/org/freedesktop/UPower/devices/{}", native_path),
+            native_path: native_path.to_string(),
+            serial: serial.to_string(),
+            model: model.to_string(),
+            is_present: true,
+            percentage: 50.0,
+            state: 2,
+            battery_level: 1,
+        }
+    }
+
+    fn controller(name: &str, serial_number: Option<&str>, gip: &str) -> Controller {
+        Controller {
+            name: name.to_string(),
+            product_id: 0x0b12,
+            vendor_id: 0x045e,
+            capacity: 0,
+            status: Status::Unknown,
+            bluetooth: false,
+            serial_number: serial_number.map(|serial_number| serial_number.to_string()),
+            device_path: None,
+            gip: gip.to_string(),
+            extension: None,
+            parts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_find_device() {
+        let devices = vec![
+            device("gip0.0", "", "Xbox Series X/S"),
+            device("ps-controller-battery-aa:bb:cc:dd:ee:ff", "", "DualSense"),
+            device("/org/bluez/hci0/dev_11_22_33_44_55_66", "", "Xbox Wireless"),
+            device("hid-0123456789-battery", "0123456789", "Pro Controller"),
+        ];
+
+        // By native path
+        let xbox = controller("Xbox Series X/S", None, "gip0.0");
+        assert_eq!(find_device(&devices, &xbox), Some(&devices[0]));
+        let dualsense = controller("DualSense", Some("AA:BB:CC:DD:EE:FF"), "NA");
+        assert_eq!(find_device(&devices, &dualsense), Some(&devices[1]));
+        let bluez = controller("Xbox Series X/S", Some("11:22:33:44:55:66"), "NA");
+        assert_eq!(find_device(&devices, &bluez), Some(&devices[2]));
+
+        // By serial
+        let pro = controller("Nintendo Controller", Some("0123456789"), "NA");
+        assert_eq!(find_device(&devices, &pro), Some(&devices[3]));
+
+        // By model, only when unambiguous
+        let pro = controller("Pro Controller", None, "NA");
+        assert_eq!(find_device(&devices, &pro), Some(&devices[3]));
+        let mut devices = devices;
+        devices.push(device(
+            "hid-9876543210-battery",
+            "9876543210",
+            "Pro Controller",
+        ));
+        assert_eq!(find_device(&devices, &pro), None);
+
+        assert_eq!(
+            find_device(&devices, &controller("Unknown", None, "gip0.1")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_capacity_and_status() {
+        let mut battery = device("gip0.0", "", "");
+        battery.percentage = 66.4;
+        assert_eq!(battery.capacity().unwrap(), 66);
+        assert_eq!(battery.status(), Status::Discharging);
+
+        // Coarse levels
+        battery.battery_level = 3;
+        assert_eq!(battery.capacity().unwrap(), 20);
+        battery.state = 4;
+        assert_eq!(battery.status(), Status::Charging);
+
+        // Errors instead of made up values
+        battery.battery_level = 42;
+        assert!(battery.capacity().is_err());
+        battery.battery_level = 1;
+        battery.is_present = false;
+        assert!(battery.capacity().is_err());
+    }
+
+    fn properties(native_path: &str, percentage: f64) -> PropMap {
+        let mut properties = PropMap::new();
+        properties.insert("NativePath".into(), prop(native_path.to_string()));
+        properties.insert("Serial".into(), prop(String::new()));
+        properties.insert("Model".into(), prop("Xbox Series X/S".to_string()));
+        properties.insert("IsPresent".into(), prop(true));
+        properties.insert("Percentage".into(), prop(percentage));
+        properties.insert("State".into(), prop(2u32));
+        properties.insert("BatteryLevel".into(), prop(1u32));
+        properties
+    }
+
+    enum MockSignal {
+        Added,
+        Removed,
+        Percentage(f64),
+    }
+
+    /// Serve the battery of an Xbox controller behind the Xbox Wireless Adapter the way UPower
+    /// would. The second device only shows up once `MockSignal::Added` is sent.
+    fn spawn_mock_upower(address: String) -> mpsc::Sender<MockSignal> {
+        let (signal_tx, signal_rx) = mpsc::channel::<MockSignal>();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let connection = Connection::new_address(&address).unwrap();
+            connection
+                .request_name("org.freedesktop.UPower", false, true, false)
+                .unwrap();
+            let added = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let is_added = added.clone();
+            connection.start_receive(
+                MatchRule::new_method_call(),
+                Box::new(move |message, connection| {
+                    let path = message.path().map(|path| path.to_string());
+                    let reply = match (message.member().as_deref(), path.as_deref()) {
+                        (Some("EnumerateDevices"), _) => {
+                            let mut paths = vec![Path::from(GIP_PATH)];
+                            if is_added.load(std::sync::atomic::Ordering::SeqCst) {
+                                paths.push(Path::from(DUALSENSE_PATH));
+                            }
+                            message.method_return().append1(paths)
+                        }
+                        (Some("GetAll"), Some(GIP_PATH)) => {
+                            message.method_return().append1(properties("gip0.0", 80.0))
+                        }
+                        (Some("GetAll"), Some(DUALSENSE_PATH)) => message
+                            .method_return()
+                            .append1(properties("ps-controller-battery-aa:bb:cc:dd", 30.0)),
+                        _ => message.error(
+                            &"org.freedesktop.DBus.Error.UnknownMethod".into(),
+                            &std::ffi::CString::new("unknown method").unwrap(),
+                        ),
+                    };
+                    let _ = connection.send(reply);
+                    true
+                }),
+            );
+            ready_tx.send(()).unwrap();
+
+            loop {
+                connection.process(Duration::from_millis(50)).unwrap();
+                let signal = match signal_rx.try_recv() {
+                    Ok(MockSignal::Added) => {
+                        added.store(true, std::sync::atomic::Ordering::SeqCst);
+                        Message::new_signal(super::UPOWER_PATH, UPOWER_INTERFACE, "DeviceAdded")
+                            .unwrap()
+                            .append1(Path::from(DUALSENSE_PATH))
+                    }
+                    Ok(MockSignal::Removed) => {
+                        Message::new_signal(super::UPOWER_PATH, UPOWER_INTERFACE, "DeviceRemoved")
+                            .unwrap()
+                            .append1(Path::from(DUALSENSE_PATH))
+                    }
+                    Ok(MockSignal::Percentage(percentage)) => {
+                        let mut changed = PropMap::new();
+                        changed.insert("Percentage".into(), prop(percentage));
+                        PropertiesPropertiesChanged {
+                            interface_name: DEVICE_INTERFACE.to_string(),
+                            changed_properties: changed,
+                            invalidated_properties: Vec::new(),
+                        }
+                        .to_emit_message(&Path::from(GIP_PATH))
+                    }
+                    Err(_) => continue,
+                };
+                let _ = connection.send(signal);
+            }
+        });
+
+        ready_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        signal_tx
+    }
+
+    #[test]
+    fn test_upower_client() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let signal_tx = spawn_mock_upower(bus.address.clone());
+
+        let upower = UPower::from_connection(Connection::new_address(&bus.address).unwrap());
+        let devices = upower.devices().unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].path, GIP_PATH);
+        assert_eq!(devices[0].native_path, "gip0.0");
+        assert_eq!(devices[0].capacity().unwrap(), 80);
+        assert_eq!(devices[0].status(), Status::Discharging);
+
+        // Push updates
+        let (changes_tx, changes_rx) = mpsc::channel();
+        let address = bus.address.clone();
+        std::thread::spawn(move || {
+            let upower = UPower::from_connection(Connection::new_address(&address).unwrap());
+            let changes_tx = std::sync::Mutex::new(changes_tx);
+            let _ = upower.watch_devices(move |path, device| {
+                let _ = changes_tx.lock().unwrap().send((
+                    path.to_string(),
+                    device.map(|device| device.capacity().unwrap()),
+                ));
+            });
+        });
+        let next = || changes_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        // Devices known at startup are reported first
+        assert_eq!(next(), (GIP_PATH.to_string(), Some(80)));
+
+        signal_tx.send(MockSignal::Percentage(60.0)).unwrap();
+        assert_eq!(next(), (GIP_PATH.to_string(), Some(60)));
+
+        signal_tx.send(MockSignal::Added).unwrap();
+        assert_eq!(next(), (DUALSENSE_PATH.to_string(), Some(30)));
+
+        signal_tx.send(MockSignal::Removed).unwrap();
+        assert_eq!(next(), (DUALSENSE_PATH.to_string(), None));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::debug;
+
+use crate::controller::Status;
+
+use super::database::{self, Quirk};
+use super::driver::ControllerDriver;
+use super::report::InputReport;
+use super::transport::{HidConnection, HidDevice, HidTransport};
+use super::Controller;
+
+// Every report of the controller is 64 bytes: 0x01 0x00, then the type and length of the message
+const REPORT_SIZE: usize = 64;
+const REPORT_HEADER: [u8; 2] = [0x01, 0x00];
+const REPORT_TYPE: usize = 2;
+const ID_CONTROLLER_WIRELESS: u8 = 0x03;
+const ID_CONTROLLER_STATUS: u8 = 0x04;
+
+// Message of the receiver when a pad connects to or leaves one of its slots
+const WIRELESS_EVENT: usize = 4;
+const WIRELESS_CONNECTED: u8 = 0x02;
+// Millivolts as a u16, followed by a percentage the controller works out itself
+const STATUS_VOLTAGE: usize = 12;
+
+// Feature report that makes the receiver send a wireless event for the slot right away
+const CMD_DONGLE_GET_STATE: u8 = 0xb4;
+const FEATURE_REPORT_SIZE: usize = REPORT_SIZE + 1;
+
+// The controller runs on two AA cells. Their voltage falls close to linearly from fresh to
+// the point where the controller turns itself off.
+const VOLTAGE_FULL_MV: u16 = 3000;
+const VOLTAGE_EMPTY_MV: u16 = 2200;
+
+// Status reports only come every few seconds
+const STATUS_REPORT_TIMEOUT: Duration = Duration::from_millis(1000);
+const READ_TIMEOUT_MS: i32 = 100;
+
+// Last voltage of each controller by ID, for the polls that don't see a new status report
+static VOLTAGES: OnceLock<Mutex<HashMap<String, u16>>> = OnceLock::new();
+// Whether a pad is connected to each slot of a receiver, by hidraw path
+static SLOTS: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+
+/// The Steam Controller, wired or on its wireless receiver, and later pads that speak the same
+/// protocol. The Steam Deck's own gamepad is left out through the device table.
+pub struct ValveDriver;
+
+impl ControllerDriver for ValveDriver {
+    fn name(&self) -> &'static str {
+        "valve"
+    }
+
+    fn matches_hid(&self, device: &HidDevice) -> bool {
+        database::get().driver(device.vendor_id, device.product_id) == Some(self.name())
+    }
+
+    fn dedupe_hid(&self, mut devices: Vec<HidDevice>) -> Vec<HidDevice> {
+        // Every slot of the receiver is an interface of its own, all with the receiver's
+        // serial number
+        let mut paths = HashSet::new();
+        devices.retain(|device| paths.insert(device.path.clone()));
+        devices
+    }
+
+    fn probe_hid(&self, device: &HidDevice) -> Controller {
+        let name = database::get()
+            .name(device.vendor_id, device.product_id)
+            .or(device.product_string.as_deref())
+            .unwrap_or("Steam Controller");
+        debug!("Found {} controller: {:?}", name, device);
+        if !is_dongle(device) {
+            return Controller::from_hidapi(device, name, 0, Status::Unknown);
+        }
+        // Tell the pads on one receiver apart by their slot, the interface number
+        let name = format!("{} #{}", name, device.interface_number);
+        let mut controller = Controller::from_hidapi(device, &name, 0, Status::Unknown);
+        controller.bluetooth = true;
+        controller
+    }
+
+    fn read_battery_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        transport: &dyn HidTransport,
+    ) -> Result<()> {
+        let mut connection = transport.open(device)?;
+        let dongle = is_dongle(device);
+        if dongle {
+            request_wireless_state(connection.as_mut())?;
+        }
+        let (connected, voltage) = read_reports(connection.as_mut())?;
+
+        // A slot nothing answers for is empty
+        if dongle {
+            let connected = connected.unwrap_or(false);
+            if let Ok(mut slots) = SLOTS.get_or_init(|| Mutex::new(HashMap::new())).lock() {
+                slots.insert(device.path.clone(), connected);
+            }
+        }
+
+        let voltage = match voltage {
+            Some(voltage) => {
+                if let Ok(mut voltages) = VOLTAGES.get_or_init(|| Mutex::new(HashMap::new())).lock()
+                {
+                    voltages.insert(controller.id(), voltage);
+                }
+                Some(voltage)
+            }
+            None => cached_voltage(&controller.id()),
+        };
+        if let Some(voltage) = voltage {
+            controller.capacity = voltage_to_percentage(voltage);
+            controller.status = Status::Discharging;
+        }
+        Ok(())
+    }
+
+    fn is_connected(&self, controller: &Controller) -> bool {
+        let Some(path) = controller.device_path.as_deref() else {
+            return true;
+        };
+        SLOTS
+            .get()
+            .and_then(|slots| slots.lock().ok()?.get(path).copied())
+            .unwrap_or(true)
+    }
+
+    fn forget(&self, device_path: &str) {
+        // Whatever pairs to the slot next is another pad, with batteries of its own
+        if let Some(voltages) = VOLTAGES.get() {
+            if let Ok(mut voltages) = voltages.lock() {
+                voltages.remove(device_path);
+            }
+        }
+        if let Some(slots) = SLOTS.get() {
+            if let Ok(mut slots) = slots.lock() {
+                slots.remove(device_path);
+            }
+        }
+    }
+}
+
+fn is_dongle(device: &HidDevice) -> bool {
+    database::get().has_quirk(device.vendor_id, device.product_id, Quirk::Dongle)
+}
+
+fn request_wireless_state(connection: &mut dyn HidConnection) -> Result<()> {
+    // No report IDs, the first byte is 0
+    let mut report = [0u8; FEATURE_REPORT_SIZE];
+    report[1] = CMD_DONGLE_GET_STATE;
+    connection.send_feature_report(&report)
+}
+
+/// Whether a pad is there, when a report told, and the battery voltage of the first status
+/// report
+fn read_reports(connection: &mut dyn HidConnection) -> Result<(Option<bool>, Option<u16>)> {
+    let mut buf = [0u8; REPORT_SIZE];
+    let mut connected = None;
+    let deadline = Instant::now() + STATUS_REPORT_TIMEOUT;
+    while Instant::now() < deadline {
+        let len = connection.read_timeout(&mut buf, READ_TIMEOUT_MS)?;
+        let Ok(report) = InputReport::new(&buf, len) else {
+            continue;
+        };
+        if report.bytes(0, REPORT_HEADER.len()) != Ok(&REPORT_HEADER[..]) {
+            continue;
+        }
+        match report.u8(REPORT_TYPE)? {
+            ID_CONTROLLER_WIRELESS => {
+                connected = Some(report.u8(WIRELESS_EVENT)? == WIRELESS_CONNECTED);
+                if connected == Some(false) {
+                    break;
+                }
+            }
+            ID_CONTROLLER_STATUS => {
+                let voltage = report.u16_le(STATUS_VOLTAGE)?;
+                return Ok((Some(true), Some(voltage)));
+            }
+            // Input state and the other messages only come from a connected pad
+            _ => connected = Some(true),
+        }
+    }
+    Ok((connected, None))
+}
+
+fn cached_voltage(id: &str) -> Option<u16> {
+    VOLTAGES.get()?.lock().ok()?.get(id).copied()
+}
+
+fn voltage_to_percentage(voltage: u16) -> u8 {
+    let voltage = voltage.clamp(VOLTAGE_EMPTY_MV, VOLTAGE_FULL_MV);
+    ((voltage - VOLTAGE_EMPTY_MV) as u32 * 100 / (VOLTAGE_FULL_MV - VOLTAGE_EMPTY_MV) as u32) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_reports, voltage_to_percentage, ValveDriver};
+    use crate::api::driver::ControllerDriver;
+    use crate::api::report::ReportError;
+    use crate::api::transport::fixture::Fixture;
+    use crate::api::transport::replay::ReplayTransport;
+    use crate::api::transport::HidTransport;
+    use crate::controller::Status;
+
+    #[test]
+    fn test_report_layouts() {
+        // Connected event, input state, then a status report with 2800mV
+        let mut fixture = Fixture::named("steam_controller_dongle");
+        fixture.reports.truncate(3);
+        let transport = ReplayTransport::new(vec![fixture.clone()]);
+        let mut connection = transport.open(&transport.devices()[0]).unwrap();
+        assert_eq!(
+            read_reports(connection.as_mut()).unwrap(),
+            (Some(true), Some(2800))
+        );
+
+        // A status report that ends in the middle of the voltage
+        fixture.reports[2].data.truncate(13);
+        let transport = ReplayTransport::new(vec![fixture]);
+        let mut connection = transport.open(&transport.devices()[0]).unwrap();
+        let err = read_reports(connection.as_mut()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReportError>(),
+            Some(ReportError::Short { needed: 14, .. })
+        ));
+    }
+
+    #[test]
+    fn test_slot_reused() {
+        let path = "/dev/replay/steam_controller_dongle_reused";
+        let read = |name: &str, reports: usize| {
+            let mut fixture = Fixture::named(name);
+            fixture.device.path = path.to_string();
+            fixture.reports.truncate(reports);
+            let device = fixture.device.clone();
+            let transport = ReplayTransport::new(vec![fixture]);
+            let mut controller = ValveDriver.probe_hid(&device);
+            ValveDriver
+                .read_battery_hid(&mut controller, &device, &transport)
+                .unwrap();
+            controller
+        };
+
+        let controller = read("steam_controller_dongle", 3);
+        assert_eq!(controller.capacity, 75);
+        assert!(ValveDriver.is_connected(&controller));
+
+        // The pad leaves its slot and drops out of the inventory
+        let controller = read("steam_controller_dongle_empty", usize::MAX);
+        assert!(!ValveDriver.is_connected(&controller));
+        crate::api::forget(&controller);
+
+        // Another pad pairs to the slot and hasn't sent a status report yet
+        let controller = read("steam_controller_dongle", 2);
+        assert!(ValveDriver.is_connected(&controller));
+        assert_eq!(controller.capacity, 0);
+        assert_eq!(controller.status, Status::Unknown);
+    }
+
+    #[test]
+    fn test_voltage_to_percentage() {
+        assert_eq!(voltage_to_percentage(3200), 100);
+        assert_eq!(voltage_to_percentage(3000), 100);
+        assert_eq!(voltage_to_percentage(2800), 75);
+        assert_eq!(voltage_to_percentage(2200), 0);
+        assert_eq!(voltage_to_percentage(1800), 0);
+    }
+}
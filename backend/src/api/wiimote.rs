@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::debug;
+
+use crate::controller::Status;
+
+use super::database;
+use super::driver::ControllerDriver;
+use super::report::{InputReport, ReportError};
+use super::transport::{HidConnection, HidDevice, HidTransport};
+use super::Controller;
+
+// Output report asking for a status report. Its only byte holds the rumble bit, which is
+// left off.
+const OUTPUT_STATUS_REQUEST: [u8; 2] = [0x15, 0x00];
+// 0x20 BB BB LF 00 00 VV: buttons, flags, battery level
+const INPUT_STATUS: u8 = 0x20;
+const STATUS_REPORT_SIZE: usize = 7;
+const STATUS_FLAGS: usize = 3;
+const STATUS_BATTERY: usize = 6;
+const FLAG_EXTENSION: u8 = 0x02;
+
+const MAX_REPORT_SIZE: usize = 22;
+// The status report comes between the data reports the remote keeps sending
+const STATUS_REPORT_TIMEOUT: Duration = Duration::from_millis(1000);
+const READ_TIMEOUT_MS: i32 = 100;
+
+// Every Wii device reports the same IDs, only the product string tells them apart
+const PRO_CONTROLLER_PRODUCT: &str = "RVL-CNT-01-UC";
+const BALANCE_BOARD_PRODUCT: &str = "RVL-WBC-01";
+
+/// Wii Remote, Wii Remote Plus and Wii U Pro Controller. With hid-wiimote loaded the battery
+/// comes from its power_supply node and the extension from its sysfs attribute. Without it the
+/// remote is asked for a status report.
+pub struct WiimoteDriver;
+
+impl ControllerDriver for WiimoteDriver {
+    fn name(&self) -> &'static str {
+        "wiimote"
+    }
+
+    fn matches_hid(&self, device: &HidDevice) -> bool {
+        database::get().driver(device.vendor_id, device.product_id) == Some(self.name())
+    }
+
+    fn probe_hid(&self, device: &HidDevice) -> Controller {
+        let name = model_name(device)
+            .or(database::get().name(device.vendor_id, device.product_id))
+            .or(device.product_string.as_deref())
+            .unwrap_or("Wii Remote");
+        debug!("Found {} controller: {:?}", name, device);
+        Controller::from_hidapi(device, name, 0, Status::Unknown)
+    }
+
+    fn identify_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        _transport: &dyn HidTransport,
+    ) -> Result<()> {
+        if let Some(extension) = extension_attribute(&device.path) {
+            controller.extension = extension_name(&extension).map(str::to_string);
+        }
+        Ok(())
+    }
+
+    fn read_battery_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        transport: &dyn HidTransport,
+    ) -> Result<()> {
+        let mut connection = transport.open(device)?;
+        let Some(status) = request_status(connection.as_mut())? else {
+            debug!("No status report from {}", device.path);
+            return Ok(());
+        };
+        controller.capacity = (status.battery as u32 * 100 / 255) as u8;
+        // The remotes run on AA cells, there is no charging. The Pro Controller has a
+        // rechargeable battery but the report doesn't tell whether it is charging.
+        controller.status = if is_pro_controller(device) {
+            Status::Unknown
+        } else {
+            Status::Discharging
+        };
+
+        // The Pro Controller always reports an extension, the kernel driver knows better
+        let has_extension = status.flags & FLAG_EXTENSION != 0;
+        if model_name(device).is_none() && extension_attribute(&device.path).is_none() {
+            controller.extension = has_extension.then(|| "Extension".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn is_pro_controller(device: &HidDevice) -> bool {
+    device
+        .product_string
+        .as_deref()
+        .is_some_and(|product| product.ends_with(PRO_CONTROLLER_PRODUCT))
+}
+
+fn model_name(device: &HidDevice) -> Option<&'static str> {
+    let product = device.product_string.as_deref()?;
+    if is_pro_controller(device) {
+        Some("Wii U Pro Controller")
+    } else if product.ends_with(BALANCE_BOARD_PRODUCT) {
+        Some("Wii Balance Board")
+    } else {
+        None
+    }
+}
+
+/// The `extension` attribute hid-wiimote gives the HID device behind "/dev/hidraw5"
+fn extension_attribute(hidraw_path: &str) -> Option<String> {
+    let name = Path::new(hidraw_path).file_name()?;
+    let path = Path::new("/sys/class/hidraw")
+        .join(name)
+        .join("device/extension");
+    fs::read_to_string(path)
+        .ok()
+        .map(|extension| extension.trim().to_string())
+}
+
+/// Display name of what hid-wiimote reports as plugged in. The Balance Board and the Pro
+/// Controller show up as extensions of themselves.
+fn extension_name(extension: &str) -> Option<&'static str> {
+    match extension {
+        "none" | "balanceboard" | "procontroller" => None,
+        "nunchuk" => Some("Nunchuk"),
+        "classic" => Some("Classic Controller"),
+        "motionp" => Some("Wii MotionPlus"),
+        "motionp+nunchuk" => Some("Wii MotionPlus + Nunchuk"),
+        "motionp+classic" => Some("Wii MotionPlus + Classic Controller"),
+        "drums" => Some("Drums"),
+        "guitar" => Some("Guitar"),
+        _ => Some("Extension"),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct StatusReport {
+    flags: u8,
+    battery: u8,
+}
+
+impl StatusReport {
+    fn parse(report: &InputReport) -> Result<Self, ReportError> {
+        report.expect(INPUT_STATUS, STATUS_REPORT_SIZE)?;
+        Ok(Self {
+            flags: report.u8(STATUS_FLAGS)?,
+            battery: report.u8(STATUS_BATTERY)?,
+        })
+    }
+}
+
+fn request_status(connection: &mut dyn HidConnection) -> Result<Option<StatusReport>> {
+    connection.write(&OUTPUT_STATUS_REQUEST)?;
+    let mut buf = [0u8; MAX_REPORT_SIZE];
+    let deadline = Instant::now() + STATUS_REPORT_TIMEOUT;
+    while Instant::now() < deadline {
+        let len = connection.read_timeout(&mut buf, READ_TIMEOUT_MS)?;
+        // Nothing arrived, or a data report
+        let Ok(report) = InputReport::new(&buf, len) else {
+            continue;
+        };
+        if report.id() == INPUT_STATUS {
+            return Ok(Some(StatusReport::parse(&report)?));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extension_name, StatusReport, WiimoteDriver, OUTPUT_STATUS_REQUEST};
+    use crate::api::driver::ControllerDriver;
+    use crate::api::report::{InputReport, ReportError};
+    use crate::api::transport::{fixture::Fixture, replay::ReplayTransport};
+    use crate::controller::Status;
+
+    #[test]
+    fn test_status_report_layout() {
+        let buf = [0x20, 0x00, 0x00, 0x12, 0x00, 0x00, 0xc0];
+        assert_eq!(
+            StatusReport::parse(&InputReport::new(&buf, buf.len()).unwrap()),
+            Ok(StatusReport {
+                flags: 0x12,
+                battery: 0xc0
+            })
+        );
+        assert!(matches!(
+            StatusReport::parse(&InputReport::new(&buf, 6).unwrap()),
+            Err(ReportError::Short { .. })
+        ));
+    }
+
+    #[test]
+    fn test_status_report() {
+        let fixture = Fixture::named("wii_remote_bt");
+        let device = fixture.device.clone();
+        let transport = ReplayTransport::new(vec![fixture]);
+        let driver = WiimoteDriver;
+        assert!(driver.matches_hid(&device));
+
+        let mut controller = driver.probe_hid(&device);
+        assert_eq!(controller.name, "Wii Remote");
+        driver
+            .read_battery_hid(&mut controller, &device, &transport)
+            .unwrap();
+        assert_eq!(controller.capacity, 75);
+        assert_eq!(controller.status, Status::Discharging);
+        assert_eq!(controller.extension.as_deref(), Some("Extension"));
+        assert_eq!(
+            transport.output_reports(&device.path),
+            vec![OUTPUT_STATUS_REQUEST.to_vec()]
+        );
+
+        // The extension was pulled out before the next poll
+        driver
+            .read_battery_hid(&mut controller, &device, &transport)
+            .unwrap();
+        assert_eq!(controller.capacity, 25);
+        assert_eq!(controller.extension, None);
+
+        let mut fixture = Fixture::named("wii_remote_bt");
+        fixture.device.path = "/dev/replay/wii_u_pro_bt".to_string();
+        fixture.device.product_id = 0x0330;
+        fixture.device.product_string = Some("Nintendo RVL-CNT-01-UC".to_string());
+        let pro_controller = fixture.device.clone();
+        let transport = ReplayTransport::new(vec![fixture]);
+        let mut controller = driver.probe_hid(&pro_controller);
+        assert_eq!(controller.name, "Wii U Pro Controller");
+        driver
+            .read_battery_hid(&mut controller, &pro_controller, &transport)
+            .unwrap();
+        assert_eq!(controller.capacity, 75);
+        assert_eq!(controller.status, Status::Unknown);
+        assert_eq!(extension_name("nunchuk"), Some("Nunchuk"));
+        assert_eq!(extension_name("procontroller"), None);
+    }
+}
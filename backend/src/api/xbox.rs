@@ -1,85 +1,174 @@
 use crate::controller::Status;
 
 use super::bluetooth::{get_battery_percentage, get_bluetooth_address};
-use dbus::blocking::Connection;
-use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
-use dbus::Path;
-use std::time::Duration;
 use anyhow::Result;
-use hidapi::{DeviceInfo, HidApi};
-use log::error;
+use log::{debug, error};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use udev::Device;
 
+use super::database;
+use super::driver::ControllerDriver;
+use super::report::InputReport;
+use super::transport::{HidDevice, HidTransport};
+use super::upower;
 use super::Controller;
 
 pub const MS_VENDOR_ID: u16 = 0x045e;
 
-// Xbox One S controller
-pub const XBOX_ONE_S_CONTROLLER_USB_PRODUCT_ID: u16 = 0x02ea; // 746
-pub const XBOX_ONE_S_CONTROLLER_BT_PRODUCT_ID: u16 = 0x02df; // 765
+// Input report the controller sends over Bluetooth when its battery changes. Its one byte
+// has the same layout as the battery status of GIP (the USB and wireless adapter protocol).
+const BATTERY_REPORT_ID: u8 = 0x04;
+const BATTERY_STATUS: usize = 1;
+const BATTERY_LEVEL: u8 = 0x03;
+const BATTERY_CHARGING: u8 = 0x10;
+// Large enough for any of the controller's input reports
+const MAX_REPORT_SIZE: usize = 64;
+// The battery report isn't sent with every input report, give it a moment to show up
+const BATTERY_REPORT_TIMEOUT: Duration = Duration::from_millis(1000);
+const READ_TIMEOUT_MS: i32 = 100;
 
-// after upgrade to the latest firmware (same as Series X/S),
-// the One S controller changed product ID!
-pub const XBOX_ONE_S_LATEST_FW_PRODUCT_ID: u16 = 0x0b20; // 2848
-
-// Xbox Wireless Controller (model 1914)
-pub const XBOX_WIRELESS_CONTROLLER_USB_PRODUCT_ID: u16 = 0x0b12; // 2834
-pub const XBOX_WIRELESS_CONTROLLER_BT_PRODUCT_ID: u16 = 0x0b13; // 2835
-
-// Xbox Elite Wireless Controller Series 2
-pub const XBOX_WIRELESS_ELITE_CONTROLLER_USB_PRODUCT_ID: u16 = 0x0b00;
-pub const XBOX_WIRELESS_ELITE_CONTROLLER_BT_PRODUCT_ID: u16 = 0x0b05;
-pub const XBOX_WIRELESS_ELITE_CONTROLLER_BTLE_PRODUCT_ID: u16 = 0x0b22;
-// Xbox Accessory
-pub const XBOX_ACCESSORY_PID: u16 = 0x02fe; // New accessory PID
-// pub const XBOX_ONE_REPORT_BT_SIZE: usize = 64;
+// Last battery report of each controller by ID, for the polls that don't see a new one
+static BATTERY_REPORTS: OnceLock<Mutex<HashMap<String, u8>>> = OnceLock::new();
 
 fn get_xbox_controller_name(product_id: u16) -> &'static str {
-    match product_id {
-        XBOX_ONE_S_CONTROLLER_USB_PRODUCT_ID => "Xbox One S",
-        XBOX_ONE_S_CONTROLLER_BT_PRODUCT_ID => "Xbox One S",
-        XBOX_ONE_S_LATEST_FW_PRODUCT_ID => "Xbox One S",
-        XBOX_WIRELESS_CONTROLLER_USB_PRODUCT_ID => "Xbox Series X/S",
-        XBOX_WIRELESS_CONTROLLER_BT_PRODUCT_ID => "Xbox Series X/S",
-        XBOX_WIRELESS_ELITE_CONTROLLER_USB_PRODUCT_ID => "Xbox Elite 2",
-        XBOX_WIRELESS_ELITE_CONTROLLER_BT_PRODUCT_ID => "Xbox Elite 2",
-        XBOX_WIRELESS_ELITE_CONTROLLER_BTLE_PRODUCT_ID => "Xbox Elite 2",
-        XBOX_ACCESSORY_PID => "Wireless Adapter",
-        _ => "Xbox Unknown",
-    }
+    database::get()
+        .name(MS_VENDOR_ID, product_id)
+        .unwrap_or("Xbox Unknown")
 }
 
 pub fn is_xbox_controller(vendor_id: u16) -> bool {
     vendor_id == MS_VENDOR_ID
 }
 
-pub fn update_xbox_controller(controller: &mut Controller, bluetooth: bool) {
+/// Xbox pads show up through hidapi when connected over Bluetooth, and as udev `input`
+/// devices when connected over USB or through the Xbox Wireless Adapter (xone's GIP bus).
+pub struct XboxDriver;
+
+impl ControllerDriver for XboxDriver {
+    fn name(&self) -> &'static str {
+        "xbox"
+    }
+
+    fn matches_hid(&self, device: &HidDevice) -> bool {
+        // Only Bluetooth pads have a hidraw node, xpad and xone aren't HID drivers
+        database::get().driver(device.vendor_id, device.product_id) == Some(self.name())
+    }
+
+    fn probe_hid(&self, device: &HidDevice) -> Controller {
+        let name = get_xbox_controller_name(device.product_id);
+        debug!("Found {} controller: {:?}", name, device);
+        Controller::from_hidapi(device, name, 0, Status::Unknown)
+    }
+
+    fn read_battery_hid(
+        &self,
+        controller: &mut Controller,
+        device: &HidDevice,
+        transport: &dyn HidTransport,
+    ) -> Result<()> {
+        parse_xbox_controller_data(controller, device, transport)
+    }
+
+    fn matches_udev(&self, device: &Device) -> bool {
+        device
+            .property_value("ID_VENDOR_ID")
+            .map(|vendor_id| vendor_id.eq_ignore_ascii_case("045e"))
+            .unwrap_or(false)
+    }
+
+    fn probe_udev(&self, device: &Device) -> Option<Controller> {
+        let controller = Controller::from_udev(device, "Unknown Controller", 0, Status::Unknown);
+
+        // Only include records where gip starts with "gip" or "input" and exclude "gip0.1".
+        // Third party pads (e.g. PowerA) can connect through the adapter too.
+        if !(controller.gip.starts_with("gip") || controller.gip.starts_with("input"))
+            || controller.gip == "gip0.1"
+            || !(controller.gip.starts_with("gip") || is_xbox_controller(controller.vendor_id))
+        {
+            return None;
+        }
+        Some(controller)
+    }
+
+    fn dedupe_udev(&self, controllers: Vec<Controller>) -> Vec<Controller> {
+        // Deduplicate based on 'gip'
+        let mut seen_gips = HashSet::new();
+        controllers
+            .into_iter()
+            .filter(|controller| seen_gips.insert(controller.gip.clone()))
+            .collect()
+    }
+
+    fn read_battery_udev(&self, controller: &mut Controller) -> Result<()> {
+        update_xbox_controller(controller)
+    }
+
+    fn forget(&self, device_path: &str) {
+        if let Some(reports) = BATTERY_REPORTS.get() {
+            if let Ok(mut reports) = reports.lock() {
+                reports.remove(device_path);
+            }
+        }
+    }
+}
 
+/// Pads on USB or on the Xbox Wireless Adapter, the Bluetooth ones go through
+/// `parse_xbox_controller_data`
+pub fn update_xbox_controller(controller: &mut Controller) -> Result<()> {
     controller.name = get_xbox_controller_name(controller.product_id).to_string();
-    controller.capacity = if controller.gip.starts_with("gip") {
-        get_battery_percentage_for_gip(&controller.gip)
-    } else if bluetooth {
-        0
-    } else {
-        100
-    };
-    //controller.capacity = if bluetooth { 0 } else { 99 }; // for now for USB, "fake" it and set capacity to 100 as charging
+    // Pads behind the Xbox Wireless Adapter report their battery through xone, which UPower picks up
+    if controller.gip.starts_with("gip") {
+        controller.name = gip_label(&controller.name, &controller.gip);
+        return upower::update_controller(controller);
+    }
 
-    controller.status =
-    if controller.gip.starts_with("gip")
-    {Status::Unknown}
-    else if bluetooth {
-        Status::Unknown
-    } else {
-        // for now for USB, "fake" it and set status to charging since it's plugged in
-        Status::Charging
-    };
- }
+    // xone's power_supply node was already tried by discovery. xpad doesn't read the GIP
+    // battery status, so all we know is that a pad on a cable is powered by it.
+    controller.capacity = 0;
+    controller.status = Status::Charging;
+    Ok(())
+}
+
+/// Tell pads of the same model apart by the slot xone gave them on the adapter, e.g.
+/// "Xbox Series X/S #2" for "gip0.2". The slot stays the same until the pad disconnects.
+fn gip_label(name: &str, gip: &str) -> String {
+    match gip.split_once('.') {
+        Some((_, client)) if !client.is_empty() => format!("{} #{}", name, client),
+        _ => name.to_string(),
+    }
+}
 
 pub fn parse_xbox_controller_data(
-    device_info: &DeviceInfo,
-    _hidapi: &HidApi,
-) -> Result<Controller> {
-    let capacity: u8 = match get_bluetooth_address(device_info) {
+    controller: &mut Controller,
+    device: &HidDevice,
+    transport: &dyn HidTransport,
+) -> Result<()> {
+    let battery = match read_battery_report(device, transport) {
+        Ok(Some(battery)) => {
+            if let Ok(mut reports) = BATTERY_REPORTS
+                .get_or_init(|| Mutex::new(HashMap::new()))
+                .lock()
+            {
+                reports.insert(controller.id(), battery);
+            }
+            Some(battery)
+        }
+        Ok(None) => cached_battery_report(&controller.id()),
+        Err(err) => {
+            error!("Error reading from device: {}", err);
+            cached_battery_report(&controller.id())
+        }
+    };
+    if let Some(battery) = battery {
+        (controller.capacity, controller.status) = decode_battery(battery);
+        return Ok(());
+    }
+
+    // Not a single battery report yet, ask BlueZ instead
+    debug!("No battery report from {}, asking BlueZ", controller.name);
+    controller.capacity = match get_bluetooth_address(device) {
         Ok(address) => match get_battery_percentage(address) {
             Ok(percentage) => percentage,
             Err(err) => {
@@ -92,67 +181,119 @@ pub fn parse_xbox_controller_data(
             0
         }
     };
-    let name = get_xbox_controller_name(device_info.product_id());
+    Ok(())
+}
 
-    let controller = Controller::from_hidapi(device_info, name, capacity, Status::Unknown);
-    Ok(controller)
+fn read_battery_report(device: &HidDevice, transport: &dyn HidTransport) -> Result<Option<u8>> {
+    let mut connection = transport.open(device)?;
+    let mut buf = [0u8; MAX_REPORT_SIZE];
+    let deadline = Instant::now() + BATTERY_REPORT_TIMEOUT;
+    while Instant::now() < deadline {
+        let len = connection.read_timeout(&mut buf, READ_TIMEOUT_MS)?;
+        // Nothing arrived, or another input report
+        let Ok(report) = InputReport::new(&buf, len) else {
+            continue;
+        };
+        if report.id() == BATTERY_REPORT_ID {
+            return Ok(Some(report.u8(BATTERY_STATUS)?));
+        }
+    }
+    Ok(None)
 }
 
-fn get_battery_percentage_for_gip(gip: &str) -> u8 {
-    // Normalize the `gip` to match UPower paths
-    let normalized_gip = format!("battery_{}", gip.replace(".", "x"));
+fn cached_battery_report(id: &str) -> Option<u8> {
+    BATTERY_REPORTS.get()?.lock().ok()?.get(id).copied()
+}
 
-    // Create a DBus connection
-    let connection = match Connection::new_system() {
-        Ok(conn) => conn,
-        Err(err) => {
-            log::error!("Failed to connect to DBus: {}", err);
-            return 10;
-        }
+/// Battery percentage and charging state from the battery status byte. The controller only
+/// reports four levels, mapped the way the GameInput API does.
+fn decode_battery(battery: u8) -> (u8, Status) {
+    let capacity = match battery & BATTERY_LEVEL {
+        0 => 10,
+        1 => 40,
+        2 => 70,
+        _ => 100,
+    };
+    let status = if battery & BATTERY_CHARGING != 0 {
+        Status::Charging
+    } else {
+        Status::Discharging
     };
+    (capacity, status)
+}
 
-    // Proxy to UPower
-    let proxy = connection.with_proxy(
-        "org.freedesktop.UPower",
-        "/org/freedesktop/UPower",
-        Duration::from_millis(5000),
-    );
-
-    // Enumerate devices
-    let (devices,): (Vec<Path>,) = match proxy.method_call(
-        "org.freedesktop.UPower",
-        "EnumerateDevices",
-        (),
-    ) {
-        Ok(devices) => devices,
-        Err(err) => {
-            log::error!("Failed to enumerate devices: {}", err);
-            return 20;
-        }
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_battery, gip_label, parse_xbox_controller_data, read_battery_report, XboxDriver,
     };
+    use crate::api::driver::ControllerDriver;
+    use crate::api::report::ReportError;
+    use crate::api::transport::fixture::Fixture;
+    use crate::api::transport::replay::ReplayTransport;
+    use crate::controller::Status;
 
-    // Iterate through devices to find the matching `gip`
-    for device_path in devices {
-        let device_path_str = device_path.to_string();
-        if let Some(upower_gip) = device_path_str.split('/').find(|&s| s.starts_with("battery_")) {
-            if upower_gip == normalized_gip {
-                // Found matching `gip`, query percentage
-                let device_proxy = connection.with_proxy(
-                    "org.freedesktop.UPower",
-                    device_path.clone(),
-                                                         Duration::from_millis(5000),
-                );
-
-                return match device_proxy.get::<f64>("org.freedesktop.UPower.Device", "Percentage") {
-                    Ok(percentage) => percentage as u8,
-                    Err(err) => {
-                        log::error!("Failed to get battery percentage for {}: {}", device_path_str, err);
-                        0
-                    }
-                };
-            }
-        }
+    #[test]
+    fn test_battery_report_layout() {
+        // An input report, then the battery report 0x04 with its status byte
+        let mut fixture = Fixture::named("xbox_series_bt");
+        let device = fixture.device.clone();
+        let transport = ReplayTransport::new(vec![fixture.clone()]);
+        assert_eq!(
+            read_battery_report(&device, &transport).unwrap(),
+            Some(0x06)
+        );
+
+        // The battery report without its status byte
+        fixture.reports[1].data.truncate(1);
+        let transport = ReplayTransport::new(vec![fixture]);
+        let err = read_battery_report(&device, &transport).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReportError>(),
+            Some(ReportError::Short {
+                report_id: 0x04,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_forgotten_battery_report() {
+        let mut fixture = Fixture::named("xbox_series_bt");
+        fixture.device.path = "/dev/replay/xbox_series_bt_forget".to_string();
+        let device = fixture.device.clone();
+        let read = |fixture: &Fixture| {
+            let transport = ReplayTransport::new(vec![fixture.clone()]);
+            let mut controller = XboxDriver.probe_hid(&device);
+            parse_xbox_controller_data(&mut controller, &device, &transport).unwrap();
+            controller.capacity
+        };
+        assert_eq!(read(&fixture), 70);
+
+        // Polls without a battery report keep the last one, until the pad is gone and the next
+        // pad on its node starts afresh
+        fixture.reports.truncate(1);
+        assert_eq!(read(&fixture), 70);
+        XboxDriver.forget(&device.path);
+        assert_eq!(read(&fixture), 0);
+    }
+
+    #[test]
+    fn test_decode_battery() {
+        assert_eq!(decode_battery(0x04), (10, Status::Discharging));
+        assert_eq!(decode_battery(0x05), (40, Status::Discharging));
+        assert_eq!(decode_battery(0x0a), (70, Status::Discharging));
+        assert_eq!(decode_battery(0x07), (100, Status::Discharging));
+        // The charging bit, whatever batteries are in
+        assert_eq!(decode_battery(0x02), (70, Status::Discharging));
+        assert_eq!(decode_battery(0x1a), (70, Status::Charging));
+        assert_eq!(decode_battery(0x13), (100, Status::Charging));
     }
 
-    0 // Return 0 if no match is found
+    #[test]
+    fn test_gip_label() {
+        assert_eq!(gip_label("Xbox Series X/S", "gip0.2"), "Xbox Series X/S #2");
+        assert_eq!(gip_label("Xbox Elite 2", "gip1.3"), "Xbox Elite 2 #3");
+        assert_eq!(gip_label("Xbox One S", "input12"), "Xbox One S");
+    }
 }
@@ -1,11 +1,12 @@
 use std::ffi::OsStr;
 
-use hidapi::DeviceInfo;
 use log::error;
 use serde::{Deserialize, Serialize};
 use udev::Device;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+use crate::api::transport::HidDevice;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Status {
     Charging,
@@ -13,7 +14,7 @@ pub enum Status {
     Unknown,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Controller {
     pub name: String,
@@ -26,18 +27,19 @@ pub struct Controller {
     pub serial_number: Option<String>,
     #[serde(skip_serializing)]
     pub device_path: Option<String>,
-    pub gip: String,  // New field for 'gip'
+    pub gip: String,
+    // What is plugged into the controller, e.g. the Nunchuk of a Wii Remote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+    // The pads that make up this controller when they are used together, e.g. a pair of
+    // Joy-Cons, each with its own battery
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<Controller>,
 }
 
 impl Controller {
-    pub fn from_udev(
-        device: &Device,
-        mut name: &str,
-        mut capacity: u8,
-        mut status: Status,
-        mut bluetooth: bool,
-    ) -> Self {
-        let serial_number = device
+    pub fn from_udev(device: &Device, name: &str, capacity: u8, status: Status) -> Self {
+        let mut serial_number = device
             .property_value("ID_SERIAL_SHORT")
             .map(|serial_number| serial_number.to_string_lossy().to_string());
         let device_path = if device.devpath().is_empty() {
@@ -46,32 +48,43 @@ impl Controller {
             Some(device.devpath().to_string_lossy().to_string())
         };
 
-        let vendor_id: u16 = device
+        let mut vendor_id: u16 = device
             .property_value("ID_VENDOR_ID")
             .map(hex_os_str_to_u16)
             .unwrap_or(0);
-        let product_id: u16 = device
+        let mut product_id: u16 = device
             .property_value("ID_MODEL_ID")
             .map(hex_os_str_to_u16)
             .unwrap_or(0);
-            let gip = device_path.as_ref().map(|path| {
+        let gip = device_path
+            .as_ref()
+            .map(|path| {
                 // Split the path into parts
                 let parts: Vec<&str> = path.split('/').collect();
 
                 // Look for a part that starts with "gip" and contains a '.'
-                if let Some(gip_part) = parts.iter().find(|&&part| part.starts_with("gip") && part.contains('.')) {
+                if let Some(gip_part) = parts
+                    .iter()
+                    .find(|&&part| part.starts_with("gip") && part.contains('.'))
+                {
                     gip_part.to_string()
                 } else {
                     // Fallback to the last part of the path
                     parts.last().unwrap_or(&"").to_string()
                 }
-            }).unwrap_or_else(|| "NA".to_string());
-            // If gip is None, set it to "NA"
-            // Set bluetooth to true if gip is not "NA"
-            if gip.starts_with("gip" ){
-                bluetooth = true }
-                else {bluetooth=false};
-
+            })
+            // If there is no device path, set it to "NA"
+            .unwrap_or_else(|| "NA".to_string());
+        // Controllers on the GIP bus are connected wirelessly through the Xbox Wireless Adapter
+        let bluetooth = gip.starts_with("gip");
+        // The USB properties describe the adapter, every pad behind it would look the same.
+        // xone gives the input device of each pad the IDs from the pad's own descriptor.
+        if bluetooth {
+            if let Some(identity) = gip_input_identity(device) {
+                (vendor_id, product_id) = (identity.vendor_id, identity.product_id);
+                serial_number = identity.serial_number;
+            }
+        }
 
         Self {
             name: name.to_string(),
@@ -82,32 +95,61 @@ impl Controller {
             bluetooth,
             serial_number,
             device_path,
-             gip: gip.to_string(),
+            gip,
+            extension: None,
+            parts: Vec::new(),
         }
     }
 
-    pub fn from_hidapi(device_info: &DeviceInfo, name: &str, capacity: u8, status: Status) -> Self {
-        let serial_number = device_info
-            .serial_number()
-            .filter(|serial_number| !serial_number.is_empty())
-            .map(|serial_number| serial_number.to_string());
-        let bluetooth = device_info.interface_number() == -1;
-        let device_path_bytes = device_info.path().to_bytes();
-        let device_path = if device_path_bytes.is_empty() {
+    pub fn from_hidapi(device: &HidDevice, name: &str, capacity: u8, status: Status) -> Self {
+        let serial_number = device
+            .serial_number
+            .clone()
+            .filter(|serial_number| !serial_number.is_empty());
+        let bluetooth = device.interface_number == -1;
+        let device_path = if device.path.is_empty() {
             None
         } else {
-            Some(String::from_utf8_lossy(device_path_bytes).to_string())
+            Some(device.path.clone())
         };
         let gip = "NA";
         Self {
             name: name.to_string(),
-            product_id: device_info.product_id(),
-            vendor_id: device_info.vendor_id(),
+            product_id: device.product_id,
+            vendor_id: device.vendor_id,
             capacity,
             status,
             bluetooth,
             serial_number,
-            device_path, gip: gip.to_string(),
+            device_path,
+            gip: gip.to_string(),
+            extension: None,
+            parts: Vec::new(),
+        }
+    }
+
+    /// One controller made of several pads. It runs low as soon as one of them does and is
+    /// only charging when none of them is discharging. It takes the ID of the first pad.
+    pub fn from_parts(name: &str, product_id: u16, parts: Vec<Controller>) -> Self {
+        let status = if parts.iter().any(Controller::is_discharging) {
+            Status::Discharging
+        } else if parts.iter().any(|part| part.status == Status::Charging) {
+            Status::Charging
+        } else {
+            Status::Unknown
+        };
+        Self {
+            name: name.to_string(),
+            product_id,
+            vendor_id: parts.first().map_or(0, |part| part.vendor_id),
+            capacity: parts.iter().map(|part| part.capacity).min().unwrap_or(0),
+            status,
+            bluetooth: parts.iter().any(|part| part.bluetooth),
+            serial_number: None,
+            device_path: parts.first().and_then(|part| part.device_path.clone()),
+            gip: "NA".to_string(),
+            extension: None,
+            parts,
         }
     }
 
@@ -128,6 +170,33 @@ impl Controller {
     }
 }
 
+struct GipIdentity {
+    vendor_id: u16,
+    product_id: u16,
+    serial_number: Option<String>,
+}
+
+/// IDs of a pad on xone's GIP bus from its input device (`id/vendor`, `id/product` and
+/// `uniq`), given the input device itself or one of its event/js nodes
+fn gip_input_identity(device: &Device) -> Option<GipIdentity> {
+    let input = if device.attribute_value("id/vendor").is_some() {
+        device.clone()
+    } else {
+        device.parent_with_subsystem("input").ok().flatten()?
+    };
+    let vendor_id = hex_os_str_to_u16(input.attribute_value("id/vendor")?);
+    let product_id = hex_os_str_to_u16(input.attribute_value("id/product")?);
+    let serial_number = input
+        .attribute_value("uniq")
+        .map(|uniq| uniq.to_string_lossy().trim().to_string())
+        .filter(|uniq| !uniq.is_empty());
+    Some(GipIdentity {
+        vendor_id,
+        product_id,
+        serial_number,
+    })
+}
+
 fn hex_os_str_to_u16(hex_os_str: &OsStr) -> u16 {
     let hex_str = hex_os_str.to_string_lossy();
 
@@ -156,6 +225,9 @@ mod tests {
             bluetooth: false,
             serial_number: None,
             device_path: None,
+            gip: "NA".to_string(),
+            extension: None,
+            parts: Vec::new(),
         };
         assert!(controller.is_discharging());
 
@@ -178,11 +250,14 @@ mod tests {
             bluetooth: false,
             serial_number: Some("1234567890".to_string()),
             device_path: Some("/dev/input/js0".to_string()),
+            gip: "NA".to_string(),
+            extension: None,
+            parts: Vec::new(),
         };
         let serialized = serde_json::to_string(&controller).unwrap();
         assert_eq!(
             serialized,
-            r#"{"name":"Test Controller","productId":1118,"vendorId":746,"capacity":0,"status":"discharging","bluetooth":false}"#
+            r#"{"name":"Test Controller","productId":1118,"vendorId":746,"capacity":0,"status":"discharging","bluetooth":false,"gip":"NA"}"#
         );
     }
 
@@ -197,6 +272,9 @@ mod tests {
             bluetooth: false,
             device_path: Some("/dev/input/js0".to_string()),
             serial_number: Some("1234567890".to_string()),
+            gip: "NA".to_string(),
+            extension: None,
+            parts: Vec::new(),
         };
 
         assert_eq!(controller.id(), "/dev/input/js0");
@@ -206,6 +284,48 @@ mod tests {
         assert_eq!(controller.id(), "746:1118");
     }
 
+    #[test]
+    fn test_from_parts() {
+        let part = |device_path: &str, capacity: u8, status: Status| Controller {
+            name: "Joy-Con".to_string(),
+            product_id: 0x2006,
+            vendor_id: 0x057e,
+            capacity,
+            status,
+            bluetooth: true,
+            serial_number: None,
+            device_path: Some(device_path.to_string()),
+            gip: "NA".to_string(),
+            extension: None,
+            parts: Vec::new(),
+        };
+
+        let controller = Controller::from_parts(
+            "Joy-Con (L/R)",
+            0x2008,
+            vec![
+                part("/dev/hidraw1", 75, Status::Charging),
+                part("/dev/hidraw2", 25, Status::Discharging),
+            ],
+        );
+        assert_eq!(controller.id(), "/dev/hidraw1");
+        assert_eq!(controller.vendor_id, 0x057e);
+        assert_eq!(controller.capacity, 25);
+        assert!(controller.is_discharging());
+
+        let controller = Controller::from_parts(
+            "Joy-Con (L/R)",
+            0x2008,
+            vec![
+                part("/dev/hidraw1", 100, Status::Charging),
+                part("/dev/hidraw2", 50, Status::Unknown),
+            ],
+        );
+        assert_eq!(controller.status, Status::Charging);
+        let serialized = serde_json::to_value(&controller).unwrap();
+        assert_eq!(serialized["parts"][1]["capacity"], 50);
+    }
+
     #[test]
     fn test_hex_os_str_to_u16() {
         let os_str = OsStr::new("045e");
@@ -1,31 +0,0 @@
-#include <stdint.h>
-
-struct dualsense_touch_point {
-    uint8_t contact;
-    uint8_t x_lo;
-    uint8_t x_hi:4, y_lo:4;
-    uint8_t y_hi;
-} __attribute__((packed));
-
-/* Main DualSense input report excluding any BT/USB specific headers. */
-struct dualsense_input_report {
-    uint8_t x, y;
-    uint8_t rx, ry;
-    uint8_t z, rz;
-    uint8_t seq_number;
-    uint8_t buttons[4];
-    uint8_t reserved[4];
-
-    /* Motion sensors */
-    uint16_t gyro[3]; /* x, y, z */
-    uint16_t accel[3]; /* x, y, z */
-    uint32_t sensor_timestamp;
-    uint8_t reserved2;
-
-    /* Touchpad */
-    struct dualsense_touch_point points[2];
-
-    uint8_t reserved3[12];
-    uint8_t status;
-    uint8_t reserved4[10];
-} __attribute__((packed));
\ No newline at end of file
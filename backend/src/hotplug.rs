@@ -0,0 +1,234 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use log::{debug, error, info};
+use serde::Serialize;
+use tokio::{io::unix::AsyncFd, sync::broadcast};
+use udev::{EventType, MonitorBuilder};
+
+use crate::{api, controller::Controller};
+
+// A single controller connecting produces a burst of udev events (hid, hidraw, input, event, js),
+// wait for it to settle before rescanning
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+const EVENT_CHANNEL_CAPACITY: usize = 16;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum InventoryEvent {
+    Connected { controller: Controller },
+    Disconnected { controller: Controller },
+}
+
+/// The set of currently connected controllers, kept up to date by the udev monitor.
+pub struct Inventory {
+    controllers: Mutex<Vec<Controller>>,
+    // Serializes rescans and battery refreshes so they don't overwrite each other
+    scan_lock: tokio::sync::Mutex<()>,
+    events: broadcast::Sender<InventoryEvent>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            controllers: Mutex::new(Vec::new()),
+            scan_lock: tokio::sync::Mutex::new(()),
+            events,
+        }
+    }
+
+    pub fn controllers(&self) -> Vec<Controller> {
+        match self.controllers.lock() {
+            Ok(controllers) => controllers.clone(),
+            Err(err) => {
+                error!("Failed to get lock for controllers: {}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<InventoryEvent> {
+        self.events.subscribe()
+    }
+
+    /// Enumerate every device and replace the inventory with the result
+    pub async fn rescan(&self) -> Result<Vec<Controller>> {
+        let _guard = self.scan_lock.lock().await;
+        let controllers = api::controllers_async().await?;
+        self.replace(controllers.clone());
+        Ok(controllers)
+    }
+
+    /// Re-read the battery of the controllers already in the inventory
+    pub async fn refresh(&self) -> Result<Vec<Controller>> {
+        let _guard = self.scan_lock.lock().await;
+        let controllers = api::refresh_async(self.controllers()).await?;
+        self.replace(controllers.clone());
+        Ok(controllers)
+    }
+
+    fn replace(&self, controllers: Vec<Controller>) {
+        let previous = match self.controllers.lock() {
+            Ok(mut current) => std::mem::replace(&mut *current, controllers.clone()),
+            Err(err) => {
+                error!("Failed to get lock for controllers: {}", err);
+                return;
+            }
+        };
+
+        let previous_ids: HashSet<String> = previous.iter().map(Controller::id).collect();
+        let current_ids: HashSet<String> = controllers.iter().map(Controller::id).collect();
+
+        for controller in previous {
+            if !current_ids.contains(&controller.id()) {
+                info!("Controller disconnected: {}", controller.name);
+                api::forget(&controller);
+                // Sending only fails when nobody is subscribed, which is fine
+                let _ = self
+                    .events
+                    .send(InventoryEvent::Disconnected { controller });
+            }
+        }
+        for controller in controllers {
+            if !previous_ids.contains(&controller.id()) {
+                info!("Controller connected: {}", controller.name);
+                let _ = self.events.send(InventoryEvent::Connected { controller });
+            }
+        }
+    }
+}
+
+/// Populate the inventory and keep it in sync with udev add/remove events.
+///
+/// The udev monitor socket can't be moved between threads, so it lives on a dedicated thread
+/// with its own single threaded runtime.
+pub fn spawn_monitor(inventory: Arc<Inventory>) {
+    let result = std::thread::Builder::new()
+        .name("hotplug".to_string())
+        .spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(err) => {
+                    error!("Failed to start hotplug runtime: {}", err);
+                    return;
+                }
+            };
+            if let Err(err) = runtime.block_on(monitor(inventory)) {
+                error!("Hotplug monitor stopped: {}", err);
+            }
+        });
+    if let Err(err) = result {
+        error!("Failed to spawn hotplug thread: {}", err);
+    }
+}
+
+async fn monitor(inventory: Arc<Inventory>) -> Result<()> {
+    let socket = MonitorBuilder::new()?
+        .match_subsystem("hidraw")?
+        .match_subsystem("input")?
+        .listen()?;
+    let socket = AsyncFd::new(socket)?;
+
+    // Start listening before the initial scan so no device falls in between
+    inventory.rescan().await?;
+    info!("Hotplug monitor started");
+
+    loop {
+        let mut guard = socket.readable().await?;
+        let changed = handle_device_events(guard.get_inner().iter());
+        guard.clear_ready();
+
+        if !changed {
+            continue;
+        }
+
+        // Swallow the rest of the burst, a single rescan covers all of it
+        tokio::time::sleep(SETTLE_DELAY).await;
+        handle_device_events(socket.get_ref().iter());
+
+        debug!("Device added or removed, rescanning controllers...");
+        if let Err(err) = inventory.rescan().await {
+            error!("Error rescanning controllers: {}", err);
+        }
+    }
+}
+
+/// Whether devices were added or removed. The hidraw nodes that were removed are forgotten
+/// right away: when a pad leaves and another one takes its node within the same burst, the
+/// rescan sees the node throughout.
+fn handle_device_events(events: impl Iterator<Item = udev::Event>) -> bool {
+    let mut changed = false;
+    for event in events {
+        if matches!(event.event_type(), EventType::Add | EventType::Remove) {
+            debug!("udev {} {:?}", event.event_type(), event.devpath());
+            changed = true;
+        }
+        if event.event_type() == EventType::Remove {
+            if let Some(devnode) = event.devnode().and_then(|devnode| devnode.to_str()) {
+                api::forget_device(devnode);
+            }
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Inventory, InventoryEvent};
+    use crate::controller::{Controller, Status};
+
+    fn controller(device_path: &str) -> Controller {
+        Controller {
+            name: "Test Controller".to_string(),
+            product_id: 0x0ce6,
+            vendor_id: 0x054c,
+            capacity: 50,
+            status: Status::Discharging,
+            bluetooth: true,
+            serial_number: None,
+            device_path: Some(device_path.to_string()),
+            gip: "NA".to_string(),
+            extension: None,
+            parts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_replace_emits_events() {
+        let inventory = Inventory::new();
+        let mut events = inventory.subscribe();
+
+        inventory.replace(vec![controller("/dev/hidraw1"), controller("/dev/hidraw2")]);
+        for expected in ["/dev/hidraw1", "/dev/hidraw2"] {
+            match events.try_recv().unwrap() {
+                InventoryEvent::Connected { controller } => assert_eq!(controller.id(), expected),
+                event => panic!("Unexpected event {:?}", event),
+            }
+        }
+
+        // Battery changes alone don't produce events
+        let mut updated = controller("/dev/hidraw1");
+        updated.capacity = 40;
+        inventory.replace(vec![updated, controller("/dev/hidraw2")]);
+        assert!(events.try_recv().is_err());
+        assert_eq!(inventory.controllers()[0].capacity, 40);
+
+        inventory.replace(vec![controller("/dev/hidraw2")]);
+        match events.try_recv().unwrap() {
+            InventoryEvent::Disconnected { controller } => {
+                assert_eq!(controller.id(), "/dev/hidraw1")
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -1,36 +1,54 @@
 mod api;
 mod controller;
+mod hotplug;
+mod poller;
 mod settings;
 mod ws;
 
-use std::{fs::File, net::SocketAddr, sync::Arc};
+use std::{fs::File, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
+use api::capture::DeviceSelector;
+use api::transport::{HidApiTransport, HidTransport};
 use axum::{
+    extract::{Query, State},
     http::{HeaderValue, Method, StatusCode},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
 use controller::Controller;
-use log::info;
+use log::{error, info};
+use serde::Deserialize;
 use simplelog::{
     ColorChoice, CombinedLogger, Config, LevelFilter, TermLogger, TerminalMode, WriteLogger,
 };
 
 use tower_http::cors::{Any, CorsLayer};
 
+use crate::hotplug::Inventory;
+use crate::poller::Poller;
 use crate::settings::SettingsService;
 
 const PORT: u16 = 33220;
 
+const DEFAULT_CAPTURE_SECONDS: u64 = 5;
+
 pub struct AppState {
     settings_service: SettingsService,
+    inventory: Arc<Inventory>,
+    poller: Poller,
+    capture_directory: PathBuf,
 }
 
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = std::env::args().collect();
 
+    if args.get(1).map(String::as_str) == Some("capture") {
+        capture_command(&args[2..]);
+        return;
+    }
+
     if args.len() != 3 {
         panic!("Error: Expected 2 arguments, but got {}", args.len() - 1);
     }
@@ -42,6 +60,17 @@ async fn main() {
         Err(_) => String::from("/tmp/controller-tools.json"),
     };
     let settings_service = SettingsService::new(&settings_location).await.unwrap();
+    // The plugin passes the path of its settings file, keep captures next to it
+    let settings_path = PathBuf::from(&settings_directory);
+    let settings_directory = match settings_path.parent() {
+        _ if settings_path.is_dir() => Some(settings_path.clone()),
+        Some(parent) if parent.is_dir() => Some(parent.to_path_buf()),
+        _ => None,
+    };
+    let capture_directory = match &settings_directory {
+        Some(directory) => directory.join("captures"),
+        None => PathBuf::from("/tmp/controller-tools-captures"),
+    };
 
     let level_filter = match settings_service.get_settings().await.debug {
         true => LevelFilter::Debug,
@@ -62,33 +91,148 @@ async fn main() {
     ])
     .unwrap();
 
-    let app_state = Arc::new(AppState { settings_service });
+    let devices_override =
+        settings_directory.map(|directory| directory.join(api::database::OVERRIDE_FILE));
+    api::database::init(devices_override.as_deref());
+
+    let inventory = Arc::new(Inventory::new());
+    hotplug::spawn_monitor(inventory.clone());
+
+    let app_state = Arc::new(AppState {
+        settings_service,
+        inventory,
+        poller: Poller::new(),
+        capture_directory,
+    });
+    poller::spawn(app_state.clone());
+
+    let wake_state = app_state.clone();
+    api::bluetooth::spawn_battery_watcher(move || wake_state.poller.wake());
+    let wake_state = app_state.clone();
+    api::upower::spawn_watcher(move || wake_state.poller.wake());
+
+    let app = app(app_state);
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], PORT));
+    info!("Logging level: {:?}", level_filter);
+    info!("Listening on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
 
-    let app = Router::new()
+fn app(state: Arc<AppState>) -> Router {
+    Router::new()
         .route("/controllers", get(controllers_json))
         .route("/ws", get(ws::ws_handler))
-        .with_state(app_state)
+        .route("/events", get(ws::events_handler))
+        .route("/input", get(ws::input_handler))
+        .route("/capture", post(capture_json))
+        .route("/diagnostics", get(diagnostics_json))
+        .route("/nintendo/info", get(nintendo_info_json))
+        .with_state(state)
         .layer(
             CorsLayer::new()
                 .allow_origin("https://steamloopback.host".parse::<HeaderValue>().unwrap())
                 .allow_headers(Any)
                 .allow_methods([Method::GET, Method::POST]),
-        );
-
-    let addr = SocketAddr::from(([127, 0, 0, 1], PORT));
-    info!("Logging level: {:?}", level_filter);
-    info!("Listening on {}", addr);
-    
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+        )
 }
 
-async fn controllers_json() -> Result<Json<Vec<Controller>>, AppError> {
-    // Spawn a tokio blocking task because `get_controllers()` is a blocking API
-    let controllers = tokio::task::spawn_blocking(api::controllers).await??;
+async fn controllers_json(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Controller>>, AppError> {
+    // The hotplug monitor keeps track of which controllers are connected, only their battery
+    // needs to be read again
+    let controllers = state.inventory.refresh().await?;
     Ok(Json(controllers))
 }
 
+/// Per-controller error counters, e.g. Bluetooth reports that failed their CRC check
+async fn diagnostics_json(
+    State(state): State<Arc<AppState>>,
+) -> Json<Vec<api::diagnostics::ControllerDiagnostics>> {
+    Json(api::diagnostics::collect(&state.inventory.controllers()))
+}
+
+#[derive(Deserialize)]
+struct CaptureParams {
+    // hidraw path or vendor:product, e.g. "054c:0ce6"
+    device: String,
+    seconds: Option<u64>,
+}
+
+/// Record the raw reports of a controller for bug reports, responds with the capture file
+async fn capture_json(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CaptureParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let selector = DeviceSelector::parse(&params.device)?;
+    let duration = Duration::from_secs(params.seconds.unwrap_or(DEFAULT_CAPTURE_SECONDS));
+    let path = api::capture_async(selector, duration, state.capture_directory.clone()).await?;
+    Ok(Json(serde_json::json!({ "file": path })))
+}
+
+#[derive(Deserialize)]
+struct DeviceParams {
+    // hidraw path or vendor:product, e.g. "057e:2009"
+    device: String,
+}
+
+/// Firmware version, controller type, MAC address and colors of a Switch controller
+async fn nintendo_info_json(
+    Query(params): Query<DeviceParams>,
+) -> Result<Json<api::nintendo::protocol::DeviceInfo>, AppError> {
+    let selector = DeviceSelector::parse(&params.device)?;
+    Ok(Json(api::nintendo_info_async(selector).await?))
+}
+
+/// `controller-tools capture [<device> [seconds] [directory]]`, lists the HID devices when
+/// no device is given
+fn capture_command(args: &[String]) {
+    TermLogger::init(
+        LevelFilter::Info,
+        Config::default(),
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    )
+    .unwrap();
+
+    let result = match args {
+        [] => HidApiTransport::new().map(|transport| {
+            for device in transport.devices() {
+                println!(
+                    "{} {:04x}:{:04x} {}",
+                    device.path,
+                    device.vendor_id,
+                    device.product_id,
+                    device.product_string.unwrap_or_default()
+                );
+            }
+        }),
+        [device, rest @ ..] => (|| {
+            let selector = DeviceSelector::parse(device)?;
+            let seconds = match rest.first() {
+                Some(seconds) => seconds.parse()?,
+                None => DEFAULT_CAPTURE_SECONDS,
+            };
+            let directory = rest.get(1).map(PathBuf::from).unwrap_or_default();
+            let path = api::capture::capture(
+                &HidApiTransport::new()?,
+                &selector,
+                Duration::from_secs(seconds),
+                &directory,
+            )?;
+            println!("{}", path.display());
+            Ok(())
+        })(),
+    };
+    if let Err(err) = result {
+        error!("Capture failed: {}", err);
+        std::process::exit(1);
+    }
+}
+
 // Make our own error that wraps `anyhow::Error`
 struct AppError(anyhow::Error);
 
@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, error, info};
+use tokio::sync::{broadcast, Notify};
+
+use crate::{controller::Controller, AppState};
+
+// How often to check the battery level
+#[cfg(not(debug_assertions))]
+const BATTERY_CHECK_INTERVAL: Duration = std::time::Duration::from_secs(60);
+#[cfg(debug_assertions)]
+const BATTERY_CHECK_INTERVAL: Duration = std::time::Duration::from_secs(10);
+
+// How often to send a notification to the client
+#[cfg(not(debug_assertions))]
+const BATTERY_ALERT_INTERVAL: Duration = std::time::Duration::from_secs(60 * 60);
+#[cfg(debug_assertions)]
+const BATTERY_ALERT_INTERVAL: Duration = std::time::Duration::from_secs(60);
+
+const LOW_BATTERY_THRESHOLD: u8 = 20;
+
+const UPDATE_CHANNEL_CAPACITY: usize = 8;
+
+/// Result of one battery check, shared with every websocket client
+#[derive(Clone, Debug)]
+pub struct PollUpdate {
+    pub controllers: Vec<Controller>,
+    pub alerts: Vec<String>,
+}
+
+/// Single battery poller owned by the daemon. Websocket clients subscribe to its updates
+/// instead of reading the controllers themselves.
+pub struct Poller {
+    updates: broadcast::Sender<PollUpdate>,
+    wake: Notify,
+}
+
+impl Poller {
+    pub fn new() -> Self {
+        let (updates, _) = broadcast::channel(UPDATE_CHANNEL_CAPACITY);
+        Self {
+            updates,
+            wake: Notify::new(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PollUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Check the controllers now instead of waiting for the next interval, e.g. because a
+    /// battery level was pushed to us
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+pub fn spawn(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut alert_tracker = AlertTracker::default();
+
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(BATTERY_CHECK_INTERVAL) => {}
+                _ = state.poller.wake.notified() => {}
+            }
+
+            debug!("Checking controllers...");
+            // Refreshing updates the controllers cached in the inventory
+            let controllers = match state.inventory.refresh().await {
+                Ok(controllers) => controllers,
+                Err(e) => {
+                    error!("Error getting controllers: {}", e);
+                    continue;
+                }
+            };
+
+            let settings = state.settings_service.get_settings().await;
+            // Don't consume alerts when there is nobody to deliver them to
+            let alerts = if !settings.notifications {
+                debug!("Notifications disabled, skipping notification check...");
+                Vec::new()
+            } else if state.poller.updates.receiver_count() == 0 {
+                debug!("No clients connected, skipping notification check...");
+                Vec::new()
+            } else {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                alert_tracker.check(&controllers, now)
+            };
+
+            // Sending only fails when nobody is subscribed
+            let _ = state.poller.updates.send(PollUpdate {
+                controllers,
+                alerts,
+            });
+        }
+    });
+}
+
+/// Remembers when each controller was last reported as low on battery
+#[derive(Default)]
+struct AlertTracker {
+    last_alerts: HashMap<String, u64>,
+}
+
+impl AlertTracker {
+    fn check(&mut self, controllers: &[Controller], now: u64) -> Vec<String> {
+        let mut alerts = Vec::new();
+
+        for controller in controllers {
+            let low_battery =
+                controller.capacity < LOW_BATTERY_THRESHOLD && controller.is_discharging();
+            debug!(
+                "Controller {} is low battery: {}",
+                controller.name, low_battery
+            );
+            if !low_battery {
+                continue;
+            }
+
+            let first_alert = !self.last_alerts.contains_key(&controller.id());
+            let last_alert = self.last_alerts.entry(controller.id()).or_insert(now);
+
+            let last_alert_secs_ago = now - *last_alert;
+            debug!(
+                "Last alert was {} seconds ago for controller {}",
+                last_alert_secs_ago, controller.name
+            );
+
+            if first_alert || last_alert_secs_ago >= BATTERY_ALERT_INTERVAL.as_secs() {
+                let message = format!(
+                    "{} is low on battery ({}%)",
+                    controller.name, controller.capacity
+                );
+                info!("Sending notification: {}", message);
+                alerts.push(message);
+
+                // Update last alert timestamp
+                *last_alert = now;
+            }
+        }
+
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlertTracker, BATTERY_ALERT_INTERVAL};
+    use crate::controller::{Controller, Status};
+
+    #[test]
+    fn test_alert_tracker() {
+        let mut controller = Controller {
+            name: "Test Controller".to_string(),
+            product_id: 0x0ce6,
+            vendor_id: 0x054c,
+            capacity: 15,
+            status: Status::Discharging,
+            bluetooth: true,
+            serial_number: None,
+            device_path: Some("/dev/hidraw1".to_string()),
+            gip: "NA".to_string(),
+            extension: None,
+            parts: Vec::new(),
+        };
+        let mut tracker = AlertTracker::default();
+
+        let alerts = tracker.check(std::slice::from_ref(&controller), 1000);
+        assert_eq!(alerts, vec!["Test Controller is low on battery (15%)"]);
+
+        // Not again until the alert interval has passed
+        assert!(tracker
+            .check(std::slice::from_ref(&controller), 1001)
+            .is_empty());
+        let later = 1000 + BATTERY_ALERT_INTERVAL.as_secs();
+        assert_eq!(
+            tracker
+                .check(std::slice::from_ref(&controller), later)
+                .len(),
+            1
+        );
+
+        controller.status = Status::Charging;
+        assert!(tracker
+            .check(std::slice::from_ref(&controller), later * 2)
+            .is_empty());
+    }
+}
@@ -80,13 +80,13 @@ mod test {
             .duration_since(SystemTime::UNIX_EPOCH)?
             .as_millis();
         let file_path = format!("/tmp/test_settings_{}.json", timestamp);
+        // The settings file is owned by the Python side of the plugin, fall back to the
+        // defaults when it hasn't been written yet
         let settings_service = SettingsService::new(&file_path).await?;
-        // Verify that the config file was created
-        assert!(tokio::fs::metadata(&file_path).await.is_ok());
+        let settings = settings_service.get_settings().await;
+        assert!(settings.notifications);
 
-        let mut settings = settings_service.get_settings().await;
-        assert_eq!(settings.notifications, true);
-        settings.notifications = false;
+        tokio::fs::write(&file_path, r#"{"notifications": false, "debug": false}"#).await?;
 
         // Read it again
         let settings_service = SettingsService::new(&file_path).await?;
@@ -1,35 +1,26 @@
-use std::{
-    collections::HashMap,
-    ops::ControlFlow,
-    sync::Arc,
-    time::{Duration, SystemTime, UNIX_EPOCH},
-};
+use std::{ops::ControlFlow, sync::Arc, time::Duration};
 
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Query, State,
     },
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 
 use futures::stream::StreamExt;
 use futures::SinkExt;
 use log::{debug, error, info};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{sync::broadcast::error::RecvError, time::MissedTickBehavior};
 
-use crate::{api, AppState};
-
-// How often to check the battery level
-#[cfg(not(debug_assertions))]
-const BATTERY_CHECK_INTERVAL: Duration = std::time::Duration::from_secs(60);
-#[cfg(debug_assertions)]
-const BATTERY_CHECK_INTERVAL: Duration = std::time::Duration::from_secs(10);
+use crate::api::{self, capture::DeviceSelector};
+use crate::{AppError, AppState};
 
-// How often to send a notification to the client
-#[cfg(not(debug_assertions))]
-const BATTERY_ALERT_INTERVAL: Duration = std::time::Duration::from_secs(60 * 60);
-#[cfg(debug_assertions)]
-const BATTERY_ALERT_INTERVAL: Duration = std::time::Duration::from_secs(60);
+// Updates per second of the input stream
+const DEFAULT_INPUT_RATE: u32 = 60;
+const MAX_INPUT_RATE: u32 = 250;
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
@@ -38,6 +29,142 @@ pub async fn ws_handler(
     ws.on_upgrade(|socket| handle_socket(socket, state))
 }
 
+/// Streams controller connected/disconnected events and battery updates as JSON so the UI can
+/// update right away
+pub async fn events_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(|socket| handle_events_socket(socket, state))
+}
+
+async fn handle_events_socket(socket: WebSocket, state: Arc<AppState>) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut events = state.inventory.subscribe();
+    let mut updates = state.poller.subscribe();
+
+    let mut send_task = tokio::spawn(async move {
+        loop {
+            let message = tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => serde_json::to_string(&event),
+                    Err(RecvError::Lagged(skipped)) => {
+                        debug!("Events socket lagged behind by {} events", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                update = updates.recv() => match update {
+                    Ok(update) => serde_json::to_string(&json!({
+                        "event": "updated",
+                        "controllers": update.controllers,
+                    })),
+                    Err(RecvError::Lagged(skipped)) => {
+                        debug!("Events socket lagged behind by {} updates", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
+            let message = match message {
+                Ok(message) => message,
+                Err(e) => {
+                    error!("Error serializing event: {}", e);
+                    continue;
+                }
+            };
+            if sender.send(Message::Text(message)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = receiver.next().await {
+            if process_message(msg).is_break() {
+                break;
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = (&mut send_task) => recv_task.abort(),
+        _ = (&mut recv_task) => send_task.abort(),
+    }
+
+    debug!("Events websocket context destroyed");
+}
+
+#[derive(Deserialize)]
+pub struct InputParams {
+    // hidraw path or vendor:product, e.g. "054c:0ce6"
+    device: String,
+    rate: Option<u32>,
+}
+
+/// Streams the decoded input (buttons, sticks, touchpad, motion) of one controller as JSON for
+/// the controller tester, at most `rate` times per second and only when it changed
+pub async fn input_handler(
+    ws: WebSocketUpgrade,
+    Query(params): Query<InputParams>,
+) -> Result<Response, AppError> {
+    let selector = DeviceSelector::parse(&params.device)?;
+    let rate = params
+        .rate
+        .unwrap_or(DEFAULT_INPUT_RATE)
+        .clamp(1, MAX_INPUT_RATE);
+    Ok(ws.on_upgrade(move |socket| handle_input_socket(socket, selector, rate)))
+}
+
+async fn handle_input_socket(socket: WebSocket, selector: DeviceSelector, rate: u32) {
+    let (mut sender, mut receiver) = socket.split();
+    // Dropping the receiver stops the reader thread
+    let mut input = api::input::watch(selector);
+    let mut interval = tokio::time::interval(Duration::from_secs(1) / rate);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let (event, closed) = match input.has_changed() {
+                    Ok(false) => continue,
+                    Ok(true) => (input.borrow_and_update().clone(), false),
+                    // The reader stopped, possibly with an error that wasn't sent yet
+                    Err(_) => {
+                        let last = input.borrow_and_update();
+                        (last.has_changed().then(|| last.clone()).flatten(), true)
+                    }
+                };
+                if let Some(event) = event {
+                    let message = match serde_json::to_string(&event) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            error!("Error serializing input: {}", e);
+                            continue;
+                        }
+                    };
+                    if sender.send(Message::Text(message)).await.is_err() {
+                        break;
+                    }
+                }
+                if closed {
+                    break;
+                }
+            }
+            message = receiver.next() => match message {
+                Some(Ok(message)) => {
+                    if process_message(message).is_break() {
+                        break;
+                    }
+                }
+                _ => break,
+            },
+        }
+    }
+
+    debug!("Input websocket context destroyed");
+}
+
 /// Actual websocket statemachine (one will be spawned per connection)
 async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
     // Send a ping to kick things off and get a response
@@ -68,69 +195,27 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
     // By splitting, we can send and receive at the same time.
     let (mut sender, mut receiver) = socket.split();
 
-    // This task will check controllers every 30 seconds and send a message to client if
-    // a controller is low on battery
+    // The battery poller is shared by all clients, this task only forwards its low battery
+    // alerts to this client
+    let mut updates = state.poller.subscribe();
     let mut send_task = tokio::spawn(async move {
-        // HashMap to store last alert timestamps for each controller
-        let mut last_alerts: HashMap<String, u64> = HashMap::new();
         let mut cnt = 0;
 
         loop {
-            tokio::time::sleep(BATTERY_CHECK_INTERVAL).await;
-
-            let settings = state.settings_service.get_settings().await;
-            if !settings.notifications {
-                debug!("Notifications disabled, skipping notification check...");
-                continue;
-            }
-
-            debug!("Checking controllers...");
-            let controllers = match api::controllers_async().await {
-                Ok(controllers) => controllers,
-                Err(e) => {
-                    error!("Error getting controllers: {}", e);
+            let update = match updates.recv().await {
+                Ok(update) => update,
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("Websocket lagged behind by {} updates", skipped);
                     continue;
                 }
+                Err(RecvError::Closed) => return cnt,
             };
 
-            for controller in controllers {
-                let low_battery = controller.capacity < 20 && controller.is_discharging();
-                debug!(
-                    "Controller {} is low battery: {}",
-                    controller.name, low_battery
-                );
-                if low_battery {
-                    let now = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs();
-
-                    let first_alert = !last_alerts.contains_key(&controller.id());
-
-                    let last_alert = last_alerts.entry(controller.id()).or_insert(now);
-
-                    let last_alert_secs_ago = now - *last_alert;
-                    debug!(
-                        "Last alert was {} seconds ago for controller {}",
-                        last_alert_secs_ago, controller.name
-                    );
-
-                    if first_alert || last_alert_secs_ago >= BATTERY_ALERT_INTERVAL.as_secs() {
-                        let message = format!(
-                            "{} is low on battery ({}%)",
-                            controller.name, controller.capacity
-                        );
-                        info!("Sending notification: {}", message);
-
-                        if sender.send(Message::Text(message)).await.is_ok() {
-                            cnt += 1;
-                        } else {
-                            return cnt;
-                        }
-
-                        // Update last alert timestamp
-                        *last_alert = now;
-                    }
+            for message in update.alerts {
+                if sender.send(Message::Text(message)).await.is_ok() {
+                    cnt += 1;
+                } else {
+                    return cnt;
                 }
             }
         }
@@ -1,8 +1,9 @@
 import { callable } from "@decky/api";
-import { IController } from "./types";
+import { IController, IControllerEvent, IInputEvent } from "./types";
 
 const PORT: number = 33220;
 const HOST: string = `http://localhost:${PORT}`;
+const WS_HOST: string = `ws://localhost:${PORT}`;
 
 export const getDebugSetting = async () => await callable<[string, boolean], boolean>("settings_getSetting")("debug", false);
 export const getNotificationsSetting = async () => await callable<[string, boolean], boolean>("settings_getSetting")("notifications", true);
@@ -13,3 +14,21 @@ export const getControllers = async (): Promise<[IController]> => {
   let res = await fetch(`${HOST}/controllers`);
   return await res.json();
 }
+
+// Subscribes to controller connected/disconnected events and battery updates. Returns a function that closes the subscription.
+export const subscribeControllerEvents = (onEvent: (event: IControllerEvent) => void): (() => void) => {
+  const ws = new WebSocket(`${WS_HOST}/events`);
+  ws.onmessage = (e: MessageEvent) => {
+    onEvent(JSON.parse(e.data));
+  };
+  return () => ws.close();
+}
+
+// Streams the decoded input of a controller, given by hidraw path or "vendor:product", at most `rate` times per second. Returns a function that closes the stream.
+export const subscribeControllerInput = (device: string, rate: number, onEvent: (event: IInputEvent) => void): (() => void) => {
+  const ws = new WebSocket(`${WS_HOST}/input?device=${encodeURIComponent(device)}&rate=${rate}`);
+  ws.onmessage = (e: MessageEvent) => {
+    onEvent(JSON.parse(e.data));
+  };
+  return () => ws.close();
+}
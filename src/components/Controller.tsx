@@ -30,20 +30,23 @@ const Controller = ({ controller }: ControllerProps) => {
               <VendorIcon controller={controller}/>
             </IconContext.Provider>
             {controller.name}
+            {controller.extension && <span style={{ opacity: 0.6, marginLeft: '6px' }}>+ {controller.extension}</span>}
           </div>
           {
-            (controller.capacity > 0 || controller.status !== "unknown") &&
-            <div className={gamepadDialogClasses.FieldChildrenInner}>
-              {
-                // only show battery capacity for non-MS vendors unless capacity is > 0 and over BT
-                // since we don't have the battery capacity yet for Xbox over USB
-                (controller.vendorId != 0x045E || (controller.capacity > 0 && controller.bluetooth)) &&
-                <span style={{ display: "inline-block", textAlign: "right", }}>{controller.capacity}%</span>
-              }
-              <IconContext.Provider value={{ style: { verticalAlign: 'middle', marginLeft: "6px" }, size: '2em' }}>
-                <BatteryIcon controller={controller}/>
-              </IconContext.Provider>
-            </div>
+            // A pair of Joy-Cons shows the battery of each half
+            (controller.parts?.length ? controller.parts : [controller]).map((part, index) =>
+              (part.capacity > 0 || part.status !== "unknown") &&
+              <div key={index} className={gamepadDialogClasses.FieldChildrenInner}>
+                {
+                  // Xbox pads on a cable only report a level with xone, 0 means unknown
+                  (part.vendorId != 0x045E || part.capacity > 0) &&
+                  <span style={{ display: "inline-block", textAlign: "right", }}>{part.capacity}%</span>
+                }
+                <IconContext.Provider value={{ style: { verticalAlign: 'middle', marginLeft: "6px" }, size: '2em' }}>
+                  <BatteryIcon controller={part}/>
+                </IconContext.Provider>
+              </div>
+            )
           }
         </div>
       </div>
@@ -12,7 +12,7 @@ const ControllersView = ({ controllers }: ControllersViewProps) => {
       .map(controller => (
         <Controller
           controller={controller}
-          key={`${controller.vendorId}:${controller.productId}`}
+          key={`${controller.vendorId}:${controller.productId}:${controller.name}`}
         />
       ))
   );
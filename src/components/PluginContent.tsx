@@ -27,6 +27,19 @@ const PluginContent = () => {
       .then(notifications => { setNotifications(notifications); });
   }, []);
 
+  // Refresh the list as soon as a controller is connected or disconnected, and whenever the
+  // backend reads the batteries again
+  useEffect(() => {
+    return backend.subscribeControllerEvents(event => {
+      if (event.event === "updated") {
+        setControllers(event.controllers);
+        return;
+      }
+      backend.getControllers()
+        .then(controllers => { setControllers(controllers); });
+    });
+  }, []);
+
   const onRefresh = () => {
     backend
       .getControllers()
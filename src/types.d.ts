@@ -20,4 +20,27 @@ export interface IController {
   capacity: number;
   status: string;
   bluetooth: boolean;
+  // What is plugged into the controller, e.g. the Nunchuk of a Wii Remote
+  extension?: string;
+  // Joy-Cons used together as one pad, each with its own battery
+  parts?: IController[];
 }
+
+export type IControllerEvent =
+  | { event: "connected" | "disconnected"; controller: IController; }
+  | { event: "updated"; controllers: IController[]; };
+
+export interface IGamepadState {
+  buttons: string[];
+  dpad: string;
+  leftStick: { x: number; y: number; };
+  rightStick: { x: number; y: number; };
+  leftTrigger: number;
+  rightTrigger: number;
+  touchPoints: { id: number; x: number; y: number; }[];
+  motion: { gyro: number[]; accel: number[]; timestampUs: number; };
+}
+
+export type IInputEvent =
+  | { event: "state"; state: IGamepadState; }
+  | { event: "error"; message: string; };